@@ -0,0 +1,103 @@
+//! Root computation that yields to the async runtime, enabled by the `tokio`
+//! feature.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use blake2::Digest;
+use tokio::task::yield_now;
+
+use crate::{Hash, MerkleTree, PLAIN};
+
+impl<D: Digest> MerkleTree<D> {
+    /// Computes the same root as [`merkle_root`](Self::merkle_root) without
+    /// holding the executor for the whole tree: leaves and then each level
+    /// are hashed `batch_size` hashes at a time, with a
+    /// [`yield_now`](tokio::task::yield_now) between batches so other tasks
+    /// can run.
+    ///
+    /// Panics if `batch_size` is 0.
+    pub async fn merkle_root_async<I, L>(leaves: I, batch_size: usize) -> String
+    where
+        I: Iterator<Item = L>,
+        L: AsRef<[u8]>,
+    {
+        assert!(batch_size > 0, "batch size must be at least 1");
+
+        let mut level: Vec<Hash> = Vec::new();
+        for leaf in leaves {
+            level.push(Self::hash_leaf(leaf));
+            if level.len().is_multiple_of(batch_size) {
+                yield_now().await;
+            }
+        }
+
+        while level.len() > 1 {
+            let mut next_level = Vec::with_capacity(level.len().div_ceil(2));
+            for batch in level.chunks(2 * batch_size) {
+                next_level.extend(batch.chunks(2).map(|chunk| Self::hash_chunk(&PLAIN, chunk)));
+                yield_now().await;
+            }
+            level = next_level;
+        }
+
+        level
+            .pop()
+            .map_or_else(Self::empty_root, |root| root.to_hex())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    use sha2::Sha256;
+
+    use crate::{Blake2bTree, MerkleTree};
+
+    /// Polls `future` to completion, counting how often it yielded.
+    fn run<F: Future>(future: F) -> (F::Output, usize) {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut yields = 0;
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return (output, yields),
+                Poll::Pending => yields += 1,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_root_matches_sync() {
+        for n in [0, 1, 2, 3, 7, 64, 1000] {
+            let leaves: Vec<String> = (0..n).map(|i| format!("leaf-{i}")).collect();
+            let expected = Blake2bTree::merkle_root(leaves.iter());
+            for batch_size in [1, 3, 16, 5000] {
+                assert_eq!(
+                    Blake2bTree::merkle_root_async(leaves.iter(), batch_size).await,
+                    expected,
+                    "{n} leaves, batches of {batch_size}"
+                );
+            }
+            assert_eq!(
+                MerkleTree::<Sha256>::merkle_root_async(leaves.iter(), 10).await,
+                MerkleTree::<Sha256>::merkle_root(leaves.iter())
+            );
+        }
+    }
+
+    #[test]
+    fn test_yields_between_batches() {
+        let leaves: Vec<String> = (0..1024).map(|i| format!("leaf-{i}")).collect();
+        let (small, small_yields) = run(Blake2bTree::merkle_root_async(leaves.iter(), 8));
+        let (large, large_yields) = run(Blake2bTree::merkle_root_async(leaves.iter(), 512));
+        assert_eq!(small, large);
+        // 128 leaf batches plus 64 + 32 + ... + 1 node batches, then one per
+        // level once a level fits in a single batch.
+        assert_eq!(small_yields, 128 + 127 + 3);
+        assert_eq!(large_yields, 2 + 10);
+    }
+}
@@ -0,0 +1,234 @@
+//! Merkle branches in the format Bitcoin SPV clients use, enabled by the
+//! `sha2` feature.
+//!
+//! Bitcoin's transaction tree differs from this crate's own in every
+//! respect, so it gets its own functions rather than a [`TreeOptions`]
+//! setting:
+//!
+//! - the leaves are the transaction ids themselves, with no leaf hashing,
+//! - a node is `SHA256(SHA256(left || right))` over raw bytes, with no prefix,
+//! - a level with an odd number of nodes pairs its last node with itself,
+//! - hashes are written in hex with their bytes reversed, as block explorers
+//!   and RPC interfaces show them.
+//!
+//! A [`BitcoinBranch`] is the sibling hashes bottom-up plus the index of the
+//! transaction, as returned by Electrum's `blockchain.transaction.get_merkle`.
+//! Siblings that are duplicates of the node itself are included.
+//!
+//! [`TreeOptions`]: crate::TreeOptions
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use sha2::{Digest, Sha256};
+
+use crate::{ProofError, VerifyError};
+
+type Hash256 = [u8; 32];
+
+/// A Bitcoin merkle branch: sibling hashes from the transaction up, as
+/// byte-reversed hex, and the transaction's index in the block.
+#[derive(Debug, Clone, PartialEq, Eq, core::hash::Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BitcoinBranch {
+    pub hashes: Vec<String>,
+    pub index: usize,
+}
+
+/// The merkle root of a block with `txids`, each byte-reversed hex as
+/// explorers show them, in the same notation. Without transactions, which
+/// no real block has, the root is all zeros.
+///
+/// Panics if a txid is not 64 hex digits.
+pub fn bitcoin_merkle_root<I>(txids: I) -> String
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    let mut level = decode_txids(txids);
+    if level.is_empty() {
+        return display_hex(&[0; 32]);
+    }
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    display_hex(&level[0])
+}
+
+/// The branch proving that the transaction at `index` is among `txids`.
+///
+/// Panics if a txid is not 64 hex digits.
+pub fn bitcoin_merkle_branch<I>(txids: I, index: usize) -> Result<BitcoinBranch, ProofError>
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    let mut level = decode_txids(txids);
+    if level.is_empty() {
+        return Err(ProofError::EmptyTree);
+    }
+    if index >= level.len() {
+        return Err(ProofError::IndexOutOfRange {
+            index,
+            len: level.len(),
+        });
+    }
+
+    let mut hashes = Vec::new();
+    let mut position = index;
+    while level.len() > 1 {
+        // A lone last node is its own sibling.
+        let sibling = level.get(position ^ 1).unwrap_or(&level[position]);
+        hashes.push(display_hex(sibling));
+        level = next_level(&level);
+        position /= 2;
+    }
+    Ok(BitcoinBranch { hashes, index })
+}
+
+/// Checks that `branch` leads from `txid` to `root`, both byte-reversed hex.
+/// Hashes that are not 64 hex digits fail with
+/// [`VerifyError::RootMismatch`].
+pub fn verify_bitcoin_branch(
+    root: impl AsRef<str>,
+    txid: impl AsRef<str>,
+    branch: &BitcoinBranch,
+) -> Result<(), VerifyError> {
+    let mut hash = parse_display_hex(txid.as_ref()).ok_or(VerifyError::RootMismatch)?;
+    let mut position = branch.index;
+    for sibling in &branch.hashes {
+        let sibling = parse_display_hex(sibling).ok_or(VerifyError::RootMismatch)?;
+        hash = if position % 2 == 1 {
+            hash_pair(&sibling, &hash)
+        } else {
+            hash_pair(&hash, &sibling)
+        };
+        position /= 2;
+    }
+
+    // Leftover index bits would place the transaction beyond the tree.
+    if position == 0 && display_hex(&hash) == root.as_ref() {
+        Ok(())
+    } else {
+        Err(VerifyError::RootMismatch)
+    }
+}
+
+fn decode_txids<I>(txids: I) -> Vec<Hash256>
+where
+    I: Iterator,
+    I::Item: AsRef<str>,
+{
+    txids
+        .map(|txid| {
+            let txid = txid.as_ref();
+            parse_display_hex(txid).unwrap_or_else(|| panic!("{txid:?} is not a txid"))
+        })
+        .collect()
+}
+
+fn next_level(level: &[Hash256]) -> Vec<Hash256> {
+    level
+        .chunks(2)
+        .map(|chunk| match chunk {
+            [left, right] => hash_pair(left, right),
+            [lone] => hash_pair(lone, lone),
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+/// `SHA256(SHA256(left || right))`.
+fn hash_pair(left: &Hash256, right: &Hash256) -> Hash256 {
+    let once = Sha256::new()
+        .chain_update(left)
+        .chain_update(right)
+        .finalize();
+    Sha256::digest(once).into()
+}
+
+/// Hex of the bytes in reverse order.
+fn display_hex(hash: &Hash256) -> String {
+    let mut reversed = *hash;
+    reversed.reverse();
+    hex::encode(reversed)
+}
+
+fn parse_display_hex(hex: &str) -> Option<Hash256> {
+    let mut hash = [0; 32];
+    hex::decode_to_slice(hex, &mut hash).ok()?;
+    hash.reverse();
+    Some(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The transactions of block 100000.
+    const BLOCK_100000: [&str; 4] = [
+        "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+        "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+        "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+        "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+    ];
+    const BLOCK_100000_ROOT: &str =
+        "f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766";
+
+    #[test]
+    fn test_block_100000() {
+        assert_eq!(bitcoin_merkle_root(BLOCK_100000.iter()), BLOCK_100000_ROOT);
+
+        for (index, txid) in BLOCK_100000.iter().enumerate() {
+            let branch = bitcoin_merkle_branch(BLOCK_100000.iter(), index).unwrap();
+            assert_eq!(branch.hashes.len(), 2);
+            assert_eq!(branch.hashes[0], BLOCK_100000[index ^ 1]);
+            assert!(verify_bitcoin_branch(BLOCK_100000_ROOT, txid, &branch).is_ok());
+            assert_eq!(
+                verify_bitcoin_branch(BLOCK_100000_ROOT, BLOCK_100000[(index + 1) % 4], &branch),
+                Err(VerifyError::RootMismatch)
+            );
+        }
+    }
+
+    #[test]
+    fn test_odd_levels_duplicate() {
+        let txids = &BLOCK_100000[..3];
+        let duplicated = [txids[0], txids[1], txids[2], txids[2]];
+        let root = bitcoin_merkle_root(txids.iter());
+        assert_eq!(root, bitcoin_merkle_root(duplicated.iter()));
+
+        let branch = bitcoin_merkle_branch(txids.iter(), 2).unwrap();
+        assert_eq!(branch.hashes[0], txids[2]);
+        assert!(verify_bitcoin_branch(&root, txids[2], &branch).is_ok());
+
+        let mut beyond = branch.clone();
+        beyond.index += 4;
+        assert_eq!(
+            verify_bitcoin_branch(&root, txids[2], &beyond),
+            Err(VerifyError::RootMismatch)
+        );
+    }
+
+    #[test]
+    fn test_single_transaction_and_errors() {
+        let txid = BLOCK_100000[0];
+        assert_eq!(bitcoin_merkle_root([txid].iter()), txid);
+        let branch = bitcoin_merkle_branch([txid].iter(), 0).unwrap();
+        assert!(branch.hashes.is_empty());
+        assert!(verify_bitcoin_branch(txid, txid, &branch).is_ok());
+
+        assert_eq!(
+            bitcoin_merkle_branch(BLOCK_100000.iter(), 4),
+            Err(ProofError::IndexOutOfRange { index: 4, len: 4 })
+        );
+        assert_eq!(
+            bitcoin_merkle_branch(core::iter::empty::<&str>(), 0),
+            Err(ProofError::EmptyTree)
+        );
+        assert_eq!(
+            verify_bitcoin_branch(BLOCK_100000_ROOT, "not hex", &branch),
+            Err(VerifyError::RootMismatch)
+        );
+    }
+}
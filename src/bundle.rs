@@ -0,0 +1,197 @@
+//! Many proofs of one tree stored with each sibling hash once.
+//!
+//! Proofs of the same tree repeat the hashes near the root: every proof
+//! carries one of the root's two children, and so on down. A [`ProofBundle`]
+//! keeps each distinct hash once in a table and has every proof refer to it
+//! by position, so `n` proofs of a depth-`d` tree take at most `2n - 2`
+//! hashes instead of `n * d`.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{Hash, MerkleProof};
+
+/// A batch of proofs sharing one table of sibling hashes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProofBundle<T> {
+    /// Every distinct sibling hash, in the order first added.
+    hashes: Vec<Hash>,
+    proofs: Vec<BundledProof<T>>,
+    /// Position of each hash in `hashes`, rebuilt on demand after
+    /// deserializing.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    positions: BTreeMap<Hash, usize>,
+}
+
+/// A [`MerkleProof`] whose hashes are positions in the bundle's table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct BundledProof<T> {
+    hashes: Vec<usize>,
+    num_of_leaves: usize,
+    leaf_index: usize,
+    leaf_content: T,
+    #[cfg_attr(feature = "serde", serde(default))]
+    path_bits: Vec<bool>,
+    root: Option<String>,
+}
+
+impl<T> ProofBundle<T> {
+    /// An empty bundle.
+    pub fn new() -> Self {
+        ProofBundle {
+            hashes: Vec::new(),
+            proofs: Vec::new(),
+            positions: BTreeMap::new(),
+        }
+    }
+
+    /// Adds `proof`, storing only the hashes the table lacks, and returns its
+    /// position for [`proof`](Self::proof).
+    pub fn add(&mut self, proof: MerkleProof<T>) -> usize {
+        if self.positions.len() != self.hashes.len() {
+            self.positions = self
+                .hashes
+                .iter()
+                .enumerate()
+                .map(|(position, hash)| (*hash, position))
+                .collect();
+        }
+
+        let hashes = proof
+            .hashes
+            .iter()
+            .map(|hash| {
+                *self.positions.entry(*hash).or_insert_with(|| {
+                    self.hashes.push(*hash);
+                    self.hashes.len() - 1
+                })
+            })
+            .collect();
+        self.proofs.push(BundledProof {
+            hashes,
+            num_of_leaves: proof.num_of_leaves,
+            leaf_index: proof.leaf_index,
+            leaf_content: proof.leaf_content,
+            path_bits: proof.path_bits,
+            root: proof.root,
+        });
+        self.proofs.len() - 1
+    }
+
+    /// The `index`th proof added, expanded back into a [`MerkleProof`], or
+    /// `None` if there is no such proof or, in a deserialized bundle, it
+    /// refers to a hash the table lacks.
+    pub fn proof(&self, index: usize) -> Option<MerkleProof<T>>
+    where
+        T: Clone,
+    {
+        let bundled = self.proofs.get(index)?;
+        Some(MerkleProof {
+            hashes: bundled
+                .hashes
+                .iter()
+                .map(|&position| self.hashes.get(position).copied())
+                .collect::<Option<_>>()?,
+            num_of_leaves: bundled.num_of_leaves,
+            leaf_index: bundled.leaf_index,
+            leaf_content: bundled.leaf_content.clone(),
+            path_bits: bundled.path_bits.clone(),
+            root: bundled.root.clone(),
+        })
+    }
+
+    /// Number of proofs in the bundle.
+    pub fn len(&self) -> usize {
+        self.proofs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.proofs.is_empty()
+    }
+
+    /// Number of distinct hashes stored for all the proofs together.
+    pub fn hash_count(&self) -> usize {
+        self.hashes.len()
+    }
+}
+
+impl<T> Default for ProofBundle<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> FromIterator<MerkleProof<T>> for ProofBundle<T> {
+    fn from_iter<I: IntoIterator<Item = MerkleProof<T>>>(proofs: I) -> Self {
+        let mut bundle = ProofBundle::new();
+        for proof in proofs {
+            bundle.add(proof);
+        }
+        bundle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Blake2bTree, MerkleTreeBuilder};
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let data: Vec<String> = (0..64).map(|i| format!("leaf-{i}")).collect();
+        let tree = MerkleTreeBuilder::<String>::new(data.into_iter());
+        let root = tree.root_hex();
+
+        let bundle: ProofBundle<String> = tree.all_proofs().collect();
+        assert_eq!(bundle.len(), 64);
+        // Every node but the root is some leaf's sibling, against 64 * 6
+        // hashes stored separately.
+        assert_eq!(bundle.hash_count(), 2 * 64 - 2);
+
+        for i in 0..bundle.len() {
+            let proof = bundle.proof(i).unwrap();
+            assert_eq!(proof, tree.proof(i).unwrap());
+            assert!(Blake2bTree::verify_proof(&root, &proof).is_ok());
+        }
+        assert_eq!(bundle.proof(64), None);
+    }
+
+    #[test]
+    fn test_bundle_keeps_order_and_roots() {
+        let data = ["a", "b", "c", "d", "e"];
+        let tree = MerkleTreeBuilder::<&str>::new(data.into_iter());
+        let mut bundle = ProofBundle::new();
+        assert!(bundle.is_empty());
+
+        assert_eq!(bundle.add(tree.bundled_proof(3).unwrap()), 0);
+        assert_eq!(bundle.add(tree.proof(0).unwrap()), 1);
+        assert_eq!(bundle.add(tree.proof(3).unwrap()), 2);
+        assert_eq!(bundle.proof(0).unwrap(), tree.bundled_proof(3).unwrap());
+        assert!(bundle.proof(0).unwrap().verify().is_ok());
+        assert_eq!(bundle.proof(1).unwrap(), tree.proof(0).unwrap());
+        // Leaf 3's hashes were already in the table.
+        assert_eq!(bundle.hash_count(), 3 + 2);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_bundle_json_round_trip() {
+        let data = ["a", "b", "c", "d", "e"];
+        let tree = MerkleTreeBuilder::<String>::new(data.iter().map(|s| s.to_string()));
+        let bundle: ProofBundle<String> = tree.all_proofs().take(3).collect();
+
+        let json = serde_json::to_string(&bundle).unwrap();
+        let mut decoded: ProofBundle<String> = serde_json::from_str(&json).unwrap();
+        for i in 0..3 {
+            assert_eq!(decoded.proof(i), bundle.proof(i));
+        }
+
+        // The lookup table is rebuilt, so known hashes are not stored again.
+        let hash_count = decoded.hash_count();
+        decoded.add(tree.proof(1).unwrap());
+        assert_eq!(decoded.hash_count(), hash_count);
+    }
+}
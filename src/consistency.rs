@@ -0,0 +1,286 @@
+//! Proofs that a tree is an append-only extension of an earlier one.
+//!
+//! A consistency proof between sizes `m` and `n` lets a client that only
+//! holds the root over the first `m` leaves check that the root over `n`
+//! leaves extends it, in the spirit of RFC 6962 section 2.1.2. The proof
+//! lists the roots of the perfect subtrees that make up the old tree, from
+//! which the verifier rebuilds the old root, followed by the siblings needed
+//! to climb from those subtrees to the new root. When `m` is a power of two
+//! the old tree is a single subtree and its root is not repeated in the
+//! proof. Either way the proof holds O(log n) hashes.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use blake2::Digest;
+
+use crate::multiproof::pairs_with_next;
+use crate::streaming::bag_subtrees;
+use crate::{Hash, MerkleTree, MerkleTreeBuilder, ProofError};
+
+impl<D: Digest> MerkleTree<D> {
+    /// Proves that the tree over the first `m` items of `leaves` is a prefix
+    /// of the tree over the first `n`.
+    pub fn consistency_proof<I, L>(leaves: I, m: usize, n: usize) -> Result<Vec<Hash>, ProofError>
+    where
+        I: Iterator<Item = L>,
+        L: AsRef<[u8]>,
+    {
+        let tree = MerkleTreeBuilder::<L, D>::new(leaves.take(n));
+        if m == 0 || m > n || tree.leaf_count() < n {
+            return Err(ProofError::InvalidTreeSizes {
+                old_size: m,
+                new_size: n,
+            });
+        }
+
+        let mut proof = Vec::new();
+        if m == n {
+            return Ok(proof);
+        }
+
+        let peaks: Vec<(usize, usize, Hash)> = peak_positions(m)
+            .into_iter()
+            .map(|(height, index)| (height, index, tree.levels[height][index]))
+            .collect();
+        if !m.is_power_of_two() {
+            proof.extend(peaks.iter().map(|&(_, _, hash)| hash));
+        }
+
+        climb::<D>(n, peaks, |height, index| {
+            let sibling = tree.levels[height][index];
+            proof.push(sibling);
+            Some(sibling)
+        });
+        Ok(proof)
+    }
+
+    /// Checks that `proof` shows the tree of `n` leaves with `new_root`
+    /// extends the tree of its first `m` leaves with `old_root`.
+    pub fn verify_consistency(
+        old_root: impl AsRef<str>,
+        new_root: impl AsRef<str>,
+        proof: &[Hash],
+        m: usize,
+        n: usize,
+    ) -> bool {
+        let (old_root, new_root) = (old_root.as_ref(), new_root.as_ref());
+        if m == 0 || m > n {
+            return false;
+        }
+        if m == n {
+            return proof.is_empty() && old_root == new_root;
+        }
+
+        let positions = peak_positions(m);
+        let mut proof = proof.iter().copied();
+        let peaks: Vec<(usize, usize, Hash)> = if m.is_power_of_two() {
+            let Some(root) = hex::decode(old_root)
+                .ok()
+                .and_then(|bytes| Hash::from_slice(&bytes))
+            else {
+                return false;
+            };
+            vec![(positions[0].0, positions[0].1, root)]
+        } else {
+            let hashes: Vec<Hash> = proof.by_ref().take(positions.len()).collect();
+            if hashes.len() != positions.len() {
+                return false;
+            }
+            let subtrees: Vec<(usize, Hash)> = positions
+                .iter()
+                .zip(&hashes)
+                .map(|(&(height, _), &hash)| (height, hash))
+                .collect();
+            if bag_subtrees::<D>(&subtrees)
+                .map(|root| root.to_hex())
+                .as_deref()
+                != Some(old_root)
+            {
+                return false;
+            }
+            positions
+                .iter()
+                .zip(hashes)
+                .map(|(&(height, index), hash)| (height, index, hash))
+                .collect()
+        };
+
+        let computed = climb::<D>(n, peaks, |_, _| proof.next());
+        proof.next().is_none() && computed.is_some_and(|root| root.to_hex() == new_root)
+    }
+}
+
+/// `(height, index)` of the perfect subtrees covering the first `m` leaves,
+/// tallest (leftmost) first.
+fn peak_positions(m: usize) -> Vec<(usize, usize)> {
+    let mut offset = 0;
+    (0..usize::BITS as usize)
+        .rev()
+        .filter(|&height| (m >> height) & 1 == 1)
+        .map(|height| {
+            let position = (height, offset >> height);
+            offset += 1 << height;
+            position
+        })
+        .collect()
+}
+
+/// Computes the root of a tree of `num_of_leaves` leaves from the `peaks`
+/// of a prefix of it (`(height, index, hash)`, tallest first), asking
+/// `sibling(level, index)` for every other node the climb needs.
+///
+/// Returns `None` if `sibling` runs out of hashes.
+fn climb<D: Digest>(
+    num_of_leaves: usize,
+    mut peaks: Vec<(usize, usize, Hash)>,
+    mut sibling: impl FnMut(usize, usize) -> Option<Hash>,
+) -> Option<Hash> {
+    let mut known: Vec<(usize, Hash)> = Vec::new();
+    let mut len = num_of_leaves;
+    let mut height = 0;
+
+    loop {
+        // Taller peaks lie further left, so each one joins at the front.
+        if let Some(&(peak_height, index, hash)) = peaks.last() {
+            if peak_height == height {
+                peaks.pop();
+                known.insert(0, (index, hash));
+            }
+        }
+        if len <= 1 {
+            break;
+        }
+
+        let mut next_level = Vec::with_capacity(known.len());
+        let mut i = 0;
+        while i < known.len() {
+            let (index, hash) = known[i];
+            let parent = if pairs_with_next(&known, i, |&(index, _)| index) {
+                i += 1;
+                MerkleTree::<D>::hash_node(&hash, &known[i].1)
+            } else if index ^ 1 >= len {
+                hash
+            } else {
+                let other = sibling(height, index ^ 1)?;
+                if index.is_multiple_of(2) {
+                    MerkleTree::<D>::hash_node(&hash, &other)
+                } else {
+                    MerkleTree::<D>::hash_node(&other, &hash)
+                }
+            };
+            next_level.push((index / 2, parent));
+            i += 1;
+        }
+        known = next_level;
+        len = len.div_ceil(2);
+        height += 1;
+    }
+
+    match known.as_slice() {
+        [(_, root)] if peaks.is_empty() => Some(*root),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tree_depth, Blake2bTree};
+
+    fn leaves(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("leaf-{i}")).collect()
+    }
+
+    #[test]
+    fn test_consistency_across_power_of_two_boundaries() {
+        let data = leaves(33);
+        for n in 1..=data.len() {
+            let new_root = Blake2bTree::merkle_root(data[..n].iter());
+            for m in 1..=n {
+                let old_root = Blake2bTree::merkle_root(data[..m].iter());
+                let proof = Blake2bTree::consistency_proof(data.iter(), m, n).unwrap();
+                assert!(proof.len() <= 2 * tree_depth(n) + 1, "m = {m}, n = {n}");
+                assert!(
+                    Blake2bTree::verify_consistency(&old_root, &new_root, &proof, m, n),
+                    "m = {m}, n = {n}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_power_of_two_old_tree_omits_its_root() {
+        let data = leaves(12);
+        let old_root = Blake2bTree::merkle_root(data[..8].iter());
+        let proof = Blake2bTree::consistency_proof(data.iter(), 8, 12).unwrap();
+        assert!(proof.iter().all(|hash| hash.to_hex() != old_root));
+    }
+
+    #[test]
+    fn test_consistency_rejects_forks() {
+        let data = leaves(20);
+        let mut forked = data.clone();
+        forked[3] = "rewritten".to_string();
+
+        let old_root = Blake2bTree::merkle_root(data[..7].iter());
+        let forked_root = Blake2bTree::merkle_root(forked.iter());
+        let new_root = Blake2bTree::merkle_root(data.iter());
+        let proof = Blake2bTree::consistency_proof(data.iter(), 7, 20).unwrap();
+        let forked_proof = Blake2bTree::consistency_proof(forked.iter(), 7, 20).unwrap();
+
+        assert!(Blake2bTree::verify_consistency(
+            &old_root, &new_root, &proof, 7, 20
+        ));
+        assert!(!Blake2bTree::verify_consistency(
+            &old_root,
+            &forked_root,
+            &forked_proof,
+            7,
+            20
+        ));
+        assert!(!Blake2bTree::verify_consistency(
+            &old_root,
+            &forked_root,
+            &proof,
+            7,
+            20
+        ));
+        assert!(!Blake2bTree::verify_consistency(
+            &old_root, &new_root, &proof, 6, 20
+        ));
+        assert!(!Blake2bTree::verify_consistency(
+            &old_root,
+            &new_root,
+            &proof[1..],
+            7,
+            20
+        ));
+
+        let mut padded = proof.clone();
+        padded.push(proof[0]);
+        assert!(!Blake2bTree::verify_consistency(
+            &old_root, &new_root, &padded, 7, 20
+        ));
+    }
+
+    #[test]
+    fn test_consistency_invalid_sizes() {
+        let data = leaves(5);
+        for (m, n) in [(0, 3), (4, 3), (2, 6)] {
+            assert_eq!(
+                Blake2bTree::consistency_proof(data.iter(), m, n).unwrap_err(),
+                ProofError::InvalidTreeSizes {
+                    old_size: m,
+                    new_size: n
+                }
+            );
+        }
+        let root = Blake2bTree::merkle_root(data.iter());
+        assert!(Blake2bTree::consistency_proof(data.iter(), 5, 5)
+            .unwrap()
+            .is_empty());
+        assert!(Blake2bTree::verify_consistency(&root, &root, &[], 5, 5));
+        assert!(!Blake2bTree::verify_consistency(&root, &root, &[], 0, 5));
+    }
+}
@@ -0,0 +1,209 @@
+//! Trees hashed by closures chosen at runtime.
+//!
+//! The digest-generic API fixes the leaf and node hash at compile time. A
+//! [`DynHasher`] boxes a leaf hasher and a node hasher instead, so plugins
+//! can decide per leaf how to hash it. Levels are built exactly as in
+//! [`MerkleTreeBuilder`](crate::MerkleTreeBuilder), with a trailing odd node
+//! promoted unchanged, and every closure output must be at most
+//! [`MAX_HASH_LEN`](crate::MAX_HASH_LEN) bytes.
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{proof_directions, sibling_path, Hash, MerkleProof, ProofError, Root, VerifyError};
+
+type LeafFn<'a> = dyn FnMut(&[u8]) -> Vec<u8> + 'a;
+type NodeFn<'a> = dyn FnMut(&[u8], &[u8]) -> Vec<u8> + 'a;
+
+/// A leaf hasher and a node hasher, shared by whoever builds a [`DynTree`]
+/// and whoever verifies its proofs.
+pub struct DynHasher<'a> {
+    leaf: Box<LeafFn<'a>>,
+    node: Box<NodeFn<'a>>,
+}
+
+impl<'a> DynHasher<'a> {
+    /// Hashes leaves with `leaf` and the two children of every internal node,
+    /// left then right, with `node`.
+    pub fn new(
+        leaf: impl FnMut(&[u8]) -> Vec<u8> + 'a,
+        node: impl FnMut(&[u8], &[u8]) -> Vec<u8> + 'a,
+    ) -> Self {
+        DynHasher {
+            leaf: Box::new(leaf),
+            node: Box::new(node),
+        }
+    }
+
+    /// Checks `proof` against `root` like
+    /// [`MerkleTree::verify_proof`](crate::MerkleTree::verify_proof), hashing
+    /// with these closures.
+    ///
+    /// Panics if a closure returns more than [`MAX_HASH_LEN`](crate::MAX_HASH_LEN) bytes.
+    pub fn verify_proof<T: AsRef<[u8]>>(
+        &mut self,
+        root: impl AsRef<str>,
+        proof: &MerkleProof<T>,
+    ) -> Result<(), VerifyError> {
+        proof.check_shape()?;
+        let directions = proof_directions(proof.leaf_index, proof.num_of_leaves);
+        let mut hash = self.hash_leaf(proof.leaf_content.as_ref());
+        for (sibling, is_right) in proof.hashes.iter().zip(directions) {
+            hash = if is_right {
+                self.hash_node(sibling, &hash)
+            } else {
+                self.hash_node(&hash, sibling)
+            };
+        }
+
+        if root.as_ref() == hash.to_hex() {
+            Ok(())
+        } else {
+            Err(VerifyError::RootMismatch)
+        }
+    }
+
+    fn hash_leaf(&mut self, leaf: &[u8]) -> Hash {
+        closure_hash((self.leaf)(leaf))
+    }
+
+    fn hash_node(&mut self, left: &Hash, right: &Hash) -> Hash {
+        closure_hash((self.node)(left.as_bytes(), right.as_bytes()))
+    }
+}
+
+fn closure_hash(bytes: Vec<u8>) -> Hash {
+    Hash::from_slice(&bytes).unwrap_or_else(|| {
+        panic!(
+            "hasher output of {} bytes exceeds MAX_HASH_LEN",
+            bytes.len()
+        )
+    })
+}
+
+/// A tree whose levels were hashed by a [`DynHasher`].
+pub struct DynTree<L> {
+    leaves: Vec<L>,
+    levels: Vec<Vec<Hash>>,
+}
+
+impl<L: AsRef<[u8]>> DynTree<L> {
+    /// Hashes `leaves` and every level above them with `hasher`.
+    ///
+    /// Panics if a closure returns more than [`MAX_HASH_LEN`](crate::MAX_HASH_LEN) bytes.
+    pub fn new(leaves: impl IntoIterator<Item = L>, hasher: &mut DynHasher<'_>) -> Self {
+        let leaves: Vec<L> = leaves.into_iter().collect();
+        let mut levels = vec![leaves
+            .iter()
+            .map(|leaf| hasher.hash_leaf(leaf.as_ref()))
+            .collect::<Vec<Hash>>()];
+
+        while levels[levels.len() - 1].len() > 1 {
+            let next_level = levels[levels.len() - 1]
+                .chunks(2)
+                .map(|chunk| match chunk {
+                    [left, right] => hasher.hash_node(left, right),
+                    [lone] => *lone,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next_level);
+        }
+
+        DynTree { leaves, levels }
+    }
+
+    /// The root, or `None` for a tree without leaves, which closures give no
+    /// hash for.
+    pub fn root(&self) -> Option<Root> {
+        self.levels[self.levels.len() - 1]
+            .first()
+            .copied()
+            .map(Root)
+    }
+
+    /// Builds the inclusion proof for the leaf at `leaf_index`.
+    pub fn proof(&self, leaf_index: usize) -> Result<MerkleProof<L>, ProofError>
+    where
+        L: Clone,
+    {
+        Ok(MerkleProof {
+            hashes: sibling_path(&self.levels, leaf_index)?,
+            num_of_leaves: self.leaves.len(),
+            leaf_index,
+            leaf_content: self.leaves[leaf_index].clone(),
+            path_bits: proof_directions(leaf_index, self.leaves.len()),
+            root: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blake2::{Blake2b512, Digest};
+
+    use super::*;
+    use crate::Blake2bTree;
+
+    /// Closures reproducing the built-in Blake2b-512 leaf and node hashes.
+    fn blake2b() -> DynHasher<'static> {
+        DynHasher::new(
+            |leaf| Blake2b512::digest([&[0x00], leaf].concat()).to_vec(),
+            |left, right| {
+                let children = [hex::encode(left), hex::encode(right)].concat();
+                Blake2b512::digest([b"\x01", children.as_bytes()].concat()).to_vec()
+            },
+        )
+    }
+
+    #[test]
+    fn test_closures_match_built_in_hashing() {
+        let data: Vec<String> = (0..11).map(|i| format!("leaf-{i}")).collect();
+        let mut hasher = blake2b();
+        let tree = DynTree::new(data.iter(), &mut hasher);
+        let root = tree.root().unwrap().to_hex();
+        assert_eq!(root, Blake2bTree::merkle_root(data.iter()));
+
+        for i in 0..data.len() {
+            let proof = tree.proof(i).unwrap();
+            assert_eq!(proof, Blake2bTree::merkle_proof(data.iter(), i).unwrap());
+            assert_eq!(hasher.verify_proof(&root, &proof), Ok(()));
+        }
+
+        let mut forged = tree.proof(3).unwrap();
+        forged.leaf_content = &data[4];
+        assert_eq!(
+            hasher.verify_proof(&root, &forged),
+            Err(VerifyError::RootMismatch)
+        );
+        assert!(DynTree::new(Vec::<&str>::new(), &mut hasher)
+            .root()
+            .is_none());
+    }
+
+    #[test]
+    fn test_leaf_hashing_decided_at_runtime() {
+        // Leaves of exactly 64 bytes are taken as already committed.
+        let mut plain = blake2b();
+        let mut hasher = DynHasher::new(
+            move |leaf| {
+                if leaf.len() == 64 {
+                    leaf.to_vec()
+                } else {
+                    plain.hash_leaf(leaf).as_bytes().to_vec()
+                }
+            },
+            |left, right| Blake2b512::digest([left, right].concat()).to_vec(),
+        );
+        let committed = Blake2b512::digest(b"elsewhere").to_vec();
+        let leaves = [b"raw".to_vec(), committed.clone(), b"also raw".to_vec()];
+        let tree = DynTree::new(leaves.iter(), &mut hasher);
+        assert_eq!(tree.levels[0][1].as_bytes(), committed);
+
+        let root = tree.root().unwrap().to_hex();
+        for i in 0..leaves.len() {
+            assert!(hasher.verify_proof(&root, &tree.proof(i).unwrap()).is_ok());
+        }
+    }
+}
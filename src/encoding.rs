@@ -0,0 +1,318 @@
+//! Compact binary encoding of [`MerkleProof`].
+//!
+//! The wire format is, in order:
+//!
+//! 1. `num_of_leaves` as an unsigned LEB128 varint,
+//! 2. `leaf_index` as a varint,
+//! 3. the number of sibling hashes as a varint,
+//! 4. the length in bytes of each hash, one byte (at most [`MAX_HASH_LEN`]),
+//! 5. the sibling hashes back to back, bottom level first,
+//! 6. the leaf content, filling the rest of the buffer.
+//!
+//! Varints are little-endian groups of seven bits with the high bit set on
+//! every byte but the last, in as few bytes as the value needs. A proof with
+//! no hashes has a hash length of zero. Decoding accepts only this canonical
+//! form, so every proof has exactly one encoding.
+//!
+//! A bundled [`root`](MerkleProof::root) is not encoded; decoded proofs carry
+//! none. Nor are the [`path_bits`](MerkleProof::path_bits): decoding derives
+//! them from the encoded leaf index and count.
+
+use core::fmt;
+
+use alloc::vec::Vec;
+
+use crate::{proof_directions, Hash, MerkleProof, MAX_HASH_LEN};
+
+/// Reasons [`MerkleProof::from_bytes`] can reject its input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input ended before the field being read.
+    Truncated,
+    /// A varint does not fit in a `usize`.
+    VarintOverflow,
+    /// The declared hash length exceeds [`MAX_HASH_LEN`].
+    HashTooLong(usize),
+    /// Sibling hashes are declared with a length of zero.
+    EmptyHash,
+    /// The input is not the canonical encoding: a varint padded with zero
+    /// groups, or a non-zero hash length for no hashes.
+    NonCanonical,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Truncated => write!(f, "encoded proof is truncated"),
+            DecodeError::VarintOverflow => write!(f, "varint does not fit in a usize"),
+            DecodeError::HashTooLong(len) => {
+                write!(f, "hash length {len} exceeds {MAX_HASH_LEN} bytes")
+            }
+            DecodeError::EmptyHash => write!(f, "sibling hashes are declared empty"),
+            DecodeError::NonCanonical => write!(f, "encoded proof is not in canonical form"),
+        }
+    }
+}
+
+impl core::error::Error for DecodeError {}
+
+/// Reasons [`MerkleProof::to_bytes`] can refuse to encode a proof.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeError {
+    /// The sibling hash at `index` is not as long as the first one, which
+    /// the single hash-length byte cannot describe.
+    HashLengthMismatch {
+        index: usize,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::HashLengthMismatch {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "sibling hash {index} is {actual} bytes, expected {expected}"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for EncodeError {}
+
+impl<T: AsRef<[u8]>> MerkleProof<T> {
+    /// Encodes the proof in the compact format described in the module docs.
+    ///
+    /// Fails with [`EncodeError::HashLengthMismatch`] if the sibling hashes
+    /// do not all have the same length.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, EncodeError> {
+        let hash_len = self.hashes.first().map_or(0, Hash::len);
+        if let Some((index, hash)) = self
+            .hashes
+            .iter()
+            .enumerate()
+            .find(|(_, hash)| hash.len() != hash_len)
+        {
+            return Err(EncodeError::HashLengthMismatch {
+                index,
+                expected: hash_len,
+                actual: hash.len(),
+            });
+        }
+
+        let content = self.leaf_content.as_ref();
+        let mut out = Vec::with_capacity(3 * 10 + 1 + self.hashes.len() * hash_len + content.len());
+        write_varint(&mut out, self.num_of_leaves);
+        write_varint(&mut out, self.leaf_index);
+        write_varint(&mut out, self.hashes.len());
+        out.push(hash_len as u8);
+        for hash in &self.hashes {
+            out.extend_from_slice(hash.as_bytes());
+        }
+        out.extend_from_slice(content);
+        Ok(out)
+    }
+}
+
+impl MerkleProof<Vec<u8>> {
+    /// Decodes a proof written by [`to_bytes`](MerkleProof::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut input = bytes;
+        let num_of_leaves = read_varint(&mut input)?;
+        let leaf_index = read_varint(&mut input)?;
+        let hash_count = read_varint(&mut input)?;
+        let (&hash_len, rest) = input.split_first().ok_or(DecodeError::Truncated)?;
+        let hash_len = usize::from(hash_len);
+        if hash_len > MAX_HASH_LEN {
+            return Err(DecodeError::HashTooLong(hash_len));
+        }
+
+        // Checked before allocating so a bogus count cannot exhaust memory.
+        let hashes_len = hash_count
+            .checked_mul(hash_len)
+            .filter(|&len| len <= rest.len())
+            .ok_or(DecodeError::Truncated)?;
+        let (hash_bytes, leaf_content) = rest.split_at(hashes_len);
+        let hashes = match (hash_count, hash_len) {
+            (0, 0) => Vec::new(),
+            (0, _) => return Err(DecodeError::NonCanonical),
+            // Empty hashes would take no bytes, leaving the count unchecked.
+            (_, 0) => return Err(DecodeError::EmptyHash),
+            _ => hash_bytes
+                .chunks_exact(hash_len)
+                .map(|chunk| Hash::from_slice(chunk).expect("length checked above"))
+                .collect(),
+        };
+
+        Ok(MerkleProof {
+            hashes,
+            num_of_leaves,
+            leaf_index,
+            leaf_content: leaf_content.to_vec(),
+            path_bits: proof_directions(leaf_index, num_of_leaves),
+            root: None,
+        })
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(input: &mut &[u8]) -> Result<usize, DecodeError> {
+    let mut value: usize = 0;
+    let mut shift = 0;
+    loop {
+        let (&byte, rest) = input.split_first().ok_or(DecodeError::Truncated)?;
+        *input = rest;
+        let bits = usize::from(byte & 0x7f);
+        if shift >= usize::BITS || (bits << shift) >> shift != bits {
+            return Err(DecodeError::VarintOverflow);
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            // A last group of zero adds nothing; only 0 itself is written so.
+            if byte == 0 && shift > 0 {
+                return Err(DecodeError::NonCanonical);
+            }
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Blake2bTree;
+
+    #[test]
+    fn test_round_trip() {
+        let data: Vec<String> = (0..300).map(|i| format!("leaf-{i}")).collect();
+        let root = Blake2bTree::merkle_root(data.iter());
+        for i in [0, 1, 150, 299] {
+            let proof = Blake2bTree::merkle_proof(data.iter(), i).unwrap();
+            let bytes = proof.to_bytes().unwrap();
+            assert_eq!(
+                bytes.len(),
+                2 + if i < 128 { 1 } else { 2 } + 1 + 1 + 64 * proof.hashes.len() + data[i].len()
+            );
+
+            let decoded = MerkleProof::from_bytes(&bytes).unwrap();
+            assert_eq!(decoded.hashes, proof.hashes);
+            assert_eq!(decoded.num_of_leaves, 300);
+            assert_eq!(decoded.leaf_index, i);
+            assert_eq!(decoded.leaf_content, data[i].as_bytes());
+            assert!(Blake2bTree::verify_proof(&root, &decoded).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_single_leaf_round_trip() {
+        let proof = Blake2bTree::merkle_proof(["only"].iter(), 0).unwrap();
+        let bytes = proof.to_bytes().unwrap();
+        assert_eq!(bytes, [1, 0, 0, 0, b'o', b'n', b'l', b'y']);
+        assert_eq!(
+            MerkleProof::from_bytes(&bytes).unwrap().leaf_content,
+            b"only"
+        );
+    }
+
+    #[test]
+    fn test_truncated_input() {
+        let data = ["a", "b", "c", "d", "e"];
+        let bytes = Blake2bTree::merkle_proof(data.iter(), 1)
+            .unwrap()
+            .to_bytes()
+            .unwrap();
+        // Every cut that removes part of the header or a hash is rejected;
+        // the leaf content is the tail, so cuts inside it just shorten it.
+        let content_start = bytes.len() - 1;
+        for len in 0..content_start {
+            assert_eq!(
+                MerkleProof::from_bytes(&bytes[..len]).unwrap_err(),
+                DecodeError::Truncated,
+                "cut at {len}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        assert_eq!(
+            MerkleProof::from_bytes(&[0xff; 11]).unwrap_err(),
+            DecodeError::VarintOverflow
+        );
+        assert_eq!(
+            MerkleProof::from_bytes(&[1, 0, 1, 65]).unwrap_err(),
+            DecodeError::HashTooLong(65)
+        );
+        assert_eq!(
+            MerkleProof::from_bytes(&[1, 0, 1, 0]).unwrap_err(),
+            DecodeError::EmptyHash
+        );
+        // A huge hash count is refused without trying to allocate for it.
+        let mut bytes = vec![1, 0];
+        write_varint(&mut bytes, usize::MAX / 2);
+        bytes.push(64);
+        assert_eq!(
+            MerkleProof::from_bytes(&bytes).unwrap_err(),
+            DecodeError::Truncated
+        );
+    }
+
+    #[test]
+    fn test_non_canonical_input() {
+        // 1 leaf, padded to two and three varint bytes.
+        for header in [&[0x81, 0x00][..], &[0x81, 0x80, 0x00]] {
+            let mut bytes = header.to_vec();
+            bytes.extend_from_slice(&[0, 0, 0]);
+            assert_eq!(
+                MerkleProof::from_bytes(&bytes).unwrap_err(),
+                DecodeError::NonCanonical
+            );
+        }
+        assert_eq!(
+            MerkleProof::from_bytes(&[1, 0x80, 0x00, 0, 0]).unwrap_err(),
+            DecodeError::NonCanonical
+        );
+        assert_eq!(
+            MerkleProof::from_bytes(&[1, 0, 0, 64]).unwrap_err(),
+            DecodeError::NonCanonical
+        );
+
+        // What to_bytes writes for values needing every group still decodes.
+        let mut bytes = Vec::new();
+        for value in [0, 127, 128, usize::MAX] {
+            write_varint(&mut bytes, value);
+        }
+        let mut input = &bytes[..];
+        for value in [0, 127, 128, usize::MAX] {
+            assert_eq!(read_varint(&mut input), Ok(value));
+        }
+    }
+
+    #[test]
+    fn test_mixed_hash_lengths_are_not_encoded() {
+        let data = ["a", "b", "c", "d", "e"];
+        let mut proof = Blake2bTree::merkle_proof(data.iter(), 1).unwrap();
+        proof.hashes[2] = crate::Blake2sTree::hash_leaf("x");
+        assert_eq!(
+            proof.to_bytes(),
+            Err(EncodeError::HashLengthMismatch {
+                index: 2,
+                expected: 64,
+                actual: 32
+            })
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! Proofs of a depth fixed at compile time.
+//!
+//! Fixed-depth trees, such as those in zero-knowledge circuits, give every
+//! proof the same number of sibling hashes. A [`FixedProof`] keeps them in an
+//! array instead of a `Vec`, so converting, storing and verifying one never
+//! touches the heap.
+
+use blake2::Digest;
+
+use crate::{Hash, MerkleProof, MerkleTree, VerifyError, MAX_HASH_LEN, PLAIN};
+
+/// An inclusion proof with exactly `DEPTH` sibling hashes, bottom level
+/// first.
+///
+/// Each [`Hash`](struct@Hash) holds its bytes inline, up to [`MAX_HASH_LEN`],
+/// so the array works like a `[[u8; 64]; DEPTH]` for any digest.
+#[derive(Debug, Clone, PartialEq, Eq, core::hash::Hash)]
+pub struct FixedProof<const DEPTH: usize, T> {
+    pub hashes: [Hash; DEPTH],
+    pub num_of_leaves: usize,
+    pub leaf_index: usize,
+    pub leaf_content: T,
+}
+
+impl<const DEPTH: usize, T> TryFrom<MerkleProof<T>> for FixedProof<DEPTH, T> {
+    type Error = VerifyError;
+
+    /// Moves the hashes into an array, failing with
+    /// [`VerifyError::InconsistentProofLength`] unless there are exactly
+    /// `DEPTH` of them.
+    ///
+    /// The array has no room for [`path_bits`](MerkleProof::path_bits), so
+    /// the proof's shape and bits are checked here, as
+    /// [`verify_proof`](MerkleTree::verify_proof) checks them, before they
+    /// are dropped.
+    fn try_from(proof: MerkleProof<T>) -> Result<Self, VerifyError> {
+        proof.check_shape()?;
+        let actual = proof.hashes.len();
+        let hashes = proof
+            .hashes
+            .try_into()
+            .map_err(|_| VerifyError::InconsistentProofLength {
+                expected: DEPTH,
+                actual,
+            })?;
+        Ok(FixedProof {
+            hashes,
+            num_of_leaves: proof.num_of_leaves,
+            leaf_index: proof.leaf_index,
+            leaf_content: proof.leaf_content,
+        })
+    }
+}
+
+impl<D: Digest> MerkleTree<D> {
+    /// Checks `proof` against `root` like
+    /// [`verify_proof`](Self::verify_proof), without allocating.
+    pub fn verify_fixed<const DEPTH: usize, T: AsRef<[u8]>>(
+        root: impl AsRef<str>,
+        proof: &FixedProof<DEPTH, T>,
+    ) -> Result<(), VerifyError> {
+        let computed = Self::path_root_of(
+            proof.leaf_content.as_ref(),
+            proof.leaf_index,
+            proof.num_of_leaves,
+            &proof.hashes,
+            &[],
+            &PLAIN,
+            |leaf| Self::leaf_hash(&PLAIN, leaf),
+        )?;
+
+        let mut buf = [0u8; 2 * MAX_HASH_LEN];
+        let hex = &mut buf[..2 * computed.len()];
+        hex::encode_to_slice(computed.as_bytes(), hex).expect("buffer sized for hash");
+        if root.as_ref().as_bytes() == hex {
+            Ok(())
+        } else {
+            Err(VerifyError::RootMismatch)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Blake2bTree, Blake2sTree, MerkleTreeBuilder};
+
+    #[test]
+    fn test_fixed_proof_verifies() {
+        let leaves: Vec<String> = (0..8).map(|i| format!("leaf-{i}")).collect();
+        let tree = MerkleTreeBuilder::<String>::new(leaves.iter().cloned());
+        let root = tree.root_hex();
+
+        for proof in tree.all_proofs() {
+            let fixed = FixedProof::<3, _>::try_from(proof).unwrap();
+            assert_eq!(Blake2bTree::verify_fixed(&root, &fixed), Ok(()));
+
+            let mut tampered = fixed.clone();
+            tampered.leaf_content.push('!');
+            assert_eq!(
+                Blake2bTree::verify_fixed(&root, &tampered),
+                Err(VerifyError::RootMismatch)
+            );
+        }
+
+        let blake2s_root = Blake2sTree::merkle_root(leaves.iter());
+        let proof = Blake2sTree::merkle_proof(leaves.iter(), 5).unwrap();
+        let fixed = FixedProof::<3, _>::try_from(proof).unwrap();
+        assert_eq!(Blake2sTree::verify_fixed(&blake2s_root, &fixed), Ok(()));
+    }
+
+    #[test]
+    fn test_depth_mismatch() {
+        let leaves = ["a", "b", "c", "d", "e"];
+        let proof = Blake2bTree::merkle_proof(leaves.iter(), 1).unwrap();
+        assert_eq!(
+            FixedProof::<2, _>::try_from(proof.clone()),
+            Err(VerifyError::InconsistentProofLength {
+                expected: 2,
+                actual: 3
+            })
+        );
+        assert!(FixedProof::<3, _>::try_from(proof).is_ok());
+
+        // The last leaf is promoted past a level, so its path is shorter.
+        let root = Blake2bTree::merkle_root(leaves.iter());
+        let mut fixed =
+            FixedProof::<3, _>::try_from(Blake2bTree::merkle_proof(leaves.iter(), 1).unwrap())
+                .unwrap();
+        fixed.leaf_index = 4;
+        assert_eq!(
+            Blake2bTree::verify_fixed(&root, &fixed),
+            Err(VerifyError::InconsistentProofLength {
+                expected: 1,
+                actual: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_conversion_checks_shape_like_verify_proof() {
+        let leaves = ["a", "b", "c", "d", "e"];
+        let root = Blake2bTree::merkle_root(leaves.iter());
+        let proof = Blake2bTree::merkle_proof(leaves.iter(), 1).unwrap();
+
+        // Relabelled as leaf 2, whose path is as long: the bits give it away
+        // before the conversion drops them.
+        let mut moved = proof.clone();
+        moved.leaf_index = 2;
+        let mismatch = VerifyError::PositionMismatch { level: 0 };
+        assert_eq!(Blake2bTree::verify_proof(&root, &moved), Err(mismatch));
+        assert_eq!(FixedProof::<3, _>::try_from(moved.clone()), Err(mismatch));
+
+        // Without bits, both fall back to climbing from the claimed index.
+        moved.path_bits.clear();
+        let fixed = FixedProof::<3, _>::try_from(moved.clone()).unwrap();
+        assert_eq!(
+            Blake2bTree::verify_fixed(&root, &fixed),
+            Blake2bTree::verify_proof(&root, &moved)
+        );
+
+        let mut empty = proof;
+        empty.num_of_leaves = 0;
+        assert_eq!(
+            FixedProof::<3, _>::try_from(empty),
+            Err(VerifyError::LeafIndexOutOfRange {
+                index: 1,
+                num_of_leaves: 0
+            })
+        );
+    }
+}
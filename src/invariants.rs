@@ -0,0 +1,81 @@
+//! Self-checks for fuzz harnesses, behind the `fuzz` feature.
+//!
+//! [`MerkleTree::check_invariants`] is an oracle: for any leaf set it must
+//! return `Ok`, so a fuzzer only has to feed it leaves and report errors.
+
+use core::fmt;
+
+use blake2::Digest;
+
+use crate::{MerkleTree, MerkleTreeBuilder, VerifyError};
+
+/// The proof for `leaf_index` did not verify against the tree's own root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvariantViolation {
+    pub leaf_index: usize,
+    pub error: VerifyError,
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "proof for leaf {} failed to verify: {}",
+            self.leaf_index, self.error
+        )
+    }
+}
+
+impl core::error::Error for InvariantViolation {}
+
+impl<D: Digest> MerkleTree<D> {
+    /// Builds the tree over `leaves`, proves every leaf and checks each proof
+    /// against the root, returning the first one that fails.
+    pub fn check_invariants<I, L>(leaves: I) -> Result<(), InvariantViolation>
+    where
+        I: Iterator<Item = L>,
+        L: AsRef<[u8]> + Clone,
+    {
+        let tree = MerkleTreeBuilder::<L, D>::new(leaves);
+        let root = tree.root_hex();
+        for proof in tree.all_proofs() {
+            Self::verify_proof(&root, &proof).map_err(|error| InvariantViolation {
+                leaf_index: proof.leaf_index,
+                error,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::{Blake2bTree, Blake2sTree};
+
+    proptest! {
+        #[test]
+        fn test_every_proof_verifies(
+            leaves in prop::collection::vec(prop::collection::vec(any::<u8>(), 0..48), 0..70)
+        ) {
+            prop_assert_eq!(Blake2bTree::check_invariants(leaves.iter()), Ok(()));
+            prop_assert_eq!(Blake2sTree::check_invariants(leaves.iter()), Ok(()));
+            prop_assert_eq!(MerkleTree::<Sha256>::check_invariants(leaves.iter()), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_violation_display() {
+        let violation = InvariantViolation {
+            leaf_index: 3,
+            error: VerifyError::RootMismatch,
+        };
+        assert_eq!(
+            violation.to_string(),
+            "proof for leaf 3 failed to verify: proof does not hash to the expected root"
+        );
+    }
+}
@@ -1,172 +1,4465 @@
 // Cargo.toml dependencies will include "blake2" and "hex" for hashing
+//
+// Without the default `std` feature the crate is `no_std` and needs only
+// `alloc`.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
-use blake2::{Blake2b512, Digest};
-use hex; // Ensure the hex crate is imported
+extern crate alloc;
 
-#[derive(Debug)]
+use core::cmp::Ordering;
+use core::fmt;
+use core::marker::PhantomData;
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Blake2b512, Blake2s256, Digest};
+use subtle::ConstantTimeEq;
+
+#[cfg(feature = "tokio")]
+mod async_root;
+#[cfg(feature = "sha2")]
+mod bitcoin;
+mod bundle;
+mod consistency;
+mod dynamic;
+mod encoding;
+mod fixed;
+#[cfg(feature = "fuzz")]
+mod invariants;
+mod multi_digest;
+mod multiproof;
+mod nary;
+mod nested;
+mod non_membership;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod progress;
+mod range;
+mod sorted;
+mod steps;
+mod streaming;
+mod typed;
+mod verifier;
+
+#[cfg(feature = "sha2")]
+pub use bitcoin::{
+    bitcoin_merkle_branch, bitcoin_merkle_root, verify_bitcoin_branch, BitcoinBranch,
+};
+pub use bundle::ProofBundle;
+pub use dynamic::{DynHasher, DynTree};
+pub use encoding::{DecodeError, EncodeError};
+pub use fixed::FixedProof;
+#[cfg(feature = "fuzz")]
+pub use invariants::InvariantViolation;
+pub use multi_digest::{merkle_roots_multi, DigestKind};
+pub use multiproof::MerkleMultiproof;
+pub use nary::{NaryProof, NaryProofLevel};
+pub use non_membership::NonMembershipProof;
+pub use range::RangeProof;
+pub use sorted::SortedProof;
+pub use steps::{ProofStep, SteppedProof};
+pub use streaming::Accumulator;
+pub use verifier::ProofVerifier;
+
+/// Prepended to leaf data before hashing (RFC 6962 style domain separation).
+const LEAF_PREFIX: u8 = 0x00;
+/// Prepended to the concatenation of two child hashes before hashing.
+const NODE_PREFIX: u8 = 0x01;
+
+/// Largest digest output, in bytes, that a [`Hash`](struct@Hash) can hold.
+pub const MAX_HASH_LEN: usize = 64;
+
+/// A raw digest output of up to [`MAX_HASH_LEN`] bytes.
+///
+/// Hashes are stored inline so building a level never allocates per node.
+/// Hex encoding only happens when asked for via [`to_hex`](Hash::to_hex).
+#[derive(Clone, Copy)]
+pub struct Hash {
+    bytes: [u8; MAX_HASH_LEN],
+    len: usize,
+}
+
+impl Hash {
+    /// Copies `bytes` into a new hash, or returns `None` if it is longer than
+    /// [`MAX_HASH_LEN`].
+    pub fn from_slice(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() > MAX_HASH_LEN {
+            return None;
+        }
+        let mut hash = Hash {
+            bytes: [0; MAX_HASH_LEN],
+            len: bytes.len(),
+        };
+        hash.bytes[..bytes.len()].copy_from_slice(bytes);
+        Some(hash)
+    }
+
+    /// Wraps a digest output. Panics if the digest is wider than [`MAX_HASH_LEN`].
+    fn from_digest<D: Digest>(digest: D) -> Self {
+        Self::from_slice(&digest.finalize()).expect("digest output exceeds MAX_HASH_LEN bytes")
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Lowercase hex encoding of the hash bytes.
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.as_bytes())
+    }
+}
+
+impl AsRef<[u8]> for Hash {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl PartialEq for Hash {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl Eq for Hash {}
+
+impl PartialOrd for Hash {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Hash {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_bytes().cmp(other.as_bytes())
+    }
+}
+
+impl core::hash::Hash for Hash {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.as_bytes().hash(state);
+    }
+}
+
+/// Serialized as a hex string in human-readable formats and as raw bytes
+/// otherwise.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Hash {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&self.to_hex())
+        } else {
+            serializer.serialize_bytes(self.as_bytes())
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Hash {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct HashVisitor;
+
+        impl serde::de::Visitor<'_> for HashVisitor {
+            type Value = Hash;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "at most {MAX_HASH_LEN} hash bytes")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Hash, E> {
+                let bytes = hex::decode(v).map_err(E::custom)?;
+                self.visit_bytes(&bytes)
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Hash, E> {
+                Hash::from_slice(v).ok_or_else(|| E::invalid_length(v.len(), &self))
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(HashVisitor)
+        } else {
+            deserializer.deserialize_bytes(HashVisitor)
+        }
+    }
+}
+
+impl fmt::Debug for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Hash({})", self.to_hex())
+    }
+}
+
+/// The root of a tree, as returned by [`MerkleTreeBuilder::root`].
+///
+/// Unlike a hex `String` it cannot be mixed up with arbitrary text. Roots
+/// compare, order and hash by their bytes, so they can key a `HashMap` or a
+/// `BTreeSet`, and display as lowercase hex.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, core::hash::Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Root(pub(crate) Hash);
+
+impl Root {
+    /// The raw digest bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_bytes()
+    }
+
+    /// Lowercase hex encoding, as returned by [`MerkleTree::merkle_root`].
+    pub fn to_hex(&self) -> String {
+        self.0.to_hex()
+    }
+}
+
+impl AsRef<[u8]> for Root {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl fmt::Display for Root {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl fmt::Debug for Root {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Root({})", self.to_hex())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, core::hash::Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MerkleProof<T> {
-    pub hashes: Vec<String>,
+    pub hashes: Vec<Hash>,
     pub num_of_leaves: usize,
     pub leaf_index: usize,
     pub leaf_content: T,
+    /// For each of [`hashes`](Self::hashes), whether the proven node is the
+    /// right child at that level, so the sibling is hashed on the left, as
+    /// [`proof_directions`] gives them for `leaf_index` and `num_of_leaves`.
+    ///
+    /// Recorded when the proof is generated. The verifiers reject a proof
+    /// whose bits differ from the directions of its claimed index and leaf
+    /// count, so the bits pin both: relabelling either gives
+    /// [`VerifyError::PositionMismatch`]. Proofs with no bits, such as those
+    /// serialized before this field existed, are checked on the index and
+    /// count alone.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub path_bits: Vec<bool>,
+    /// Hex-encoded root the proof was built against, if bundled with it (see
+    /// [`with_root`](Self::with_root)), so [`verify`](Self::verify) needs no
+    /// separate root.
+    ///
+    /// This is a convenience, not a security guarantee: whoever produced the
+    /// proof chose this root, so a proof that verifies against it only shows
+    /// the proof is self-consistent. Check the root against one obtained from
+    /// a trusted source, or verify against that root directly.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub root: Option<String>,
+}
+
+/// Leaf content longer than this many characters is abbreviated when a
+/// proof is displayed.
+const DISPLAY_LEAF_CHARS: usize = 32;
+
+/// A one-line summary for logs, e.g. `MerkleProof{leaf 3/8, depth 3, "abc"}`,
+/// where depth is the number of sibling hashes.
+impl<T: fmt::Display> fmt::Display for MerkleProof<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "MerkleProof{{leaf {}/{}, depth {}, ",
+            self.leaf_index,
+            self.num_of_leaves,
+            self.hashes.len()
+        )?;
+        let leaf = self.leaf_content.to_string();
+        match leaf.char_indices().nth(DISPLAY_LEAF_CHARS) {
+            Some((end, _)) => write!(f, "{:?}...", &leaf[..end])?,
+            None => write!(f, "{leaf:?}")?,
+        }
+        write!(f, "}}")
+    }
+}
+
+impl<T: AsRef<[u8]>> MerkleProof<T> {
+    /// Verifies the proof against its bundled [`root`](Self::root), as
+    /// [`MerkleTree::verify_bundled_proof`] does for the default
+    /// Blake2b-512 tree. See the warning on that field: only a trusted root
+    /// makes the result meaningful.
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        Blake2bTree::verify_bundled_proof(self)
+    }
+}
+
+impl<T> MerkleProof<T> {
+    /// Bundles the hex-encoded `root` with the proof, replacing any root it
+    /// already carried.
+    pub fn with_root(self, root: impl AsRef<str>) -> Self {
+        MerkleProof {
+            root: Some(root.as_ref().to_string()),
+            ..self
+        }
+    }
+
+    /// Checks that the proof is well-formed without any root: the leaf index
+    /// addresses a leaf of a tree of `num_of_leaves`, there are exactly
+    /// [`proof_length`] hashes, all of one non-zero length, any
+    /// [`path_bits`](Self::path_bits) are the directions of that leaf, and a
+    /// bundled [`root`](Self::root) is lowercase hex of that length.
+    ///
+    /// The index, hash count and bits are held to the same rules as in
+    /// [`MerkleTree::verify_proof`], so the two never disagree on them.
+    ///
+    /// This catches malformed proofs early and cheaply; a proof that passes
+    /// may still fail to verify.
+    pub fn validate_structure(&self) -> Result<(), StructureError> {
+        if self.leaf_index >= self.num_of_leaves {
+            return Err(StructureError::LeafIndexOutOfRange {
+                index: self.leaf_index,
+                num_of_leaves: self.num_of_leaves,
+            });
+        }
+
+        let expected = proof_length(self.leaf_index, self.num_of_leaves);
+        if self.hashes.len() != expected {
+            return Err(StructureError::WrongHashCount {
+                expected,
+                actual: self.hashes.len(),
+            });
+        }
+        if let Some(level) =
+            path_bits_mismatch(self.leaf_index, self.num_of_leaves, &self.path_bits)
+        {
+            return Err(StructureError::PathBitsMismatch { level });
+        }
+
+        let hash_len = self.hashes.first().map(Hash::len);
+        for (index, hash) in self.hashes.iter().enumerate() {
+            if hash.is_empty() {
+                return Err(StructureError::EmptyHash { index });
+            }
+            if Some(hash.len()) != hash_len {
+                return Err(StructureError::HashLengthMismatch {
+                    index,
+                    expected: hash_len.unwrap_or_default(),
+                    actual: hash.len(),
+                });
+            }
+        }
+
+        if let Some(root) = &self.root {
+            let is_hex = root
+                .bytes()
+                .all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'));
+            let len_ok = match hash_len {
+                Some(len) => root.len() == 2 * len,
+                None => !root.is_empty() && root.len() % 2 == 0 && root.len() <= 2 * MAX_HASH_LEN,
+            };
+            if !is_hex || !len_ok {
+                return Err(StructureError::InvalidRoot);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the leaf index addresses a leaf of a tree of
+    /// `num_of_leaves`, that the proof has exactly [`proof_length`] hashes
+    /// and that any [`path_bits`](Self::path_bits) are the directions of
+    /// that leaf.
+    pub(crate) fn check_shape(&self) -> Result<(), VerifyError> {
+        check_path_shape(self.leaf_index, self.num_of_leaves, self.hashes.len())?;
+        check_path_bits(self.leaf_index, self.num_of_leaves, &self.path_bits)
+    }
+}
+
+/// A proof together with the root of the tree it was built from, taken from
+/// the same build so the two always match.
+#[derive(Debug, Clone, PartialEq, Eq, core::hash::Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProofWithRoot<T> {
+    /// Hex-encoded root.
+    pub root: String,
+    pub proof: MerkleProof<T>,
+}
+
+/// What building a tree cost, from [`MerkleTreeBuilder::build_with_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BuildStats {
+    /// Digest invocations: one per hashed leaf and one per internal node
+    /// hashed (a promoted odd node costs nothing).
+    pub hash_calls: usize,
+    /// Number of levels, as in [`MerkleTreeBuilder::levels`].
+    pub levels: usize,
+}
+
+/// Reasons a [`MerkleProof`] can fail verification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The proof was well-formed but hashed to a different root.
+    RootMismatch,
+    /// The number of sibling hashes does not match the depth of a tree with
+    /// `num_of_leaves` leaves.
+    InconsistentProofLength { expected: usize, actual: usize },
+    /// `leaf_index` does not address a leaf of a tree with `num_of_leaves` leaves.
+    LeafIndexOutOfRange { index: usize, num_of_leaves: usize },
+    /// A multiproof's indices are empty, not strictly increasing, or do not
+    /// line up with its leaf contents.
+    MalformedMultiproof,
+    /// An n-ary proof declares an arity below 2.
+    InvalidArity(usize),
+    /// The position recorded at `level` of an n-ary proof, or the side
+    /// recorded at `level` of [`MerkleProof::path_bits`], does not match the
+    /// path implied by `leaf_index`.
+    PositionMismatch { level: usize },
+    /// The proof claims a different leaf count from the one the verifier
+    /// trusts.
+    LeafCountMismatch { expected: usize, actual: usize },
+    /// The leaves of a non-membership proof are not adjacent or do not sort
+    /// on either side of the key.
+    KeyNotBracketed,
+    /// The proof carries a different leaf from the one the verifier expects.
+    LeafMismatch,
+    /// A key-value proof is for a different key from the one the verifier
+    /// expects.
+    KeyMismatch,
+    /// A range proof's range is empty or does not match the number of leaf
+    /// hashes given.
+    MalformedRange,
+    /// The proof carries no bundled root to verify against.
+    MissingRoot,
+    /// A sub-tree proof cannot be composed with a top-tree proof because the
+    /// sub-tree's leaf count is not a power of two.
+    UnevenSubtree { num_of_leaves: usize },
+    /// The leaf count or index of a composed proof does not fit in a `usize`.
+    LeafCountOverflow,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::RootMismatch => write!(f, "proof does not hash to the expected root"),
+            VerifyError::InconsistentProofLength { expected, actual } => {
+                write!(f, "proof has {actual} sibling hashes, expected {expected}")
+            }
+            VerifyError::LeafIndexOutOfRange {
+                index,
+                num_of_leaves,
+            } => write!(
+                f,
+                "leaf index {index} is out of range for {num_of_leaves} leaves"
+            ),
+            VerifyError::MalformedMultiproof => {
+                write!(f, "multiproof indices do not match its leaf contents")
+            }
+            VerifyError::InvalidArity(arity) => write!(f, "arity {arity} is below 2"),
+            VerifyError::PositionMismatch { level } => {
+                write!(f, "position at level {level} does not match the leaf index")
+            }
+            VerifyError::LeafCountMismatch { expected, actual } => {
+                write!(f, "proof is for {actual} leaves, expected {expected}")
+            }
+            VerifyError::KeyNotBracketed => {
+                write!(f, "proven leaves do not bracket the key")
+            }
+            VerifyError::LeafMismatch => write!(f, "proof is for a different leaf"),
+            VerifyError::KeyMismatch => write!(f, "proof is for a different key"),
+            VerifyError::MalformedRange => {
+                write!(f, "range does not match the number of leaf hashes")
+            }
+            VerifyError::MissingRoot => write!(f, "proof carries no root"),
+            VerifyError::UnevenSubtree { num_of_leaves } => write!(
+                f,
+                "sub-tree of {num_of_leaves} leaves is not a power of two"
+            ),
+            VerifyError::LeafCountOverflow => {
+                write!(f, "composed proof has more leaves than fit in a usize")
+            }
+        }
+    }
+}
+
+impl core::error::Error for VerifyError {}
+
+/// Ways a [`MerkleProof`] can be malformed, from
+/// [`MerkleProof::validate_structure`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StructureError {
+    /// `leaf_index` does not address a leaf of a tree with `num_of_leaves` leaves.
+    LeafIndexOutOfRange { index: usize, num_of_leaves: usize },
+    /// The number of sibling hashes does not match the depth of the leaf.
+    WrongHashCount { expected: usize, actual: usize },
+    /// The side recorded at `level` of [`MerkleProof::path_bits`] is not the
+    /// one `leaf_index` and `num_of_leaves` imply.
+    PathBitsMismatch { level: usize },
+    /// The sibling hash at `index` has no bytes.
+    EmptyHash { index: usize },
+    /// The sibling hash at `index` is not as long as the first one.
+    HashLengthMismatch {
+        index: usize,
+        expected: usize,
+        actual: usize,
+    },
+    /// The bundled root is not lowercase hex of the sibling hashes' length.
+    InvalidRoot,
+}
+
+impl fmt::Display for StructureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StructureError::LeafIndexOutOfRange {
+                index,
+                num_of_leaves,
+            } => write!(
+                f,
+                "leaf index {index} is out of range for {num_of_leaves} leaves"
+            ),
+            StructureError::WrongHashCount { expected, actual } => {
+                write!(f, "proof has {actual} sibling hashes, expected {expected}")
+            }
+            StructureError::PathBitsMismatch { level } => {
+                write!(f, "path bit {level} does not match the leaf's position")
+            }
+            StructureError::EmptyHash { index } => write!(f, "sibling hash {index} is empty"),
+            StructureError::HashLengthMismatch {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "sibling hash {index} is {actual} bytes, expected {expected}"
+            ),
+            StructureError::InvalidRoot => {
+                write!(f, "bundled root is not a lowercase hex hash")
+            }
+        }
+    }
+}
+
+impl core::error::Error for StructureError {}
+
+/// Reasons a [`MerkleProof`] cannot be generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofError {
+    /// There are no leaves to prove.
+    EmptyTree,
+    /// The requested leaf does not exist.
+    IndexOutOfRange { index: usize, len: usize },
+    /// A multiproof was requested for no leaves.
+    NoIndices,
+    /// A consistency proof needs `0 < old_size <= new_size <= leaves`.
+    InvalidTreeSizes { old_size: usize, new_size: usize },
+    /// A non-membership proof was requested for a key that is the leaf at
+    /// `index`.
+    KeyPresent { index: usize },
+    /// No leaf has the content a proof was requested for.
+    LeafNotFound,
+    /// A range proof was requested for `start >= end`.
+    EmptyRange { start: usize, end: usize },
+    /// A tree built with [`DuplicatePolicy::Reject`] was given the leaf at
+    /// `first` again at `duplicate`.
+    DuplicateLeaf { first: usize, duplicate: usize },
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProofError::EmptyTree => write!(f, "cannot build a proof for an empty tree"),
+            ProofError::IndexOutOfRange { index, len } => {
+                write!(f, "leaf index {index} is out of range for {len} leaves")
+            }
+            ProofError::NoIndices => write!(f, "no leaf indices were given"),
+            ProofError::InvalidTreeSizes { old_size, new_size } => write!(
+                f,
+                "cannot prove a tree of {old_size} leaves is a prefix of one of {new_size}"
+            ),
+            ProofError::KeyPresent { index } => {
+                write!(f, "key is present as leaf {index}")
+            }
+            ProofError::LeafNotFound => write!(f, "no leaf has the requested content"),
+            ProofError::EmptyRange { start, end } => {
+                write!(f, "leaf range [{start}, {end}) is empty")
+            }
+            ProofError::DuplicateLeaf { first, duplicate } => {
+                write!(f, "leaf {duplicate} duplicates leaf {first}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for ProofError {}
+
+/// Number of levels above the leaves in a tree of `num_of_leaves` leaves,
+/// and so the most sibling hashes any of its proofs can hold.
+///
+/// Every level halves the node count, rounding up because a trailing odd node
+/// is carried up unchanged. That gives `ceil(log2(n))` for `n >= 1`; empty and
+/// single-leaf trees have depth 0.
+///
+/// Leaf counts up to `usize::MAX` are supported: walking a path only halves
+/// indices and counts (rounding up with `div_ceil`) and compares `index ^ 1`
+/// with the count, none of which can overflow, so a verifier handles any
+/// `num_of_leaves` a proof claims. Building a tree is bounded by memory long
+/// before that.
+pub fn tree_depth(num_of_leaves: usize) -> usize {
+    let mut len = num_of_leaves;
+    let mut depth = 0;
+    while len > 1 {
+        len = len.div_ceil(2);
+        depth += 1;
+    }
+    depth
+}
+
+/// Number of sibling hashes in the proof for the leaf at `leaf_index` of a
+/// tree of `num_of_leaves` leaves.
+///
+/// This is [`tree_depth`] minus the levels where the path passes through a
+/// trailing odd node, which has no sibling. Proofs for the leaves of a
+/// power-of-two tree all have the full depth.
+pub fn proof_length(leaf_index: usize, num_of_leaves: usize) -> usize {
+    let (mut index, mut len) = (leaf_index, num_of_leaves);
+    let mut length = 0;
+    while len > 1 {
+        if index ^ 1 < len {
+            length += 1;
+        }
+        index /= 2;
+        len = len.div_ceil(2);
+    }
+    length
+}
+
+/// For each sibling hash in the proof for the leaf at `leaf_index` of a tree
+/// of `num_of_leaves` leaves, bottom first, whether the proven node is the
+/// right child at that level (so the sibling is hashed on the left).
+///
+/// Levels where the node is promoted alone have no sibling and no entry, so
+/// the result has [`proof_length`] entries.
+pub fn proof_directions(leaf_index: usize, num_of_leaves: usize) -> Vec<bool> {
+    let (mut index, mut len) = (leaf_index, num_of_leaves);
+    let mut directions = Vec::new();
+    while len > 1 {
+        if index ^ 1 < len {
+            directions.push(!index.is_multiple_of(2));
+        }
+        index /= 2;
+        len = len.div_ceil(2);
+    }
+    directions
+}
+
+/// Bytes of node storage a [`MerkleTreeBuilder`] over `num_of_leaves` leaves
+/// will hold across all of its [`levels`](MerkleTreeBuilder::levels), leaf
+/// hashes and root included, for deciding up front whether to stream
+/// instead.
+///
+/// Every node is an inline [`Hash`], [`MAX_HASH_LEN`] bytes plus a length,
+/// whatever the digest, so `hash_len` does not shrink the estimate. The
+/// leaves themselves and the `Vec` headers are not counted. For trees built
+/// with `pad_to_power_of_two`, pass the padded count.
+///
+/// Saturates at `usize::MAX` when the total would not fit in a `usize`, and
+/// returns that too for a `hash_len` over [`MAX_HASH_LEN`], which no tree
+/// can hold: either way, building is out of the question.
+pub fn estimated_memory(num_of_leaves: usize, hash_len: usize) -> usize {
+    if hash_len > MAX_HASH_LEN {
+        return usize::MAX;
+    }
+    let mut len = num_of_leaves;
+    let mut nodes = len;
+    while len > 1 {
+        len = len.div_ceil(2);
+        nodes = nodes.saturating_add(len);
+    }
+    nodes.saturating_mul(core::mem::size_of::<Hash>())
+}
+
+/// Checks that `leaf_index` addresses a leaf of a tree of `num_of_leaves`
+/// and that its path has `hash_count` siblings, as [`proof_length`] gives.
+pub(crate) fn check_path_shape(
+    leaf_index: usize,
+    num_of_leaves: usize,
+    hash_count: usize,
+) -> Result<(), VerifyError> {
+    if leaf_index >= num_of_leaves {
+        return Err(VerifyError::LeafIndexOutOfRange {
+            index: leaf_index,
+            num_of_leaves,
+        });
+    }
+
+    let expected = proof_length(leaf_index, num_of_leaves);
+    if hash_count != expected {
+        return Err(VerifyError::InconsistentProofLength {
+            expected,
+            actual: hash_count,
+        });
+    }
+    Ok(())
+}
+
+/// Checks that `path_bits` are exactly the [`proof_directions`] of the leaf
+/// at `leaf_index` of `num_of_leaves`, or empty for proofs that predate them.
+fn check_path_bits(
+    leaf_index: usize,
+    num_of_leaves: usize,
+    path_bits: &[bool],
+) -> Result<(), VerifyError> {
+    match path_bits_mismatch(leaf_index, num_of_leaves, path_bits) {
+        Some(level) => Err(VerifyError::PositionMismatch { level }),
+        None => Ok(()),
+    }
+}
+
+/// The first level at which non-empty `path_bits` differ from the
+/// [`proof_directions`] of the leaf at `leaf_index` of `num_of_leaves`,
+/// including by running short or long.
+fn path_bits_mismatch(
+    leaf_index: usize,
+    num_of_leaves: usize,
+    path_bits: &[bool],
+) -> Option<usize> {
+    if path_bits.is_empty() {
+        return None;
+    }
+    let (mut index, mut len) = (leaf_index, num_of_leaves);
+    let mut bits = path_bits.iter();
+    let mut level = 0;
+    while len > 1 {
+        if index ^ 1 < len {
+            if bits.next() != Some(&!index.is_multiple_of(2)) {
+                return Some(level);
+            }
+            level += 1;
+        }
+        index /= 2;
+        len = len.div_ceil(2);
+    }
+    bits.next().map(|_| level)
+}
+
+/// Options for trees whose leaves are supplied already hashed.
+const PREHASHED: TreeOptions = TreeOptions {
+    sorted_pairs: false,
+    prehashed_leaves: true,
+    key: None,
+    odd_nodes: OddNodeStrategy::Promote,
+    node_encoding: NodeEncoding::Hex,
+    pad_to_power_of_two: false,
+    pair_order: None,
+    child_order: ChildOrder::LeftRight,
+};
+
+/// Options of the plain tree, usable where a `&'static` is needed.
+const PLAIN: TreeOptions = TreeOptions {
+    sorted_pairs: false,
+    prehashed_leaves: false,
+    key: None,
+    odd_nodes: OddNodeStrategy::Promote,
+    node_encoding: NodeEncoding::Hex,
+    pad_to_power_of_two: false,
+    pair_order: None,
+    child_order: ChildOrder::LeftRight,
+};
+
+/// Tree construction settings that a prover and verifier must agree on.
+///
+/// The default is the positional binary tree produced by
+/// [`MerkleTree::merkle_root`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeOptions {
+    /// Concatenate the two children of every node in lexicographic byte order
+    /// instead of left then right (the OpenZeppelin convention). Proofs then
+    /// verify without knowing which side each sibling is on.
+    pub sorted_pairs: bool,
+    /// Take every leaf to be its own, already computed, leaf hash instead of
+    /// hashing it as `H(0x00 || leaf)`. Leaves must be at most
+    /// [`MAX_HASH_LEN`] bytes.
+    ///
+    /// The leaf bytes become the bottom level verbatim, with no prefix, key or
+    /// hashing of any kind, so a bottom level built by another system is
+    /// reproduced exactly (identity leaves). Leaves need not be as long as
+    /// the digest output.
+    ///
+    /// The leaf prefix is what stops an internal node posing as a leaf, so
+    /// only use this when the leaf hashes come from a domain of their own.
+    pub prehashed_leaves: bool,
+    /// Per-tree key absorbed at the start of every hash, as
+    /// `H(len(key) as u64 LE || key || ...)`, so trees with different keys
+    /// have disjoint hash spaces and a proof from one never verifies against
+    /// another's root. Works with any digest.
+    pub key: Option<Vec<u8>>,
+    /// How the last node of a level with an odd number of nodes is hashed.
+    pub odd_nodes: OddNodeStrategy,
+    /// How child hashes are fed to the digest when hashing a node.
+    pub node_encoding: NodeEncoding,
+    /// Append sentinel leaf hashes until the leaf count is a power of two,
+    /// so every leaf sits at the same depth (as fixed-depth circuits need).
+    /// The sentinel is the all-zero hash of the digest's output length, which
+    /// no leaf hashes to. Proofs count the padding in `num_of_leaves` and
+    /// verify like any proof of a perfect tree; only real leaves can be
+    /// proven.
+    pub pad_to_power_of_two: bool,
+    /// Decides the order of every node's two children: they are swapped when
+    /// the comparator puts the right one first. This generalizes
+    /// [`sorted_pairs`](Self::sorted_pairs), which it overrides, and like it
+    /// makes proofs independent of sibling sides. Trees built with different
+    /// comparators have different roots.
+    pub pair_order: Option<PairComparator>,
+    /// Which child is fed to the node hash first. A fixed swap applied on top
+    /// of [`sorted_pairs`](Self::sorted_pairs) and
+    /// [`pair_order`](Self::pair_order), for trees committed by systems that
+    /// hashed right then left.
+    pub child_order: ChildOrder,
+}
+
+/// A comparator for [`TreeOptions::pair_order`].
+///
+/// Two comparators are equal when they are the same function pointer, which
+/// the compiler may or may not merge for identical functions, so compare
+/// options holding one with care.
+#[derive(Clone, Copy)]
+pub struct PairComparator(pub fn(&Hash, &Hash) -> Ordering);
+
+impl PartialEq for PairComparator {
+    fn eq(&self, other: &Self) -> bool {
+        core::ptr::fn_addr_eq(self.0, other.0)
+    }
+}
+
+impl Eq for PairComparator {}
+
+impl fmt::Debug for PairComparator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PairComparator({:p})", self.0)
+    }
+}
+
+/// How an internal node's children are fed to the digest.
+///
+/// The two encodings give different roots for every tree of two or more
+/// leaves, so both sides of a proof must use the same one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NodeEncoding {
+    /// `H(0x01 || hex(left) || hex(right))`: each child as lowercase hex
+    /// text, as this crate always has, so existing roots stay valid.
+    #[default]
+    Hex,
+    /// `H(0x01 || left || right)` over the raw digest bytes, as in RFC 6962
+    /// and most other Merkle trees. Half as much input per node.
+    Raw,
+}
+
+/// How a level with an odd number of nodes hashes its last node, to match
+/// the tree of another implementation.
+///
+/// Proofs never carry a sibling for such a node: under every strategy the
+/// verifier can rebuild it, so proof lengths are the same for all three and
+/// [`proof_length`] and [`proof_directions`] apply unchanged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OddNodeStrategy {
+    /// Move the node up to the next level unchanged, as in RFC 6962.
+    #[default]
+    Promote,
+    /// Pair the node with a copy of itself, as in Bitcoin. Then `[.., c]`
+    /// and `[.., c, c]` have the same root (CVE-2012-2459).
+    Duplicate,
+    /// Pair the node with an all-zero hash as long as the digest output.
+    PadZero,
+}
+
+/// The order in which a node's two children are hashed.
+///
+/// The two orders give different roots for every tree of two or more leaves,
+/// so both sides of a proof must use the same one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChildOrder {
+    /// `H(0x01 || left || right)`, as this crate always has.
+    #[default]
+    LeftRight,
+    /// `H(0x01 || right || left)`, to verify proofs against roots of legacy
+    /// systems that concatenated children the other way round.
+    RightLeft,
+}
+
+/// What [`MerkleTreeBuilder::with_duplicate_policy`] does with leaves whose
+/// leaf hash repeats an earlier one, for trees that commit to a set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Keep every leaf, as the other constructors do.
+    #[default]
+    Allow,
+    /// Fail with [`ProofError::DuplicateLeaf`] at the first repeat.
+    Reject,
+    /// Drop every repeat, keeping the first occurrence, so later leaves move
+    /// down to fill the gaps.
+    Dedup,
+}
+
+/// Merkle tree operations over the digest `D`.
+///
+/// Any hash implementing [`Digest`] can be used, e.g. `MerkleTree::<Sha256>`.
+/// The digest defaults to `Blake2b512`.
+pub struct MerkleTree<D = Blake2b512> {
+    _digest: PhantomData<D>,
 }
 
-pub struct MerkleTree;
+/// The original Blake2b-512 tree.
+pub type Blake2bTree = MerkleTree<Blake2b512>;
+
+/// A Blake2s-256 tree, with 32-byte hashes.
+pub type Blake2sTree = MerkleTree<Blake2s256>;
+
+/// A Blake2b tree with 256-bit output, with 32-byte hashes. This is Blake2b
+/// parameterised for a 32-byte digest, which differs from the first half of
+/// a Blake2b-512 digest.
+pub type Blake2b256Tree = MerkleTree<Blake2b<U32>>;
+
+impl<D: Digest> MerkleTree<D> {
+    /// Builds the tree over `leaves` once, returning a handle that answers
+    /// [`root`](MerkleTreeBuilder::root), [`proof`](MerkleTreeBuilder::proof)
+    /// and [`leaf_count`](MerkleTreeBuilder::leaf_count) from its cached
+    /// levels instead of rehashing on every call.
+    pub fn from_leaves<I, L>(leaves: I) -> MerkleTreeBuilder<L, D>
+    where
+        I: IntoIterator<Item = L>,
+        L: AsRef<[u8]>,
+    {
+        MerkleTreeBuilder::new(leaves.into_iter())
+    }
+
+    /// Computes the hex-encoded root over `leaves`, hashing each one as
+    /// `H(0x00 || leaf)` first.
+    ///
+    /// An empty iterator yields the well-known empty-tree root, the digest of
+    /// the empty byte string (see [`empty_root`](Self::empty_root)).
+    ///
+    /// Hashes are kept inline as [`Hash`](struct@Hash) values, so each level
+    /// is a single allocation and hashing the leaves allocates nothing per
+    /// leaf.
+    pub fn merkle_root<I, L>(leaves: I) -> String
+    where
+        I: Iterator<Item = L>,
+        L: AsRef<[u8]>,
+    {
+        MerkleTreeBuilder::<L, D>::new(leaves).root_hex()
+    }
+
+    /// Like [`merkle_root`](Self::merkle_root), for a tree built with
+    /// `options`, e.g. [`TreeOptions::prehashed_leaves`] to use each leaf
+    /// as its own leaf hash.
+    pub fn merkle_root_with<I, L>(leaves: I, options: &TreeOptions) -> String
+    where
+        I: Iterator<Item = L>,
+        L: AsRef<[u8]>,
+    {
+        MerkleTreeBuilder::<L, D>::with_options(leaves, options.clone()).root_hex()
+    }
+
+    /// Root of a tree with no leaves: `H("")`.
+    ///
+    /// Hashed leaves carry a prefix byte, so this can never collide with the
+    /// root of a non-empty tree.
+    pub fn empty_root() -> String {
+        hex::encode(D::new().finalize())
+    }
+
+    /// Hashes two hex-encoded roots into their parent as an internal node,
+    /// so trees built separately can be joined without their leaves. The
+    /// result is the root over both leaf sets when the left tree is a
+    /// perfect tree at least as deep as the right one, such as two halves of
+    /// a power-of-two tree.
+    ///
+    /// Panics if either root is not a hex-encoded hash.
+    pub fn combine_roots(left_root: impl AsRef<str>, right_root: impl AsRef<str>) -> String {
+        let decode = |root: &str| {
+            hex::decode(root)
+                .ok()
+                .and_then(|bytes| Hash::from_slice(&bytes))
+                .unwrap_or_else(|| panic!("{root:?} is not a hex-encoded hash"))
+        };
+        let (left, right) = (decode(left_root.as_ref()), decode(right_root.as_ref()));
+        Self::hash_node(&left, &right).to_hex()
+    }
+
+    /// Builds the inclusion proof for the leaf at `leaf_index`, hashing every
+    /// leaf as [`merkle_root`](Self::merkle_root) does.
+    ///
+    /// In a single-leaf tree the root is the leaf hash itself, so the proof
+    /// of leaf 0 has no hashes and verifying it compares the leaf hash with
+    /// the root.
+    pub fn merkle_proof<I, L>(leaves: I, leaf_index: usize) -> Result<MerkleProof<L>, ProofError>
+    where
+        I: Iterator<Item = L>,
+        L: AsRef<[u8]>,
+    {
+        MerkleTreeBuilder::<L, D>::new(leaves).into_proof(leaf_index)
+    }
+
+    /// Builds the inclusion proof for the first leaf whose bytes equal
+    /// `leaf`, located as [`MerkleTreeBuilder::index_of`] does. When the
+    /// value occurs more than once, the proof is for its lowest index.
+    pub fn merkle_proof_by_value<I, L>(
+        leaves: I,
+        leaf: impl AsRef<[u8]>,
+    ) -> Result<MerkleProof<L>, ProofError>
+    where
+        I: Iterator<Item = L>,
+        L: AsRef<[u8]>,
+    {
+        let tree = MerkleTreeBuilder::<L, D>::new(leaves);
+        let index = tree.index_of(leaf).ok_or(ProofError::LeafNotFound)?;
+        tree.into_proof(index)
+    }
+
+    /// Like [`merkle_proof`](Self::merkle_proof), for a tree built with
+    /// `options`. Check the proof with
+    /// [`verify_proof_with`](Self::verify_proof_with) and the same options:
+    /// whether the leaves were pre-hashed is not recorded in the proof, and a
+    /// verifier that disagrees rejects it.
+    pub fn merkle_proof_with<I, L>(
+        leaves: I,
+        leaf_index: usize,
+        options: &TreeOptions,
+    ) -> Result<MerkleProof<L>, ProofError>
+    where
+        I: Iterator<Item = L>,
+        L: AsRef<[u8]>,
+    {
+        MerkleTreeBuilder::<L, D>::with_options(leaves, options.clone()).into_proof(leaf_index)
+    }
+
+    /// Builds the inclusion proof for the leaf at `leaf_index` along with the
+    /// root, both from a single pass over `leaves`.
+    pub fn merkle_proof_and_root<I, L>(
+        leaves: I,
+        leaf_index: usize,
+    ) -> Result<ProofWithRoot<L>, ProofError>
+    where
+        I: Iterator<Item = L>,
+        L: AsRef<[u8]>,
+    {
+        let tree = MerkleTreeBuilder::<L, D>::new(leaves);
+        let root = tree.root_hex();
+        Ok(ProofWithRoot {
+            proof: tree.into_proof(leaf_index)?,
+            root,
+        })
+    }
+
+    /// Checks that `proof` is consistent with a tree of `proof.num_of_leaves`
+    /// leaves and that it hashes to `root`.
+    ///
+    /// The hash count must be exactly [`proof_length`] for the claimed index
+    /// and size, so sibling hashes cannot be added or dropped. The claimed
+    /// size itself is not bound by the root, though: a sibling subtree can be
+    /// passed off as a single leaf, so one path may verify under more than one
+    /// leaf count. When the leaf count is known independently, check it with
+    /// [`verify_proof_for_size`](Self::verify_proof_for_size).
+    ///
+    /// [`path_bits`](MerkleProof::path_bits), when present, must be the
+    /// [`proof_directions`] of the claimed index and size.
+    pub fn verify_proof<T: AsRef<[u8]>>(
+        root: impl AsRef<str>,
+        proof: &MerkleProof<T>,
+    ) -> Result<(), VerifyError> {
+        Self::verify_proof_with(root, proof, &TreeOptions::default())
+    }
+
+    /// Like [`verify_proof`](Self::verify_proof), against the root bundled in
+    /// [`proof.root`](MerkleProof::root), failing with
+    /// [`VerifyError::MissingRoot`] when there is none.
+    ///
+    /// The bundled root is only as trustworthy as the proof's source; when a
+    /// trusted root is at hand, pass it to `verify_proof` instead.
+    pub fn verify_bundled_proof<T: AsRef<[u8]>>(proof: &MerkleProof<T>) -> Result<(), VerifyError> {
+        let root = proof.root.as_deref().ok_or(VerifyError::MissingRoot)?;
+        Self::verify_proof(root, proof)
+    }
+
+    /// Like [`verify_proof`](Self::verify_proof), for a tree the caller
+    /// knows to hold `num_of_leaves` leaves. The proof is rejected unless it
+    /// was built for exactly that size.
+    pub fn verify_proof_for_size<T: AsRef<[u8]>>(
+        root: impl AsRef<str>,
+        num_of_leaves: usize,
+        proof: &MerkleProof<T>,
+    ) -> Result<(), VerifyError> {
+        if proof.num_of_leaves != num_of_leaves {
+            return Err(VerifyError::LeafCountMismatch {
+                expected: num_of_leaves,
+                actual: proof.num_of_leaves,
+            });
+        }
+        Self::verify_proof(root, proof)
+    }
+
+    /// Like [`verify_proof`](Self::verify_proof), comparing the computed root
+    /// with `root` in constant time.
+    ///
+    /// Only that final comparison is hardened: hashing the path, decoding and
+    /// the checks on the proof's shape take time that depends on the proof.
+    pub fn verify_proof_ct<T: AsRef<[u8]>>(
+        root: impl AsRef<str>,
+        proof: &MerkleProof<T>,
+    ) -> Result<(), VerifyError> {
+        let computed = Self::path_root(proof, &PLAIN, |leaf| Self::leaf_hash(&PLAIN, leaf))?;
+        if bool::from(computed.to_hex().as_bytes().ct_eq(root.as_ref().as_bytes())) {
+            Ok(())
+        } else {
+            Err(VerifyError::RootMismatch)
+        }
+    }
+
+    /// Like [`verify_proof`](Self::verify_proof), for a verifier that knows
+    /// which leaf it expects. The proof is rejected unless it carries exactly
+    /// `expected_leaf`, and the path is hashed from `expected_leaf` rather
+    /// than from the carried content.
+    pub fn verify_proof_for_leaf<T: AsRef<[u8]>>(
+        root: impl AsRef<str>,
+        proof: &MerkleProof<T>,
+        expected_leaf: impl AsRef<[u8]>,
+    ) -> Result<(), VerifyError> {
+        let expected_leaf = expected_leaf.as_ref();
+        if proof.leaf_content.as_ref() != expected_leaf {
+            return Err(VerifyError::LeafMismatch);
+        }
+        Self::verify_path(root.as_ref(), proof, &PLAIN, |_| {
+            Self::leaf_hash(&PLAIN, expected_leaf)
+        })
+    }
+
+    /// Like [`verify_proof`](Self::verify_proof), for a tree built with
+    /// `options`.
+    pub fn verify_proof_with<T: AsRef<[u8]>>(
+        root: impl AsRef<str>,
+        proof: &MerkleProof<T>,
+        options: &TreeOptions,
+    ) -> Result<(), VerifyError> {
+        if Self::recompute_root_with(proof, options)? == root.as_ref() {
+            Ok(())
+        } else {
+            Err(VerifyError::RootMismatch)
+        }
+    }
+
+    /// The hex-encoded root that `proof` climbs to, without comparing it to
+    /// any expected root, for diagnosing a [`VerifyError::RootMismatch`].
+    /// A proof of the wrong shape fails as it would in
+    /// [`verify_proof`](Self::verify_proof).
+    pub fn recompute_root<T: AsRef<[u8]>>(proof: &MerkleProof<T>) -> Result<String, VerifyError> {
+        Self::recompute_root_with(proof, &PLAIN)
+    }
+
+    /// Like [`recompute_root`](Self::recompute_root), for a tree built with
+    /// `options`.
+    pub fn recompute_root_with<T: AsRef<[u8]>>(
+        proof: &MerkleProof<T>,
+        options: &TreeOptions,
+    ) -> Result<String, VerifyError> {
+        Self::path_root(proof, options, |leaf| Self::leaf_hash(options, leaf))
+            .map(|root| root.to_hex())
+    }
+
+    /// Returns the index of the first of `roots` that `proof` verifies
+    /// against, or `None` if it verifies against none of them. The path is
+    /// hashed once and the result compared with every candidate.
+    pub fn verify_against_any<R, T>(roots: &[R], proof: &MerkleProof<T>) -> Option<usize>
+    where
+        R: AsRef<str>,
+        T: AsRef<[u8]>,
+    {
+        let root = Self::path_root(proof, &PLAIN, |leaf| Self::leaf_hash(&PLAIN, leaf))
+            .ok()?
+            .to_hex();
+        roots
+            .iter()
+            .position(|candidate| candidate.as_ref() == root)
+    }
+
+    /// Returns the first of `strategies` under which `proof` verifies against
+    /// `root`, or `None` if it verifies under none of them, to accept proofs
+    /// from provers that disagree on [`TreeOptions::odd_nodes`].
+    ///
+    /// A path that never meets a lone node hashes the same under every
+    /// strategy, so its proof verifies under all of them and the first is
+    /// reported.
+    pub fn verify_proof_any_strategy<T: AsRef<[u8]>>(
+        root: impl AsRef<str>,
+        proof: &MerkleProof<T>,
+        strategies: &[OddNodeStrategy],
+    ) -> Option<OddNodeStrategy> {
+        strategies.iter().copied().find(|&odd_nodes| {
+            let options = TreeOptions {
+                odd_nodes,
+                ..TreeOptions::default()
+            };
+            Self::verify_proof_with(root.as_ref(), proof, &options).is_ok()
+        })
+    }
+
+    /// Shared body of the single-proof verifiers: checks the shape of `proof`
+    /// and that the leaf hash `leaf_hash` derives from its content climbs to
+    /// `root`.
+    fn verify_path<T>(
+        root: &str,
+        proof: &MerkleProof<T>,
+        options: &TreeOptions,
+        leaf_hash: impl FnOnce(&T) -> Option<Hash>,
+    ) -> Result<(), VerifyError> {
+        if root == Self::path_root(proof, options, leaf_hash)?.to_hex() {
+            Ok(())
+        } else {
+            Err(VerifyError::RootMismatch)
+        }
+    }
+
+    /// Checks the shape of `proof`, and its [`path_bits`](MerkleProof::path_bits)
+    /// if it has any, and returns the root its path climbs to.
+    fn path_root<T>(
+        proof: &MerkleProof<T>,
+        options: &TreeOptions,
+        leaf_hash: impl FnOnce(&T) -> Option<Hash>,
+    ) -> Result<Hash, VerifyError> {
+        Self::path_root_of(
+            &proof.leaf_content,
+            proof.leaf_index,
+            proof.num_of_leaves,
+            &proof.hashes,
+            &proof.path_bits,
+            options,
+            leaf_hash,
+        )
+    }
+
+    /// [`path_root`](Self::path_root) on the fields of a proof, so callers
+    /// holding them separately check and climb them the same way.
+    fn path_root_of<T: ?Sized>(
+        leaf_content: &T,
+        leaf_index: usize,
+        num_of_leaves: usize,
+        hashes: &[Hash],
+        path_bits: &[bool],
+        options: &TreeOptions,
+        leaf_hash: impl FnOnce(&T) -> Option<Hash>,
+    ) -> Result<Hash, VerifyError> {
+        check_path_shape(leaf_index, num_of_leaves, hashes.len())?;
+        check_path_bits(leaf_index, num_of_leaves, path_bits)?;
+
+        let Some(hash) = leaf_hash(leaf_content) else {
+            return Err(VerifyError::RootMismatch);
+        };
+        Ok(Self::climb(
+            hash,
+            leaf_index,
+            num_of_leaves,
+            hashes,
+            options,
+        ))
+    }
+
+    /// Hashes `hash`, the leaf hash at `leaf_index`, up to the root with
+    /// `siblings`, which must number [`proof_length`] for that leaf.
+    fn climb(
+        mut hash: Hash,
+        leaf_index: usize,
+        num_of_leaves: usize,
+        siblings: &[Hash],
+        options: &TreeOptions,
+    ) -> Hash {
+        let mut index = leaf_index;
+        let mut len = num_of_leaves;
+        let mut siblings = siblings.iter();
+
+        while len > 1 {
+            // A trailing odd node has no sibling in the proof.
+            hash = if index ^ 1 >= len {
+                Self::hash_lone(options, &hash)
+            } else {
+                let sibling_hash = siblings.next().expect("caller checked the proof length");
+                if index.is_multiple_of(2) {
+                    Self::hash_pair(options, &hash, sibling_hash)
+                } else {
+                    Self::hash_pair(options, sibling_hash, &hash)
+                }
+            };
+
+            index /= 2;
+            len = len.div_ceil(2);
+        }
+
+        hash
+    }
+
+    /// Like [`verify_proof`](Self::verify_proof), for callers that only need a yes/no.
+    pub fn verify_proof_bool<T: AsRef<[u8]>>(
+        root: impl AsRef<str>,
+        proof: &MerkleProof<T>,
+    ) -> bool {
+        Self::verify_proof(root, proof).is_ok()
+    }
+
+    /// Like [`verify_proof_bool`](Self::verify_proof_bool), with the proof's
+    /// fields passed separately, e.g. as read back from a database row,
+    /// instead of assembled into a [`MerkleProof`]. The fields are checked,
+    /// hashed and climbed by the same code as `verify_proof`, as for a proof
+    /// without [`path_bits`](MerkleProof::path_bits).
+    pub fn verify(
+        leaf_content: impl AsRef<[u8]>,
+        leaf_index: usize,
+        num_of_leaves: usize,
+        proof_hashes: &[Hash],
+        root: impl AsRef<str>,
+    ) -> bool {
+        Self::path_root_of(
+            leaf_content.as_ref(),
+            leaf_index,
+            num_of_leaves,
+            proof_hashes,
+            &[],
+            &PLAIN,
+            |leaf| Self::leaf_hash(&PLAIN, leaf),
+        )
+        .is_ok_and(|computed| computed.to_hex() == root.as_ref())
+    }
+
+    /// Verifies each of `proofs` against the same `root`, returning one result
+    /// per proof in the same order.
+    pub fn verify_proofs<T: AsRef<[u8]>>(
+        root: impl AsRef<str>,
+        proofs: &[MerkleProof<T>],
+    ) -> Vec<Result<(), VerifyError>> {
+        let root = root.as_ref();
+        proofs
+            .iter()
+            .map(|proof| Self::verify_proof(root, proof))
+            .collect()
+    }
+
+    /// Computes the hex-encoded root over leaves that are already leaf
+    /// hashes: each item is used as-is rather than hashed (see
+    /// [`TreeOptions::prehashed_leaves`]).
+    ///
+    /// Panics if an item is longer than [`MAX_HASH_LEN`] bytes.
+    pub fn merkle_root_of_hashes<I, H>(hashes: I) -> String
+    where
+        I: Iterator<Item = H>,
+        H: AsRef<[u8]>,
+    {
+        Self::merkle_root_with(hashes, &PREHASHED)
+    }
+
+    /// Builds the inclusion proof for the pre-hashed leaf at `leaf_index`.
+    /// `leaf_content` of the result is that leaf hash.
+    ///
+    /// Panics if an item is longer than [`MAX_HASH_LEN`] bytes.
+    pub fn merkle_proof_of_hashes<I, H>(
+        hashes: I,
+        leaf_index: usize,
+    ) -> Result<MerkleProof<H>, ProofError>
+    where
+        I: Iterator<Item = H>,
+        H: AsRef<[u8]>,
+    {
+        Self::merkle_proof_with(hashes, leaf_index, &PREHASHED)
+    }
+
+    /// Like [`verify_proof`](Self::verify_proof), for a proof from
+    /// [`merkle_proof_of_hashes`](Self::merkle_proof_of_hashes):
+    /// `leaf_content` is taken as the leaf hash and not hashed again.
+    pub fn verify_proof_of_hashes<T: AsRef<[u8]>>(
+        root: impl AsRef<str>,
+        proof: &MerkleProof<T>,
+    ) -> Result<(), VerifyError> {
+        Self::verify_proof_with(root, proof, &PREHASHED)
+    }
+
+    /// Like [`verify_proof`](Self::verify_proof), starting the walk from
+    /// `leaf_hash` (see [`hash_leaf`](Self::hash_leaf)), so the verifier
+    /// never needs the leaf content itself.
+    pub fn verify_proof_from_leaf_hash(
+        root: impl AsRef<str>,
+        leaf_hash: &Hash,
+        leaf_index: usize,
+        num_of_leaves: usize,
+        hashes: &[Hash],
+    ) -> Result<(), VerifyError> {
+        let proof = MerkleProof {
+            hashes: hashes.to_vec(),
+            num_of_leaves,
+            leaf_index,
+            leaf_content: (),
+            path_bits: Vec::new(),
+            root: None,
+        };
+        Self::verify_path(root.as_ref(), &proof, &PLAIN, |_| Some(*leaf_hash))
+    }
+
+    /// Patches `proof` after the leaf proven by `changed` took the leaf hash
+    /// `new_leaf_hash`, so it verifies against the new root without being
+    /// rebuilt.
+    ///
+    /// Exactly one of `proof`'s hashes covers the changed leaf; it is
+    /// recomputed by climbing from `new_leaf_hash` along `changed`'s path,
+    /// which the change itself leaves intact, so `changed` may be the proof
+    /// issued before the change. A proof of the changed leaf itself needs no
+    /// patching.
+    pub fn update_proof<T, U>(
+        proof: &mut MerkleProof<T>,
+        changed: &MerkleProof<U>,
+        new_leaf_hash: &Hash,
+    ) -> Result<(), VerifyError> {
+        proof.check_shape()?;
+        changed.check_shape()?;
+        if changed.num_of_leaves != proof.num_of_leaves {
+            return Err(VerifyError::LeafCountMismatch {
+                expected: proof.num_of_leaves,
+                actual: changed.num_of_leaves,
+            });
+        }
+
+        let (mut index, mut changed_index) = (proof.leaf_index, changed.leaf_index);
+        let mut len = proof.num_of_leaves;
+        let (mut slot, mut changed_siblings) = (0, changed.hashes.iter());
+        let mut hash = *new_leaf_hash;
+        while index != changed_index {
+            if index / 2 == changed_index / 2 {
+                // The changed subtree is the sibling at this level.
+                proof.hashes[slot] = hash;
+                break;
+            }
+            if index ^ 1 < len {
+                slot += 1;
+            }
+            hash = if changed_index ^ 1 >= len {
+                Self::hash_lone(&PLAIN, &hash)
+            } else {
+                let sibling = changed_siblings.next().expect("proof length checked above");
+                if changed_index.is_multiple_of(2) {
+                    Self::hash_pair(&PLAIN, &hash, sibling)
+                } else {
+                    Self::hash_pair(&PLAIN, sibling, &hash)
+                }
+            };
+            index /= 2;
+            changed_index /= 2;
+            len = len.div_ceil(2);
+        }
+        Ok(())
+    }
+
+    /// The leaf hash of `leaf` as `options` define it, or `None` for a
+    /// pre-hashed leaf longer than [`MAX_HASH_LEN`].
+    fn leaf_hash(options: &TreeOptions, leaf: impl AsRef<[u8]>) -> Option<Hash> {
+        if options.prehashed_leaves {
+            Hash::from_slice(leaf.as_ref())
+        } else {
+            Some(Self::hash_leaf_with(options, leaf))
+        }
+    }
+
+    /// A fresh hasher, with the tree's key absorbed if it has one.
+    fn new_hasher(options: &TreeOptions) -> D {
+        let mut hasher = D::new();
+        if let Some(key) = &options.key {
+            hasher.update((key.len() as u64).to_le_bytes());
+            hasher.update(key);
+        }
+        hasher
+    }
+
+    /// Hashes a leaf as `H(0x00 || leaf)`, the bottom-level hash every
+    /// proof starts from.
+    pub fn hash_leaf(leaf: impl AsRef<[u8]>) -> Hash {
+        Self::hash_leaf_with(&PLAIN, leaf)
+    }
+
+    /// [`hash_leaf`](Self::hash_leaf) under the key in `options`.
+    fn hash_leaf_with(options: &TreeOptions, leaf: impl AsRef<[u8]>) -> Hash {
+        let mut hasher = Self::new_hasher(options);
+        hasher.update([LEAF_PREFIX]);
+        hasher.update(leaf);
+        Hash::from_digest(hasher)
+    }
+
+    /// Hashes a whole level into the level above it.
+    ///
+    /// Even levels once took a `chunks_exact` path of their own; hashing
+    /// dominates either way, and `bench_even_level_chunks` measures the two
+    /// within noise of each other, so one path serves every level.
+    fn hash_level(options: &TreeOptions, level: &[Hash]) -> Vec<Hash> {
+        level
+            .chunks(2)
+            .map(|chunk| Self::hash_chunk(options, chunk))
+            .collect()
+    }
+
+    /// Hashes one `chunks(2)` group of a level into its parent. A trailing odd
+    /// node goes through [`hash_lone`](Self::hash_lone).
+    fn hash_chunk(options: &TreeOptions, chunk: &[Hash]) -> Hash {
+        match chunk {
+            [a, b] => Self::hash_pair(options, a, b),
+            [a] => Self::hash_lone(options, a),
+            _ => unreachable!(),
+        }
+    }
+
+    /// The parent of a trailing odd node, per [`TreeOptions::odd_nodes`]. By
+    /// default it is promoted unchanged, as in RFC 6962: pairing it with
+    /// itself would give `[.., c]` and `[.., c, c]` the same root
+    /// (CVE-2012-2459).
+    fn hash_lone(options: &TreeOptions, node: &Hash) -> Hash {
+        match options.odd_nodes {
+            OddNodeStrategy::Promote => *node,
+            OddNodeStrategy::Duplicate => Self::hash_pair(options, node, node),
+            OddNodeStrategy::PadZero => Self::hash_pair(options, node, &Self::zero_hash()),
+        }
+    }
+
+    /// The all-zero hash of the digest's output length, used for
+    /// [`OddNodeStrategy::PadZero`] and as the padding leaf of
+    /// [`TreeOptions::pad_to_power_of_two`].
+    fn zero_hash() -> Hash {
+        Hash::from_slice(&[0; MAX_HASH_LEN][..<D as Digest>::output_size()])
+            .expect("digest output exceeds MAX_HASH_LEN bytes")
+    }
+
+    /// Hashes the parent of `left` and `right`, ordering them as `options`
+    /// requires.
+    fn hash_pair(options: &TreeOptions, left: &Hash, right: &Hash) -> Hash {
+        let swap = match options.pair_order {
+            Some(PairComparator(compare)) => compare(left, right) == Ordering::Greater,
+            None => options.sorted_pairs && right < left,
+        };
+        let swap = swap != (options.child_order == ChildOrder::RightLeft);
+        let (left, right) = if swap { (right, left) } else { (left, right) };
+        Self::hash_children_with(options, [left, right])
+    }
+
+    /// Hashes an internal node as `H(0x01 || hex(left) || hex(right))`.
+    ///
+    /// Children are fed to the digest hex-encoded, as they were when hashes
+    /// were carried around as strings, so roots are unchanged.
+    fn hash_node(left: &Hash, right: &Hash) -> Hash {
+        Self::hash_children([left, right])
+    }
+
+    /// Hashes an internal node with any number of children as
+    /// `H(0x01 || hex(c0) || hex(c1) || ...)`.
+    fn hash_children<'a>(children: impl IntoIterator<Item = &'a Hash>) -> Hash {
+        Self::hash_children_with(&PLAIN, children)
+    }
+
+    /// [`hash_children`](Self::hash_children) under the key and node encoding
+    /// in `options`.
+    fn hash_children_with<'a>(
+        options: &TreeOptions,
+        children: impl IntoIterator<Item = &'a Hash>,
+    ) -> Hash {
+        let mut buf = [0u8; 2 * MAX_HASH_LEN];
+        let mut hasher = Self::new_hasher(options);
+        hasher.update([NODE_PREFIX]);
+        for child in children {
+            match options.node_encoding {
+                NodeEncoding::Hex => {
+                    let hex = &mut buf[..2 * child.len()];
+                    hex::encode_to_slice(child.as_bytes(), hex).expect("buffer sized for child");
+                    hasher.update(&*hex);
+                }
+                NodeEncoding::Raw => hasher.update(child.as_bytes()),
+            }
+        }
+        Hash::from_digest(hasher)
+    }
+}
+
+/// A tree built once from its leaves.
+///
+/// Every level is cached (leaf hashes at index 0, the root last), so
+/// [`root`](Self::root) is free and [`proof`](Self::proof) only reads
+/// O(log n) stored hashes instead of rehashing the tree.
+///
+/// The tree is `Send + Sync` when its leaves are, and every `&self` method
+/// only reads the cached levels, so an `Arc<MerkleTreeBuilder<L>>` can serve
+/// [`proof`](Self::proof) calls from many threads without a lock.
+#[derive(Debug, Clone)]
+pub struct MerkleTreeBuilder<L, D = Blake2b512> {
+    leaves: Vec<L>,
+    levels: Vec<Vec<Hash>>,
+    options: TreeOptions,
+    _digest: PhantomData<D>,
+}
+
+impl<L: AsRef<[u8]>, D: Digest> MerkleTreeBuilder<L, D> {
+    /// Hashes `leaves` and every level above them.
+    pub fn new<I>(leaves: I) -> Self
+    where
+        I: Iterator<Item = L>,
+    {
+        Self::with_options(leaves, TreeOptions::default())
+    }
+
+    /// Like [`new`](Self::new), hashing under `key` (see [`TreeOptions::key`]).
+    /// Verify its proofs with [`MerkleTree::verify_proof_with`] and the same
+    /// key in [`options`](Self::options).
+    pub fn with_key<I>(leaves: I, key: &[u8]) -> Self
+    where
+        I: Iterator<Item = L>,
+    {
+        Self::with_options(
+            leaves,
+            TreeOptions {
+                key: Some(key.to_vec()),
+                ..TreeOptions::default()
+            },
+        )
+    }
+
+    /// Like [`new`](Self::new), building the tree as `options` describe.
+    /// Proofs from it must be checked with [`MerkleTree::verify_proof_with`]
+    /// and the same options.
+    pub fn with_options<I>(leaves: I, options: TreeOptions) -> Self
+    where
+        I: Iterator<Item = L>,
+    {
+        Self::build_with_stats(leaves, options).0
+    }
+
+    /// Like [`with_options`](Self::with_options), also reporting how many
+    /// digest invocations the build took, for cost estimates. Proofs from the
+    /// built tree hash nothing.
+    pub fn build_with_stats<I>(leaves: I, options: TreeOptions) -> (Self, BuildStats)
+    where
+        I: Iterator<Item = L>,
+    {
+        let leaves: Vec<L> = leaves.collect();
+        let leaf_level = leaves
+            .iter()
+            .map(|leaf| leaf_hash_or_panic::<D>(&options, leaf))
+            .collect();
+        let leaf_hashes = if options.prehashed_leaves {
+            0
+        } else {
+            leaves.len()
+        };
+        let (tree, node_hashes) = Self::from_leaf_level(leaves, leaf_level, options);
+        let hash_calls = leaf_hashes + node_hashes;
+
+        let stats = BuildStats {
+            hash_calls,
+            levels: tree.levels.len(),
+        };
+        (tree, stats)
+    }
+
+    /// Builds the levels above `leaf_level`, the hashes of `leaves`,
+    /// returning the tree and the number of node hashes computed.
+    fn from_leaf_level(
+        leaves: Vec<L>,
+        leaf_level: Vec<Hash>,
+        options: TreeOptions,
+    ) -> (Self, usize) {
+        let mut tree = MerkleTreeBuilder {
+            levels: vec![leaf_level],
+            leaves,
+            options,
+            _digest: PhantomData,
+        };
+        tree.pad_leaf_level();
+        let hash_calls = tree.hash_levels();
+        (tree, hash_calls)
+    }
+
+    /// Like [`with_options`](Self::with_options), applying `policy` to
+    /// leaves whose leaf hash repeats an earlier one, so that a tree meant to
+    /// commit to a set cannot hold an element twice. Only construction is
+    /// checked: [`append`](Self::append) and
+    /// [`update_leaf`](Self::update_leaf) accept any leaf.
+    pub fn with_duplicate_policy<I>(
+        leaves: I,
+        options: TreeOptions,
+        policy: DuplicatePolicy,
+    ) -> Result<Self, ProofError>
+    where
+        I: Iterator<Item = L>,
+    {
+        if policy == DuplicatePolicy::Allow {
+            return Ok(Self::with_options(leaves, options));
+        }
+
+        let mut seen = BTreeMap::new();
+        let mut kept = Vec::new();
+        let mut leaf_level = Vec::new();
+        for (index, leaf) in leaves.enumerate() {
+            let hash = leaf_hash_or_panic::<D>(&options, &leaf);
+            if let Some(&first) = seen.get(&hash) {
+                if policy == DuplicatePolicy::Reject {
+                    return Err(ProofError::DuplicateLeaf {
+                        first,
+                        duplicate: index,
+                    });
+                }
+                continue;
+            }
+            seen.insert(hash, index);
+            kept.push(leaf);
+            leaf_level.push(hash);
+        }
+        Ok(Self::from_leaf_level(kept, leaf_level, options).0)
+    }
+
+    /// Appends sentinel hashes to the leaf level up to the next power of two,
+    /// if the options ask for it.
+    fn pad_leaf_level(&mut self) {
+        if self.options.pad_to_power_of_two && !self.leaves.is_empty() {
+            let width = self
+                .leaves
+                .len()
+                .checked_next_power_of_two()
+                .expect("padded leaf count exceeds usize::MAX");
+            self.levels[0].resize(width, MerkleTree::<D>::zero_hash());
+        }
+    }
+
+    /// Rebuilds every level above the leaf hashes, returning the number of
+    /// digest invocations that took.
+    fn hash_levels(&mut self) -> usize {
+        self.levels.truncate(1);
+        let mut hash_calls = 0;
+        while self.levels[self.levels.len() - 1].len() > 1 {
+            let level = &self.levels[self.levels.len() - 1];
+            hash_calls += level.len() / 2;
+            if level.len() % 2 == 1 && self.options.odd_nodes != OddNodeStrategy::Promote {
+                hash_calls += 1;
+            }
+            let next_level = MerkleTree::<D>::hash_level(&self.options, level);
+            self.levels.push(next_level);
+        }
+        hash_calls
+    }
+
+    /// Number of leaf slots, padding included, and so the `num_of_leaves` of
+    /// this tree's proofs.
+    fn width(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// The options this tree was built with.
+    pub fn options(&self) -> &TreeOptions {
+        &self.options
+    }
+
+    /// Number of leaves in the tree.
+    pub fn leaf_count(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Index of the first leaf whose bytes equal `leaf`, for requesting its
+    /// [`proof`](Self::proof). A tree may hold the same leaf more than once;
+    /// [`indices_of`](Self::indices_of) finds every copy.
+    pub fn index_of(&self, leaf: impl AsRef<[u8]>) -> Option<usize> {
+        let leaf = leaf.as_ref();
+        self.leaves
+            .iter()
+            .position(|candidate| candidate.as_ref() == leaf)
+    }
+
+    /// Indices of every leaf whose bytes equal `leaf`, in increasing order.
+    pub fn indices_of(&self, leaf: impl AsRef<[u8]>) -> Vec<usize> {
+        let leaf = leaf.as_ref();
+        self.leaves
+            .iter()
+            .enumerate()
+            .filter(|(_, candidate)| candidate.as_ref() == leaf)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Every node hash of the tree, one level per entry: leaf hashes at index
+    /// 0 (followed by any sentinel padding) and the root alone in the last
+    /// level. An empty tree has a single empty level.
+    pub fn levels(&self) -> &[Vec<Hash>] {
+        &self.levels
+    }
+
+    /// The hashes of the leaves in index order, without any sentinel
+    /// padding, for rebuilding the tree elsewhere (see
+    /// [`MerkleTree::merkle_root_of_hashes`]) or comparing leaf sets without
+    /// the leaves themselves.
+    pub fn leaf_hashes(&self) -> &[Hash] {
+        &self.levels[0][..self.leaves.len()]
+    }
+
+    /// The hex-encoded hash at `position` in `level` (0 for leaf hashes, as
+    /// in [`levels`](Self::levels)), or `None` if there is no such node.
+    pub fn node_hash(&self, level: usize, position: usize) -> Option<String> {
+        self.levels
+            .get(level)?
+            .get(position)
+            .map(|hash| hash.to_hex())
+    }
+
+    /// The root, or the digest of [`MerkleTree::empty_root`] when there are
+    /// no leaves (the hash of just the key, for a keyed tree).
+    pub fn root(&self) -> Root {
+        Root(self.root_hash())
+    }
+
+    /// The hex-encoded root, as accepted by the verifiers.
+    pub fn root_hex(&self) -> String {
+        self.root_hash().to_hex()
+    }
+
+    /// The raw digest bytes of the root, without a hex round trip.
+    pub fn root_bytes(&self) -> Vec<u8> {
+        self.root_hash().as_bytes().to_vec()
+    }
+
+    fn root_hash(&self) -> Hash {
+        match self.levels.last().and_then(|level| level.first()) {
+            Some(root) => *root,
+            None => Hash::from_digest(MerkleTree::<D>::new_hasher(&self.options)),
+        }
+    }
+
+    /// Appends `leaf` and returns the new root.
+    ///
+    /// Only the rightmost spine is rehashed, so this costs O(log n). A padded
+    /// tree fills its next sentinel slot the same way, and rebuilds once its
+    /// padding runs out and the width doubles.
+    pub fn append(&mut self, leaf: L) -> Root {
+        let index = self.leaves.len();
+        let hash = leaf_hash_or_panic::<D>(&self.options, &leaf);
+        self.leaves.push(leaf);
+        if index < self.width() {
+            self.levels[0][index] = hash;
+        } else {
+            self.levels[0].push(hash);
+            if self.options.pad_to_power_of_two && index > 0 {
+                self.pad_leaf_level();
+                self.hash_levels();
+                return self.root();
+            }
+        }
+        self.rehash_path(index);
+        self.root()
+    }
+
+    /// Drops every leaf from `new_len` on and returns the new root, the
+    /// same as building the tree over the first `new_len` leaves. Undoes
+    /// [`append`](Self::append); does nothing if the tree has no more than
+    /// `new_len` leaves.
+    ///
+    /// Only the new rightmost spine is rehashed, so this costs O(log n). A
+    /// padded tree is rebuilt instead, since its dropped slots turn back into
+    /// padding.
+    pub fn truncate(&mut self, new_len: usize) -> Root {
+        if new_len >= self.leaves.len() {
+            return self.root();
+        }
+
+        self.leaves.truncate(new_len);
+        self.levels[0].truncate(new_len);
+        if self.options.pad_to_power_of_two {
+            self.pad_leaf_level();
+            self.hash_levels();
+            return self.root();
+        }
+
+        let mut level = 1;
+        while level < self.levels.len() && self.levels[level - 1].len() > 1 {
+            let width = self.levels[level - 1].len().div_ceil(2);
+            self.levels[level].truncate(width);
+            level += 1;
+        }
+        self.levels.truncate(level);
+        if new_len > 0 {
+            self.rehash_path(new_len - 1);
+        }
+        self.root()
+    }
+
+    /// Replaces the leaf at `index` and returns the new root.
+    ///
+    /// Only the O(log n) nodes between that leaf and the root are rehashed.
+    pub fn update_leaf(&mut self, index: usize, new_value: L) -> Result<Root, ProofError> {
+        if index >= self.leaves.len() {
+            return Err(ProofError::IndexOutOfRange {
+                index,
+                len: self.leaves.len(),
+            });
+        }
+
+        self.levels[0][index] = leaf_hash_or_panic::<D>(&self.options, &new_value);
+        self.leaves[index] = new_value;
+        self.rehash_path(index);
+        Ok(self.root())
+    }
+
+    /// Recomputes every ancestor of the leaf at `index`, adding a level on top
+    /// if the tree grew.
+    fn rehash_path(&mut self, mut index: usize) {
+        let mut level = 0;
+        while self.levels[level].len() > 1 {
+            let nodes = &self.levels[level];
+            let left = index & !1;
+            let parent = match nodes.get(left + 1) {
+                Some(right) => MerkleTree::<D>::hash_pair(&self.options, &nodes[left], right),
+                None => MerkleTree::<D>::hash_lone(&self.options, &nodes[left]),
+            };
+
+            if level + 1 == self.levels.len() {
+                self.levels.push(Vec::new());
+            }
+            let next_level = &mut self.levels[level + 1];
+            match next_level.get_mut(left / 2) {
+                Some(node) => *node = parent,
+                None => next_level.push(parent),
+            }
+            index /= 2;
+            level += 1;
+        }
+    }
+
+    /// Indices of the leaves that differ between this tree and `other`, in
+    /// increasing order.
+    ///
+    /// Both trees are walked from the root down, descending only into
+    /// subtrees whose hashes differ, so `k` changed leaves cost O(k log n)
+    /// comparisons. Trees built with different options differ everywhere.
+    ///
+    /// Panics if the trees have different leaf counts.
+    pub fn diff(&self, other: &Self) -> Vec<usize> {
+        assert_eq!(
+            self.leaves.len(),
+            other.leaves.len(),
+            "cannot diff trees with different leaf counts"
+        );
+
+        let mut changed = Vec::new();
+        if self.leaves.is_empty() {
+            return changed;
+        }
+        let mut pending = vec![(self.levels.len() - 1, 0)];
+        while let Some((level, index)) = pending.pop() {
+            if self.levels[level][index] == other.levels[level][index] {
+                continue;
+            }
+            if level == 0 {
+                changed.push(index);
+                continue;
+            }
+            // Right child first, so the left subtree is reported first.
+            let left = 2 * index;
+            if left + 1 < self.levels[level - 1].len() {
+                pending.push((level - 1, left + 1));
+            }
+            pending.push((level - 1, left));
+        }
+        changed
+    }
+
+    /// Builds the inclusion proof for the leaf at `leaf_index` from the
+    /// cached levels in O(log n), without rehashing anything. The only leaf
+    /// read is the one cloned into `leaf_content`.
+    pub fn proof(&self, leaf_index: usize) -> Result<MerkleProof<L>, ProofError>
+    where
+        L: Clone,
+    {
+        let hashes = self.proof_hashes(leaf_index)?;
+        Ok(MerkleProof {
+            hashes,
+            num_of_leaves: self.width(),
+            leaf_index,
+            leaf_content: self.leaves[leaf_index].clone(),
+            path_bits: proof_directions(leaf_index, self.width()),
+            root: None,
+        })
+    }
+
+    /// Like [`proof`](Self::proof), bundling the current root into the proof.
+    /// [`MerkleProof::verify`] checks it for a default Blake2b-512 tree;
+    /// otherwise pass `proof.root` to [`MerkleTree::verify_proof_with`].
+    pub fn bundled_proof(&self, leaf_index: usize) -> Result<MerkleProof<L>, ProofError>
+    where
+        L: Clone,
+    {
+        Ok(self.proof(leaf_index)?.with_root(self.root_hex()))
+    }
+
+    /// Like [`proof`](Self::proof), consuming the tree so the leaf is moved
+    /// into the proof rather than cloned.
+    pub fn into_proof(mut self, leaf_index: usize) -> Result<MerkleProof<L>, ProofError> {
+        let hashes = self.proof_hashes(leaf_index)?;
+        Ok(MerkleProof {
+            hashes,
+            num_of_leaves: self.width(),
+            leaf_index,
+            path_bits: proof_directions(leaf_index, self.width()),
+            leaf_content: self.leaves.swap_remove(leaf_index),
+            root: None,
+        })
+    }
+
+    /// Sibling hashes on the path from the leaf at `leaf_index` to the root.
+    fn proof_hashes(&self, leaf_index: usize) -> Result<Vec<Hash>, ProofError> {
+        self.check_leaf_index(leaf_index)?;
+        sibling_path(&self.levels, leaf_index)
+    }
+
+    /// Checks that `leaf_index` addresses a real leaf; padding slots have no
+    /// leaf to prove.
+    pub(crate) fn check_leaf_index(&self, leaf_index: usize) -> Result<(), ProofError> {
+        if self.leaves.is_empty() {
+            return Err(ProofError::EmptyTree);
+        }
+        if leaf_index >= self.leaves.len() {
+            return Err(ProofError::IndexOutOfRange {
+                index: leaf_index,
+                len: self.leaves.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Proofs for every leaf in index order, each read from the cached
+    /// levels in O(log n), so O(n log n) for the whole tree.
+    pub fn all_proofs(&self) -> impl Iterator<Item = MerkleProof<L>> + '_
+    where
+        L: Clone,
+    {
+        (0..self.leaves.len()).map(|index| self.proof(index).expect("index is in range"))
+    }
+
+    /// Like [`proof`](Self::proof), also returning the current root.
+    pub fn proof_and_root(&self, leaf_index: usize) -> Result<ProofWithRoot<L>, ProofError>
+    where
+        L: Clone,
+    {
+        Ok(ProofWithRoot {
+            proof: self.proof(leaf_index)?,
+            root: self.root_hex(),
+        })
+    }
+}
+
+/// Collects leaves into a tree, like [`MerkleTree::from_leaves`]. An empty
+/// iterator gives the empty tree.
+impl<L: AsRef<[u8]>, D: Digest> FromIterator<L> for MerkleTreeBuilder<L, D> {
+    fn from_iter<I: IntoIterator<Item = L>>(leaves: I) -> Self {
+        Self::new(leaves.into_iter())
+    }
+}
+
+/// Builds a tree over copies of the leaves, refusing an empty slice with
+/// [`ProofError::EmptyTree`] since such a tree has nothing to prove.
+impl<L: AsRef<[u8]> + Clone, D: Digest> TryFrom<&[L]> for MerkleTreeBuilder<L, D> {
+    type Error = ProofError;
+
+    fn try_from(leaves: &[L]) -> Result<Self, ProofError> {
+        if leaves.is_empty() {
+            return Err(ProofError::EmptyTree);
+        }
+        Ok(Self::new(leaves.iter().cloned()))
+    }
+}
+
+/// Sibling hashes on the path from the leaf at `leaf_index` to the root of
+/// a tree with the given `levels`, leaf hashes first.
+pub(crate) fn sibling_path(
+    levels: &[Vec<Hash>],
+    leaf_index: usize,
+) -> Result<Vec<Hash>, ProofError> {
+    let len = levels[0].len();
+    if len == 0 {
+        return Err(ProofError::EmptyTree);
+    }
+    if leaf_index >= len {
+        return Err(ProofError::IndexOutOfRange {
+            index: leaf_index,
+            len,
+        });
+    }
+
+    let mut index = leaf_index;
+    Ok(levels[..levels.len() - 1]
+        .iter()
+        .filter_map(|level| {
+            // A trailing odd node has no sibling to record.
+            let sibling = level.get(index ^ 1).copied();
+            index /= 2;
+            sibling
+        })
+        .collect())
+}
+
+/// [`MerkleTree::leaf_hash`], panicking on a pre-hashed leaf that is too
+/// long to be a hash.
+fn leaf_hash_or_panic<D: Digest>(options: &TreeOptions, leaf: impl AsRef<[u8]>) -> Hash {
+    let len = leaf.as_ref().len();
+    MerkleTree::<D>::leaf_hash(options, leaf)
+        .unwrap_or_else(|| panic!("pre-hashed leaf of {len} bytes exceeds MAX_HASH_LEN"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::Sha256;
+
+    #[test]
+    fn test_merkle_root() {
+        let data = ["a", "b", "c", "d", "e"];
+        let root = Blake2bTree::merkle_root(data.iter().cloned().map(String::from));
+        assert!(!root.is_empty(), "Root hash should not be empty");
+    }
+
+    #[test]
+    fn test_merkle_proof() {
+        let data = ["a", "b", "c", "d", "e"];
+        let proof = Blake2bTree::merkle_proof(data.iter().cloned().map(String::from), 1).unwrap();
+        assert_eq!(proof.leaf_index, 1);
+        assert_eq!(proof.leaf_content, "b");
+    }
+
+    #[test]
+    fn test_verify_proof() {
+        let data = ["a", "b", "c", "d", "e"];
+        let root = Blake2bTree::merkle_root(data.iter().cloned().map(String::from));
+        let proof = Blake2bTree::merkle_proof(data.iter().cloned().map(String::from), 1).unwrap();
+        assert!(
+            Blake2bTree::verify_proof(&root, &proof).is_ok(),
+            "Proof should be valid"
+        );
+    }
+
+    #[test]
+    fn test_odd_number_of_leaves() {
+        let data = ["a", "b", "c", "d", "e", "f", "g"];
+        let root = Blake2bTree::merkle_root(data.iter().cloned().map(String::from));
+        let proof = Blake2bTree::merkle_proof(data.iter().cloned().map(String::from), 4).unwrap();
+        assert!(
+            Blake2bTree::verify_proof(&root, &proof).is_ok(),
+            "Proof should be valid"
+        );
+    }
+
+    #[test]
+    fn test_generic_digest_roots() {
+        let data = ["a", "b", "c", "d", "e"];
+        let blake = Blake2bTree::merkle_root(data.iter().cloned().map(String::from));
+        let sha = MerkleTree::<Sha256>::merkle_root(data.iter().cloned().map(String::from));
+
+        assert_ne!(blake, sha, "Different digests should give different roots");
+        assert_eq!(blake.len(), 128);
+        assert_eq!(sha.len(), 64);
+        assert_eq!(blake, "b6e6178307582518d07fc7433472d7876338fb1b756c47319360b89d05533cd2b0a842416db0b0b5e7833fc0a40880c2d5754bd74e61c4d6abf9e9bc1aa0ae68");
+        assert_eq!(
+            sha,
+            "c9481669d120766d583b0e0980a42a8b75f9535b3630d95626550bdc70c9d19d"
+        );
+    }
+
+    #[test]
+    fn test_generic_digest_proof() {
+        let data = ["a", "b", "c", "d", "e"];
+        let root = MerkleTree::<Sha256>::merkle_root(data.iter().cloned().map(String::from));
+        let proof =
+            MerkleTree::<Sha256>::merkle_proof(data.iter().cloned().map(String::from), 3).unwrap();
+        assert!(MerkleTree::<Sha256>::verify_proof(&root, &proof).is_ok());
+        assert!(Blake2bTree::verify_proof(&root, &proof).is_err());
+    }
+
+    fn assert_proofs_use_output_size<D: Digest>() {
+        let data: Vec<String> = (0..7).map(|i| format!("leaf-{i}")).collect();
+        let options = TreeOptions {
+            key: Some(b"tree".to_vec()),
+            ..TreeOptions::default()
+        };
+        let tree = MerkleTreeBuilder::<_, D>::with_options(data.iter(), options.clone());
+        assert_eq!(tree.root_bytes().len(), <D as Digest>::output_size());
+        for i in 0..data.len() {
+            let proof = tree.proof(i).unwrap();
+            assert!(proof
+                .hashes
+                .iter()
+                .all(|hash| hash.len() == <D as Digest>::output_size()));
+            let decoded = MerkleProof::from_bytes(&proof.to_bytes().unwrap()).unwrap();
+            assert!(
+                MerkleTree::<D>::verify_proof_with(tree.root_hex(), &decoded, &options).is_ok()
+            );
+        }
+
+        let plain = MerkleTree::<D>::merkle_proof(data.iter(), 3).unwrap();
+        assert!(plain
+            .hashes
+            .iter()
+            .all(|hash| hash.len() == <D as Digest>::output_size()));
+        assert!(
+            MerkleTree::<D>::verify_proof(MerkleTree::<D>::merkle_root(data.iter()), &plain)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_256_bit_blake2_trees() {
+        assert_proofs_use_output_size::<Blake2s256>();
+        assert_proofs_use_output_size::<Blake2b<U32>>();
+        assert_proofs_use_output_size::<Blake2b512>();
+
+        let data = ["a", "b", "c", "d", "e"];
+        let blake2s = Blake2sTree::merkle_root(data.iter());
+        let blake2b = Blake2b256Tree::merkle_root(data.iter());
+        assert_eq!(blake2s.len(), 64);
+        assert_eq!(blake2b.len(), 64);
+        assert_ne!(blake2s, blake2b);
+        assert!(!Blake2bTree::merkle_root(data.iter()).starts_with(&blake2b));
+    }
+
+    #[test]
+    fn test_raw_node_encoding() {
+        let data = ["a", "b", "c", "d", "e"];
+        let raw = TreeOptions {
+            node_encoding: NodeEncoding::Raw,
+            ..TreeOptions::default()
+        };
+        assert_eq!(
+            Blake2bTree::merkle_root_with(data.iter(), &TreeOptions::default()),
+            "b6e6178307582518d07fc7433472d7876338fb1b756c47319360b89d05533cd2b0a842416db0b0b5e7833fc0a40880c2d5754bd74e61c4d6abf9e9bc1aa0ae68"
+        );
+        let root = Blake2bTree::merkle_root_with(data.iter(), &raw);
+        assert_eq!(root, "9b46bb43f208a906ee650b77436ae5eedc84105b2579f5547bf3736e595e8baf6363f787ab6c296eb2c401c22b0277fbe8050ace2a89a7d48f884c2fff5a3c3a");
+        for i in 0..data.len() {
+            let proof = Blake2bTree::merkle_proof_with(data.iter(), i, &raw).unwrap();
+            assert!(Blake2bTree::verify_proof_with(&root, &proof, &raw).is_ok());
+            assert_eq!(
+                Blake2bTree::verify_proof(&root, &proof),
+                Err(VerifyError::RootMismatch)
+            );
+        }
+
+        // The eight-leaf test vector of the RFC 6962 reference implementation.
+        let leaves: [&[u8]; 8] = [
+            b"",
+            b"\x00",
+            b"\x10",
+            b"\x20\x21",
+            b"\x30\x31",
+            b"\x40\x41\x42\x43",
+            b"\x50\x51\x52\x53\x54\x55\x56\x57",
+            b"\x60\x61\x62\x63\x64\x65\x66\x67\x68\x69\x6a\x6b\x6c\x6d\x6e\x6f",
+        ];
+        assert_eq!(
+            MerkleTree::<Sha256>::merkle_root_with(leaves.iter(), &raw),
+            "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328"
+        );
+    }
+
+    #[test]
+    fn test_node_hashing_matches_string_concatenation() {
+        // The original implementation built `left_hex + &right_hex` and hashed
+        // the string; hashing the children through a stack buffer must agree.
+        let leaf = |s: &String| hex::encode(Blake2b512::digest([b"\x00", s.as_bytes()].concat()));
+        let node = |l: &String, r: &String| {
+            let concatenated = l.clone() + r;
+            hex::encode(Blake2b512::digest(
+                [b"\x01", concatenated.as_bytes()].concat(),
+            ))
+        };
+
+        for n in 1..20 {
+            let data: Vec<String> = (0..n).map(|i| format!("leaf-{i}")).collect();
+            let mut level: Vec<String> = data.iter().map(leaf).collect();
+            while level.len() > 1 {
+                level = level
+                    .chunks(2)
+                    .map(|pair| match pair {
+                        [l, r] => node(l, r),
+                        [lone] => lone.clone(),
+                        _ => unreachable!(),
+                    })
+                    .collect();
+            }
+            assert_eq!(
+                Blake2bTree::merkle_root(data.iter()),
+                level[0],
+                "{n} leaves"
+            );
+        }
+    }
+
+    #[test]
+    fn test_internal_node_as_leaf_is_rejected() {
+        let data = ["a", "b", "c", "d"];
+        let root = Blake2bTree::merkle_root(data.iter().cloned().map(String::from));
+
+        // Present the parent of "a" and "b" as if it were a leaf of a two-leaf tree.
+        let (a, b) = (Blake2bTree::hash_leaf("a"), Blake2bTree::hash_leaf("b"));
+        let left = Blake2bTree::hash_node(&a, &b);
+        let right =
+            Blake2bTree::hash_node(&Blake2bTree::hash_leaf("c"), &Blake2bTree::hash_leaf("d"));
+        assert_eq!(Blake2bTree::hash_node(&left, &right).to_hex(), root);
+
+        let forged = MerkleProof {
+            hashes: vec![right],
+            num_of_leaves: 2,
+            leaf_index: 0,
+            leaf_content: a.to_hex() + &b.to_hex(),
+            path_bits: Vec::new(),
+            root: None,
+        };
+        assert_eq!(
+            Blake2bTree::verify_proof(&root, &forged),
+            Err(VerifyError::RootMismatch),
+            "Internal node must not verify as a leaf"
+        );
+        let with_bits = MerkleProof {
+            path_bits: vec![false],
+            ..forged
+        };
+        assert_eq!(
+            Blake2bTree::verify_proof(&root, &with_bits),
+            Err(VerifyError::RootMismatch)
+        );
+    }
+
+    #[test]
+    fn test_verify_errors() {
+        let data = ["a", "b", "c", "d", "e"];
+        let root = Blake2bTree::merkle_root(data.iter().cloned().map(String::from));
+        let proof = Blake2bTree::merkle_proof(data.iter().cloned().map(String::from), 1).unwrap();
+
+        let wrong_root = Blake2bTree::merkle_root(data[..4].iter().cloned().map(String::from));
+        assert_eq!(
+            Blake2bTree::verify_proof(&wrong_root, &proof),
+            Err(VerifyError::RootMismatch)
+        );
+
+        let truncated = MerkleProof {
+            hashes: proof.hashes[1..].to_vec(),
+            num_of_leaves: proof.num_of_leaves,
+            leaf_index: proof.leaf_index,
+            leaf_content: proof.leaf_content.clone(),
+            path_bits: Vec::new(),
+            root: None,
+        };
+        assert_eq!(
+            Blake2bTree::verify_proof(&root, &truncated),
+            Err(VerifyError::InconsistentProofLength {
+                expected: 3,
+                actual: 2
+            })
+        );
+
+        let out_of_range = MerkleProof {
+            hashes: proof.hashes.clone(),
+            num_of_leaves: proof.num_of_leaves,
+            leaf_index: 5,
+            leaf_content: proof.leaf_content.clone(),
+            path_bits: Vec::new(),
+            root: None,
+        };
+        assert_eq!(
+            Blake2bTree::verify_proof(&root, &out_of_range),
+            Err(VerifyError::LeafIndexOutOfRange {
+                index: 5,
+                num_of_leaves: 5
+            })
+        );
+
+        // The same checks come first when the proof carries its path bits.
+        assert_eq!(proof.path_bits, proof_directions(1, 5));
+        let truncated = MerkleProof {
+            path_bits: proof.path_bits.clone(),
+            ..truncated
+        };
+        assert_eq!(
+            Blake2bTree::verify_proof(&root, &truncated),
+            Err(VerifyError::InconsistentProofLength {
+                expected: 3,
+                actual: 2
+            })
+        );
+        let out_of_range = MerkleProof {
+            path_bits: proof.path_bits.clone(),
+            ..out_of_range
+        };
+        assert_eq!(
+            Blake2bTree::verify_proof(&root, &out_of_range),
+            Err(VerifyError::LeafIndexOutOfRange {
+                index: 5,
+                num_of_leaves: 5
+            })
+        );
+    }
+
+    #[test]
+    fn test_out_of_range_index_rejected_before_hashing() {
+        let data: Vec<String> = (0..8).map(|i| format!("leaf-{i}")).collect();
+        let root = Blake2bTree::merkle_root(data.iter());
+        let mut proof = Blake2bTree::merkle_proof(data.iter(), 4).unwrap();
+        proof.leaf_index = 100;
+        let expected = Err(VerifyError::LeafIndexOutOfRange {
+            index: 100,
+            num_of_leaves: 8,
+        });
+        assert_eq!(Blake2bTree::verify_proof(&root, &proof), expected);
+        assert_eq!(Blake2bTree::verify_proof_ct(&root, &proof), expected);
+        assert_eq!(
+            Blake2bTree::verify_proof_for_leaf(&root, &proof, &data[4]),
+            expected
+        );
+        assert_eq!(Blake2bTree::verify_against_any(&[&root], &proof), None);
+    }
+
+    #[test]
+    fn test_reshaped_proofs_are_rejected() {
+        let data = ["a", "b", "c"];
+        let root = Blake2bTree::merkle_root(data.iter());
+        let proof = Blake2bTree::merkle_proof(data.iter(), 0).unwrap();
+        assert!(Blake2bTree::verify_proof_for_size(&root, 3, &proof).is_ok());
+
+        // In a tree of 4 leaves the second sibling would be the parent of
+        // leaves 2 and 3. Claiming that the lone leaf "c" is that parent
+        // gives a path that hashes to the same root.
+        let mut reshaped = proof.clone();
+        reshaped.num_of_leaves = 4;
+        assert!(Blake2bTree::verify_proof(&root, &reshaped).is_ok());
+        assert_eq!(
+            Blake2bTree::verify_proof_for_size(&root, 3, &reshaped),
+            Err(VerifyError::LeafCountMismatch {
+                expected: 3,
+                actual: 4
+            })
+        );
+
+        // Padding or truncating the hashes never fits the claimed size.
+        let mut padded = proof.clone();
+        padded.hashes.push(proof.hashes[0]);
+        let mut truncated = proof.clone();
+        truncated.hashes.pop();
+        for forged in [padded, truncated] {
+            assert!(matches!(
+                Blake2bTree::verify_proof_for_size(&root, 3, &forged),
+                Err(VerifyError::InconsistentProofLength { .. })
+            ));
+        }
+
+        // Moving the leaf to the lone position changes the expected shape.
+        let mut moved = proof.clone();
+        moved.leaf_index = 2;
+        assert!(Blake2bTree::verify_proof_for_size(&root, 3, &moved).is_err());
+    }
+
+    #[test]
+    fn test_verify_proof_ct() {
+        let data = ["a", "b", "c", "d", "e"];
+        let root = Blake2bTree::merkle_root(data.iter());
+        for i in 0..data.len() {
+            let proof = Blake2bTree::merkle_proof(data.iter(), i).unwrap();
+            assert_eq!(Blake2bTree::verify_proof_ct(&root, &proof), Ok(()));
+
+            let mut forged = proof.clone();
+            forged.leaf_content = &"x";
+            for (root, proof) in [(root.as_str(), &forged), (&root[..64], &proof)] {
+                assert_eq!(
+                    Blake2bTree::verify_proof_ct(root, proof),
+                    Blake2bTree::verify_proof(root, proof)
+                );
+            }
+        }
+
+        let mut truncated = Blake2bTree::merkle_proof(data.iter(), 0).unwrap();
+        truncated.hashes.pop();
+        assert_eq!(
+            Blake2bTree::verify_proof_ct(&root, &truncated),
+            Blake2bTree::verify_proof(&root, &truncated)
+        );
+    }
+
+    #[test]
+    fn test_verify_proof_for_leaf() {
+        let data = ["a", "b", "c", "d", "e"];
+        let root = Blake2bTree::merkle_root(data.iter());
+        let proof = Blake2bTree::merkle_proof(data.iter(), 2).unwrap();
+        assert_eq!(
+            Blake2bTree::verify_proof_for_leaf(&root, &proof, "c"),
+            Ok(())
+        );
+
+        // A valid proof for another leaf is not evidence for the expected one.
+        assert_eq!(
+            Blake2bTree::verify_proof_for_leaf(&root, &proof, "d"),
+            Err(VerifyError::LeafMismatch)
+        );
+        let other = Blake2bTree::merkle_proof(data.iter(), 3).unwrap();
+        assert_eq!(
+            Blake2bTree::verify_proof_for_leaf(&root, &other, "c"),
+            Err(VerifyError::LeafMismatch)
+        );
+
+        // Matching content still has to sit on a path to the root.
+        let mut forged = proof.clone();
+        forged.leaf_content = &"x";
+        assert_eq!(
+            Blake2bTree::verify_proof_for_leaf(&root, &forged, "x"),
+            Err(VerifyError::RootMismatch)
+        );
+        let other_root = Blake2bTree::merkle_root(data[..4].iter());
+        assert_eq!(
+            Blake2bTree::verify_proof_for_leaf(&other_root, &proof, "c"),
+            Err(VerifyError::RootMismatch)
+        );
+    }
+
+    #[test]
+    fn test_verify_proof_from_leaf_hash() {
+        let data = ["a", "b", "c", "d", "e"];
+        let root = Blake2bTree::merkle_root(data.iter());
+        for (i, leaf) in data.iter().enumerate() {
+            let proof = Blake2bTree::merkle_proof(data.iter(), i).unwrap();
+            let leaf_hash = Blake2bTree::hash_leaf(leaf);
+            assert_eq!(
+                Blake2bTree::verify_proof_from_leaf_hash(
+                    &root,
+                    &leaf_hash,
+                    i,
+                    data.len(),
+                    &proof.hashes
+                ),
+                Blake2bTree::verify_proof(&root, &proof)
+            );
+        }
+
+        let proof = Blake2bTree::merkle_proof(data.iter(), 1).unwrap();
+        let verify = |leaf_hash: &Hash, index| {
+            Blake2bTree::verify_proof_from_leaf_hash(&root, leaf_hash, index, 5, &proof.hashes)
+        };
+        assert_eq!(verify(&Blake2bTree::hash_leaf("b"), 1), Ok(()));
+        assert_eq!(
+            verify(&Blake2bTree::hash_leaf("x"), 1),
+            Err(VerifyError::RootMismatch)
+        );
+        // The content itself is not a leaf hash.
+        let raw = Hash::from_slice(&Blake2b512::digest(b"b")).unwrap();
+        assert_eq!(verify(&raw, 1), Err(VerifyError::RootMismatch));
+        assert_eq!(
+            verify(&Blake2bTree::hash_leaf("b"), 5),
+            Err(VerifyError::LeafIndexOutOfRange {
+                index: 5,
+                num_of_leaves: 5
+            })
+        );
+    }
+
+    #[test]
+    fn test_verify_against_any() {
+        let data: Vec<String> = (0..6).map(|i| format!("leaf-{i}")).collect();
+        let roots: Vec<String> = (1..=data.len())
+            .map(|n| Blake2bTree::merkle_root(data[..n].iter()))
+            .collect();
+        let proof = Blake2bTree::merkle_proof(data[..4].iter(), 1).unwrap();
+        assert_eq!(Blake2bTree::verify_against_any(&roots, &proof), Some(3));
+        assert_eq!(Blake2bTree::verify_against_any(&roots[..3], &proof), None);
+        assert_eq!(
+            Blake2bTree::verify_against_any(&[] as &[&str], &proof),
+            None
+        );
+
+        let mut forged = proof.clone();
+        forged.leaf_content = &data[2];
+        assert_eq!(Blake2bTree::verify_against_any(&roots, &forged), None);
+    }
+
+    #[test]
+    fn test_merkle_proof_moves_large_leaves() {
+        // Not `Clone`, so the proof can only hold the leaf it was given.
+        struct Blob(Vec<u8>);
+        impl AsRef<[u8]> for Blob {
+            fn as_ref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        let blobs = || (0..16u8).map(|i| Blob(vec![i; 1 << 20]));
+        let root = Blake2bTree::merkle_root(blobs());
+        let target = blobs().nth(9).unwrap();
+        let address = target.0.as_ptr();
+        let leaves = blobs().take(9).chain([target]).chain(blobs().skip(10));
+
+        let proof = Blake2bTree::merkle_proof(leaves, 9).unwrap();
+        assert_eq!(proof.leaf_content.0.as_ptr(), address);
+        assert!(Blake2bTree::verify_proof(&root, &proof).is_ok());
+
+        let with_root = Blake2bTree::merkle_proof_and_root(blobs(), 15).unwrap();
+        assert_eq!(with_root.root, root);
+        assert!(Blake2bTree::verify_proof(&root, &with_root.proof).is_ok());
+    }
+
+    #[test]
+    fn test_proof_display() {
+        let data = ["a", "b", "c", "abc", "e", "f", "g", "h"];
+        let proof = Blake2bTree::merkle_proof(data.iter(), 3).unwrap();
+        assert_eq!(
+            proof.to_string(),
+            r#"MerkleProof{leaf 3/8, depth 3, "abc"}"#
+        );
+
+        let long = "x".repeat(40);
+        let proof = Blake2bTree::merkle_proof([&long].into_iter(), 0).unwrap();
+        assert_eq!(
+            proof.to_string(),
+            format!("MerkleProof{{leaf 0/1, depth 0, \"{}\"...}}", &long[..32])
+        );
+        assert!(format!("{proof:?}").contains(&long));
+    }
+
+    #[test]
+    fn test_collect_and_try_from() {
+        let data: Vec<String> = ["a", "b", "c", "d", "e"].map(String::from).to_vec();
+        let root = Blake2bTree::merkle_root(data.iter());
+
+        let tree: MerkleTreeBuilder<String> = data.clone().into_iter().collect();
+        assert_eq!(tree.root_hex(), root);
+        assert_eq!(tree.proof(2).unwrap().leaf_content, "c");
+        let empty: MerkleTreeBuilder<String> = std::iter::empty().collect();
+        assert_eq!(empty.root_hex(), Blake2bTree::empty_root());
+
+        let tree = MerkleTreeBuilder::<String>::try_from(data.as_slice()).unwrap();
+        assert_eq!(tree.root_hex(), root);
+        assert_eq!(
+            MerkleTreeBuilder::<String>::try_from(&[] as &[String]).unwrap_err(),
+            ProofError::EmptyTree
+        );
+    }
+
+    #[test]
+    fn test_merkle_proof_by_value() {
+        let data = ["a", "b", "c", "b", "d"];
+        let root = Blake2bTree::merkle_root(data.iter());
+        let proof = Blake2bTree::merkle_proof_by_value(data.iter(), "c").unwrap();
+        assert_eq!(proof, Blake2bTree::merkle_proof(data.iter(), 2).unwrap());
+        assert!(Blake2bTree::verify_proof(&root, &proof).is_ok());
+
+        // A repeated value is proven at its first index.
+        let proof = Blake2bTree::merkle_proof_by_value(data.iter(), b"b").unwrap();
+        assert_eq!(proof.leaf_index, 1);
+
+        assert_eq!(
+            Blake2bTree::merkle_proof_by_value(data.iter(), "z").unwrap_err(),
+            ProofError::LeafNotFound
+        );
+        assert_eq!(
+            Blake2bTree::merkle_proof_by_value(std::iter::empty::<&str>(), "a").unwrap_err(),
+            ProofError::LeafNotFound
+        );
+    }
+
+    #[test]
+    fn test_index_of() {
+        let tree = Blake2bTree::from_leaves(["a", "b", "c", "b", "d", "b"]);
+        assert_eq!(tree.index_of("c"), Some(2));
+        assert_eq!(tree.indices_of("c"), [2]);
+        assert_eq!(tree.index_of("b"), Some(1));
+        assert_eq!(tree.indices_of("b"), [1, 3, 5]);
+        assert_eq!(tree.index_of("z"), None);
+        assert!(tree.indices_of("z").is_empty());
+
+        let index = tree.index_of(b"d").unwrap();
+        let proof = tree.proof(index).unwrap();
+        assert_eq!(proof.leaf_content, "d");
+        assert!(Blake2bTree::verify_proof(tree.root_hex(), &proof).is_ok());
+    }
+
+    #[test]
+    fn test_verify_proof_bool() {
+        let data = ["a", "b", "c"];
+        let root = Blake2bTree::merkle_root(data.iter().cloned().map(String::from));
+        let proof = Blake2bTree::merkle_proof(data.iter().cloned().map(String::from), 2).unwrap();
+        assert!(Blake2bTree::verify_proof_bool(&root, &proof));
+        assert!(!Blake2bTree::verify_proof_bool("00", &proof));
+    }
+
+    #[test]
+    fn test_from_leaves_handle() {
+        let data = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let root = Blake2bTree::merkle_root(data.iter());
+        let expected = Blake2bTree::merkle_proof(data.iter(), 2).unwrap().hashes;
+
+        let tree = Blake2bTree::from_leaves(data);
+        assert_eq!(tree.leaf_count(), 3);
+        assert_eq!(tree.root_hex(), root);
+        let proof = tree.proof(2).unwrap();
+        assert_eq!(proof.hashes, expected);
+        assert_eq!(proof.leaf_content, "c");
+        assert!(Blake2bTree::verify_proof(&root, &proof).is_ok());
+        assert_eq!(
+            tree.proof(3).unwrap_err(),
+            ProofError::IndexOutOfRange { index: 3, len: 3 }
+        );
+    }
+
+    #[test]
+    fn test_verify_proofs_reports_each_proof() {
+        let data = ["a", "b", "c", "d", "e"];
+        let root = Blake2bTree::merkle_root(data.iter());
+        let mut proofs: Vec<_> = (0..data.len())
+            .map(|i| Blake2bTree::merkle_proof(data.iter(), i).unwrap())
+            .collect();
+        proofs[1].leaf_content = &"x";
+        proofs[3].hashes.pop();
+
+        let results = Blake2bTree::verify_proofs(&root, &proofs);
+        assert_eq!(
+            results,
+            [
+                Ok(()),
+                Err(VerifyError::RootMismatch),
+                Ok(()),
+                Err(VerifyError::InconsistentProofLength {
+                    expected: 3,
+                    actual: 2
+                }),
+                Ok(()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_proof_and_root() {
+        let data: Vec<String> = (0..11).map(|i| format!("leaf-{i}")).collect();
+        for i in 0..data.len() {
+            let ProofWithRoot { root, proof } =
+                Blake2bTree::merkle_proof_and_root(data.iter(), i).unwrap();
+            assert_eq!(root, Blake2bTree::merkle_root(data.iter()));
+            assert!(Blake2bTree::verify_proof(&root, &proof).is_ok());
+        }
+        assert_eq!(
+            Blake2bTree::merkle_proof_and_root(data.iter(), 11).unwrap_err(),
+            ProofError::IndexOutOfRange { index: 11, len: 11 }
+        );
+    }
+
+    #[test]
+    fn test_proof_equality_and_clone() {
+        let data = ["a", "b", "c", "d", "e"];
+        let root = Blake2bTree::merkle_root(data.iter());
+        let proof = Blake2bTree::merkle_proof(data.iter(), 3).unwrap();
+        assert_eq!(proof, Blake2bTree::merkle_proof(data.iter(), 3).unwrap());
+
+        let mut tampered = proof.clone();
+        tampered.hashes.swap(0, 1);
+        assert_ne!(tampered, proof);
+        assert!(Blake2bTree::verify_proof(&root, &tampered).is_err());
+        assert!(Blake2bTree::verify_proof(&root, &proof).is_ok());
+
+        let proofs: std::collections::HashSet<_> = (0..data.len())
+            .chain(0..data.len())
+            .map(|i| Blake2bTree::merkle_proof(data.iter(), i).unwrap())
+            .collect();
+        assert_eq!(proofs.len(), data.len());
+    }
+
+    #[test]
+    fn test_verify_accepts_any_root_string() {
+        let data = ["a", "b", "c", "d", "e"];
+        let root = Blake2bTree::merkle_root(data.iter());
+        let proof = Blake2bTree::merkle_proof(data.iter(), 3).unwrap();
+
+        assert!(Blake2bTree::verify_proof(root.as_str(), &proof).is_ok());
+        assert!(Blake2bTree::verify_proof("b6e6178307582518d07fc7433472d7876338fb1b756c47319360b89d05533cd2b0a842416db0b0b5e7833fc0a40880c2d5754bd74e61c4d6abf9e9bc1aa0ae68", &proof).is_ok());
+        assert!(Blake2bTree::verify_proof(root.to_uppercase(), &proof).is_err());
+        assert!(Blake2bTree::verify_proof(root, &proof).is_ok());
+    }
+
+    #[test]
+    fn test_empty_leaves() {
+        let root = Blake2bTree::merkle_root(std::iter::empty::<String>());
+        assert_eq!(root, Blake2bTree::empty_root());
+        assert_eq!(root, hex::encode(Blake2b512::digest(b"")));
+        assert_ne!(
+            root,
+            Blake2bTree::merkle_root(std::iter::once(String::new()))
+        );
+    }
+
+    #[test]
+    fn test_empty_string_leaves() {
+        // An empty leaf still hashes as H(0x00), so a one-leaf tree over ""
+        // is neither the empty tree's root nor the bare digest of "".
+        let one = Blake2bTree::merkle_root([""].iter());
+        assert_eq!(one, "2fa3f686df876995167e7c2e5d74c4c7b6e48f8068fe0e44208344d480f7904c36963e44115fe3eb2a3ac8694c28bcb4f5a0f3276f2e79487d8219057a506e4b");
+        assert_eq!(one, hex::encode(Blake2b512::digest([0x00])));
+        assert_ne!(one, Blake2bTree::empty_root());
+
+        let two = Blake2bTree::merkle_root(["", ""].iter());
+        assert_eq!(two, "d2c87738d63ddd40f0affbfc2854e8bd1150c386836fde8235cab6bc23a911d4fb77079cdbd249090cdd255bd21cb249f936f2eebd65c2b6241432286a26db7c");
+        assert_ne!(two, one);
+
+        let mixed = ["", "a", ""];
+        let root = Blake2bTree::merkle_root(mixed.iter());
+        assert_eq!(root, "24d86b88b100c9202b3387114b545f6ca273217fe97a47c2039ed828321cb307ffe69740548f83dbdbbd8ced185360b028ebd0167c7b61d21a76288414b9490c");
+        for i in 0..mixed.len() {
+            let proof = Blake2bTree::merkle_proof(mixed.iter(), i).unwrap();
+            assert!(Blake2bTree::verify_proof(&root, &proof).is_ok());
+        }
+        let single = Blake2bTree::merkle_proof([""].iter(), 0).unwrap();
+        assert!(single.hashes.is_empty());
+        assert!(Blake2bTree::verify_proof(&one, &single).is_ok());
+        assert!(Blake2bTree::verify_proof(Blake2bTree::empty_root(), &single).is_err());
+    }
+
+    #[test]
+    fn test_path_bits_pin_index_and_count() {
+        for n in 1..20 {
+            let data: Vec<String> = (0..n).map(|i| format!("leaf-{i}")).collect();
+            let root = Blake2bTree::merkle_root(data.iter());
+            for i in 0..n {
+                let proof = Blake2bTree::merkle_proof(data.iter(), i).unwrap();
+                assert_eq!(proof.path_bits, proof_directions(i, n));
+                assert_eq!(Blake2bTree::verify_proof(&root, &proof), Ok(()));
+
+                // A wrong count only gets through where it gives the very
+                // same path, as leaf 0 of 3 and of 4 share theirs.
+                for wrong in [0, 1, i, n - 1, n + 1, 2 * n, usize::MAX] {
+                    let mut tampered = proof.clone();
+                    tampered.num_of_leaves = wrong;
+                    let same_path = i < wrong && proof_directions(i, wrong) == proof.path_bits;
+                    assert_eq!(
+                        Blake2bTree::verify_proof(&root, &tampered).is_ok(),
+                        same_path,
+                        "leaf {i} of {n} claimed in {wrong}"
+                    );
+                }
+                for other in (0..n).filter(|&other| other != i) {
+                    let mut moved = proof.clone();
+                    moved.leaf_index = other;
+                    assert!(
+                        Blake2bTree::verify_proof(&root, &moved).is_err(),
+                        "leaf {i} of {n} relabelled {other}"
+                    );
+                }
+                if let Some(first) = proof.path_bits.first() {
+                    let mut flipped = proof.clone();
+                    flipped.path_bits[0] = !first;
+                    assert_eq!(
+                        Blake2bTree::verify_proof(&root, &flipped),
+                        Err(VerifyError::PositionMismatch { level: 0 })
+                    );
+                }
+            }
+        }
+
+        // The fifth of five leaves is promoted alone twice, so its one bit
+        // would also fit indices 1, 2 and 3 if the count did not place it.
+        let data = ["a", "b", "c", "d", "e"];
+        let root = Blake2bTree::merkle_root(data.iter());
+        let proof = Blake2bTree::merkle_proof(data.iter(), 4).unwrap();
+        for other in 1..4 {
+            let mut moved = proof.clone();
+            moved.leaf_index = other;
+            assert!(Blake2bTree::verify_proof(&root, &moved).is_err());
+        }
+
+        // A leaf of eight is no leaf of three, bits or not.
+        let data: Vec<String> = (0..8).map(|i| format!("leaf-{i}")).collect();
+        let root = Blake2bTree::merkle_root(data.iter());
+        let mut proof = Blake2bTree::merkle_proof(data.iter(), 5).unwrap();
+        assert_eq!(
+            Blake2bTree::verify_proof_for_size(&root, 3, &proof),
+            Err(VerifyError::LeafCountMismatch {
+                expected: 3,
+                actual: 8
+            })
+        );
+        proof.num_of_leaves = 3;
+        assert_eq!(
+            Blake2bTree::verify_proof_for_size(&root, 3, &proof),
+            Err(VerifyError::LeafIndexOutOfRange {
+                index: 5,
+                num_of_leaves: 3
+            })
+        );
+
+        // Bits that do not match the claimed path, in value or in number.
+        let proof = Blake2bTree::merkle_proof(data.iter(), 5).unwrap();
+        let mut short = proof.clone();
+        short.path_bits.pop();
+        assert_eq!(
+            Blake2bTree::verify_proof(&root, &short),
+            Err(VerifyError::PositionMismatch { level: 2 })
+        );
+        let mut long = proof.clone();
+        long.path_bits.push(false);
+        assert_eq!(
+            Blake2bTree::verify_proof(&root, &long),
+            Err(VerifyError::PositionMismatch { level: 3 })
+        );
+
+        // Without bits, as in proofs from before they existed, the index and
+        // count alone place the siblings.
+        let mut legacy = proof.clone();
+        legacy.path_bits.clear();
+        assert_eq!(Blake2bTree::verify_proof(&root, &legacy), Ok(()));
+        legacy.leaf_index = 4;
+        assert_eq!(
+            Blake2bTree::verify_proof(&root, &legacy),
+            Err(VerifyError::RootMismatch)
+        );
+    }
+
+    #[test]
+    fn test_single_leaf_tree() {
+        let root = Blake2bTree::merkle_root(["only"].iter());
+        assert_eq!(root, Blake2bTree::hash_leaf("only").to_hex());
+
+        let proof = Blake2bTree::merkle_proof(["only"].iter(), 0).unwrap();
+        assert!(proof.hashes.is_empty());
+        assert_eq!(proof.num_of_leaves, 1);
+        assert_eq!(
+            proof,
+            MerkleTreeBuilder::<_, Blake2b512>::new(["only"].iter())
+                .proof(0)
+                .unwrap()
+        );
+        assert!(Blake2bTree::verify_proof(&root, &proof).is_ok());
+
+        let mut wrong_leaf = proof.clone();
+        wrong_leaf.leaf_content = &"other";
+        assert_eq!(
+            Blake2bTree::verify_proof(&root, &wrong_leaf),
+            Err(VerifyError::RootMismatch)
+        );
+
+        // An empty path only fits leaf 0 of a one-leaf tree.
+        let mut moved = proof.clone();
+        moved.leaf_index = 1;
+        assert_eq!(
+            Blake2bTree::verify_proof(&root, &moved),
+            Err(VerifyError::LeafIndexOutOfRange {
+                index: 1,
+                num_of_leaves: 1
+            })
+        );
+        moved.num_of_leaves = 2;
+        assert_eq!(
+            Blake2bTree::verify_proof(&root, &moved),
+            Err(VerifyError::InconsistentProofLength {
+                expected: 1,
+                actual: 0
+            })
+        );
+
+        assert_eq!(
+            Blake2bTree::merkle_proof(["only"].iter(), 1).unwrap_err(),
+            ProofError::IndexOutOfRange { index: 1, len: 1 }
+        );
+    }
+
+    #[test]
+    fn test_combine_roots() {
+        let data: Vec<String> = (0..16).map(|i| format!("leaf-{i}")).collect();
+        for n in [2, 4, 8, 16] {
+            let (left, right) = data[..n].split_at(n / 2);
+            assert_eq!(
+                Blake2bTree::combine_roots(
+                    Blake2bTree::merkle_root(left.iter()),
+                    Blake2bTree::merkle_root(right.iter())
+                ),
+                Blake2bTree::merkle_root(data[..n].iter()),
+                "{n} leaves"
+            );
+        }
+
+        // A perfect left tree may carry a shallower right one.
+        assert_eq!(
+            Blake2bTree::combine_roots(
+                Blake2bTree::merkle_root(data[..8].iter()),
+                Blake2bTree::merkle_root(data[8..13].iter())
+            ),
+            Blake2bTree::merkle_root(data[..13].iter())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a hex-encoded hash")]
+    fn test_combine_roots_rejects_non_hex() {
+        Blake2bTree::combine_roots("not hex", Blake2bTree::empty_root());
+    }
+
+    #[test]
+    fn test_proof_index_out_of_range() {
+        let data = ["a", "b", "c"];
+        assert_eq!(
+            Blake2bTree::merkle_proof(data.iter().cloned().map(String::from), 3).unwrap_err(),
+            ProofError::IndexOutOfRange { index: 3, len: 3 }
+        );
+        assert_eq!(
+            Blake2bTree::merkle_proof(data.iter().cloned().map(String::from), 1000).unwrap_err(),
+            ProofError::IndexOutOfRange {
+                index: 1000,
+                len: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_proof_empty_leaves() {
+        assert_eq!(
+            Blake2bTree::merkle_proof(std::iter::empty::<String>(), 0).unwrap_err(),
+            ProofError::EmptyTree
+        );
+    }
+
+    #[test]
+    fn test_byte_leaves() {
+        let data: Vec<Vec<u8>> = vec![vec![0xff, 0xfe, 0x00], vec![0x80], vec![], vec![0xc3, 0x28]];
+        let root = Blake2bTree::merkle_root(data.iter());
+        let proof = Blake2bTree::merkle_proof(data.iter(), 3).unwrap();
+        assert_eq!(proof.leaf_content, &vec![0xc3, 0x28]);
+        assert!(Blake2bTree::verify_proof(&root, &proof).is_ok());
+
+        let slices: Vec<&[u8]> = data.iter().map(Vec::as_slice).collect();
+        assert_eq!(Blake2bTree::merkle_root(slices.into_iter()), root);
+    }
+
+    #[test]
+    fn test_string_and_byte_leaves_agree() {
+        let data = ["a", "b", "c"];
+        let from_strings = Blake2bTree::merkle_root(data.iter().cloned().map(String::from));
+        let from_strs = Blake2bTree::merkle_root(data.iter());
+        let from_bytes = Blake2bTree::merkle_root(data.iter().map(|s| s.as_bytes().to_vec()));
+        assert_eq!(from_strings, from_strs);
+        assert_eq!(from_strings, from_bytes);
+    }
+
+    #[test]
+    fn test_proof_hashes_are_raw_bytes() {
+        let data = ["a", "b", "c", "d", "e"];
+        let proof = Blake2bTree::merkle_proof(data.iter(), 2).unwrap();
+        for hash in &proof.hashes {
+            assert_eq!(hash.len(), 64);
+            assert_eq!(hash.to_hex(), hex::encode(hash.as_bytes()));
+        }
+        let proof = MerkleTree::<Sha256>::merkle_proof(data.iter(), 2).unwrap();
+        assert!(proof.hashes.iter().all(|hash| hash.len() == 32));
+    }
+
+    #[test]
+    fn test_hash_from_slice() {
+        let hash = Hash::from_slice(&[0xab, 0xcd]).unwrap();
+        assert_eq!(hash.as_bytes(), &[0xab, 0xcd]);
+        assert_eq!(hash.to_hex(), "abcd");
+        assert_ne!(hash, Hash::from_slice(&[0xab, 0xcd, 0x00]).unwrap());
+        assert!(Hash::from_slice(&[0; MAX_HASH_LEN + 1]).is_none());
+    }
+
+    #[test]
+    fn test_builder_serves_many_proofs() {
+        let data: Vec<String> = (0..1000).map(|i| format!("leaf-{i}")).collect();
+        let tree = MerkleTreeBuilder::<_, Blake2b512>::new(data.iter());
+        let root = tree.root_hex();
+        assert_eq!(root, Blake2bTree::merkle_root(data.iter()));
+        assert_eq!(tree.leaf_count(), 1000);
+
+        // One build, a thousand proofs, no rehashing.
+        for (i, leaf) in data.iter().enumerate() {
+            let proof = tree.proof(i).unwrap();
+            assert_eq!(proof.leaf_content, leaf);
+            assert!(Blake2bTree::verify_proof(&root, &proof).is_ok());
+        }
+        assert_eq!(
+            tree.proof(1000).unwrap_err(),
+            ProofError::IndexOutOfRange {
+                index: 1000,
+                len: 1000
+            }
+        );
+    }
+
+    #[test]
+    fn test_tree_proof_reads_no_leaf_bytes() {
+        use core::cell::Cell;
+
+        /// A leaf that counts how often its bytes are read.
+        #[derive(Clone)]
+        struct Counted<'a> {
+            bytes: String,
+            reads: &'a Cell<usize>,
+        }
+
+        impl AsRef<[u8]> for Counted<'_> {
+            fn as_ref(&self) -> &[u8] {
+                self.reads.set(self.reads.get() + 1);
+                self.bytes.as_bytes()
+            }
+        }
+
+        let reads = Cell::new(0);
+        let data: Vec<String> = (0..13).map(|i| format!("leaf-{i}")).collect();
+        let tree = MerkleTreeBuilder::<_, Blake2b512>::new(data.iter().map(|bytes| Counted {
+            bytes: bytes.clone(),
+            reads: &reads,
+        }));
+        assert!(reads.get() >= data.len());
+
+        // Proofs come from the cached levels: no leaf is hashed again.
+        reads.set(0);
+        let proofs: Vec<_> = (0..data.len()).map(|i| tree.proof(i).unwrap()).collect();
+        assert_eq!(reads.get(), 0);
+
+        let root = tree.root_hex();
+        for (i, proof) in proofs.iter().enumerate() {
+            assert_eq!(proof.leaf_content.bytes, data[i]);
+            assert_eq!(Blake2bTree::verify_proof(&root, proof), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_builder_matches_static_api() {
+        let data = ["a", "b", "c", "d", "e", "f", "g"];
+        let tree = MerkleTreeBuilder::<_, Sha256>::new(data.iter());
+        assert_eq!(
+            tree.root_hex(),
+            MerkleTree::<Sha256>::merkle_root(data.iter())
+        );
+        for i in 0..data.len() {
+            let expected = MerkleTree::<Sha256>::merkle_proof(data.iter(), i).unwrap();
+            assert_eq!(tree.proof(i).unwrap(), expected);
+        }
+
+        let empty = MerkleTreeBuilder::<&str, Sha256>::new(std::iter::empty());
+        assert_eq!(empty.root_hex(), MerkleTree::<Sha256>::empty_root());
+        assert_eq!(empty.proof(0).unwrap_err(), ProofError::EmptyTree);
+    }
+
+    #[test]
+    fn test_builder_levels() {
+        for n in [1, 2, 5, 8, 13] {
+            let data: Vec<String> = (0..n).map(|i| format!("leaf-{i}")).collect();
+            let tree = MerkleTreeBuilder::<_, Blake2b512>::new(data.iter());
+            let levels = tree.levels();
+
+            assert_eq!(levels.len(), tree_depth(n) + 1);
+            assert_eq!(levels[0].len(), n);
+            assert_eq!(levels[0][0], Blake2bTree::hash_leaf(&data[0]));
+            assert_eq!(levels[levels.len() - 1].len(), 1);
+            assert_eq!(levels[levels.len() - 1][0].to_hex(), tree.root_hex());
+        }
+
+        let empty = MerkleTreeBuilder::<&str, Blake2b512>::new(std::iter::empty());
+        assert_eq!(empty.levels(), [Vec::new()]);
+    }
+
+    #[test]
+    fn test_build_stats() {
+        // A perfect tree over 2^k leaves hashes 2^k leaves and 2^k - 1 nodes.
+        for k in 0..6 {
+            let n = 1usize << k;
+            let (tree, stats) = MerkleTreeBuilder::<_, Blake2b512>::build_with_stats(
+                (0..n).map(|i| i.to_string()),
+                TreeOptions::default(),
+            );
+            assert_eq!(
+                stats,
+                BuildStats {
+                    hash_calls: 2 * n - 1,
+                    levels: k + 1
+                }
+            );
+            assert_eq!(stats.levels, tree.levels().len());
+        }
+
+        // Five leaves: 5 leaf hashes, then levels of 5, 3 and 2 nodes.
+        let leaves = ["a", "b", "c", "d", "e"];
+        let stats = |options| {
+            MerkleTreeBuilder::<_, Blake2b512>::build_with_stats(leaves.iter(), options).1
+        };
+        assert_eq!(stats(TreeOptions::default()).hash_calls, 5 + 2 + 1 + 1);
+        let duplicate = TreeOptions {
+            odd_nodes: OddNodeStrategy::Duplicate,
+            ..TreeOptions::default()
+        };
+        assert_eq!(stats(duplicate).hash_calls, 5 + 3 + 2 + 1);
+        let hashes: Vec<_> = leaves.iter().map(Blake2bTree::hash_leaf).collect();
+        let (_, prehashed) = MerkleTreeBuilder::<_, Blake2b512>::build_with_stats(
+            hashes.iter().map(Hash::as_bytes),
+            PREHASHED,
+        );
+        assert_eq!(prehashed.hash_calls, 2 + 1 + 1);
+
+        let (_, empty) = MerkleTreeBuilder::<&str, Blake2b512>::build_with_stats(
+            std::iter::empty(),
+            TreeOptions::default(),
+        );
+        assert_eq!(
+            empty,
+            BuildStats {
+                hash_calls: 0,
+                levels: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_all_proofs() {
+        let data: Vec<String> = (0..37).map(|i| format!("leaf-{i}")).collect();
+        let tree = MerkleTreeBuilder::<_, Blake2b512>::new(data.iter());
+        let root = tree.root_hex();
+
+        let proofs: Vec<_> = tree.all_proofs().collect();
+        assert_eq!(proofs.len(), data.len());
+        for (i, proof) in proofs.iter().enumerate() {
+            assert_eq!(proof.leaf_index, i);
+            assert_eq!(*proof, tree.proof(i).unwrap());
+            assert!(Blake2bTree::verify_proof(&root, proof).is_ok());
+        }
+
+        let empty = MerkleTreeBuilder::<&str, Blake2b512>::new(std::iter::empty());
+        assert_eq!(empty.all_proofs().count(), 0);
+    }
+
+    #[test]
+    fn test_root_bytes_and_hex() {
+        for n in [0, 1, 5] {
+            let data: Vec<String> = (0..n).map(|i| format!("leaf-{i}")).collect();
+            let tree = MerkleTreeBuilder::<_, Blake2b512>::new(data.iter());
+            assert_eq!(tree.root_bytes().len(), 64);
+            assert_eq!(hex::encode(tree.root_bytes()), tree.root_hex());
+            assert_eq!(tree.root_hex(), Blake2bTree::merkle_root(data.iter()));
+        }
+    }
+
+    #[test]
+    fn test_keyed_trees_are_disjoint() {
+        let data = ["a", "b", "c", "d", "e"];
+        let tree_a = MerkleTreeBuilder::<_, Blake2b512>::with_key(data.iter(), b"tree A");
+        let tree_b = MerkleTreeBuilder::<_, Blake2b512>::with_key(data.iter(), b"tree B");
+        let plain = Blake2bTree::merkle_root(data.iter());
+        assert_ne!(tree_a.root_hex(), tree_b.root_hex());
+        assert_ne!(tree_a.root_hex(), plain);
+
+        let proof = tree_a.proof(2).unwrap();
+        assert!(
+            Blake2bTree::verify_proof_with(tree_a.root_hex(), &proof, tree_a.options()).is_ok()
+        );
+        assert_eq!(
+            Blake2bTree::verify_proof_with(tree_b.root_hex(), &proof, tree_b.options()),
+            Err(VerifyError::RootMismatch)
+        );
+        assert_eq!(
+            Blake2bTree::verify_proof_with(tree_a.root_hex(), &proof, tree_b.options()),
+            Err(VerifyError::RootMismatch)
+        );
+        assert!(Blake2bTree::verify_proof(tree_a.root_hex(), &proof).is_err());
+
+        let multiproof = tree_a.multiproof(&[0, 4]).unwrap();
+        assert!(Blake2bTree::verify_multiproof_with(
+            tree_a.root_hex(),
+            &multiproof,
+            tree_a.options()
+        )
+        .is_ok());
+
+        // An empty key is still a key: it differs from no key at all.
+        let empty_key = MerkleTreeBuilder::<_, Blake2b512>::with_key(data.iter(), b"");
+        assert_ne!(empty_key.root_hex(), plain);
+    }
+
+    #[test]
+    fn test_roots_key_maps_and_sets() {
+        use std::collections::{BTreeSet, HashMap};
+
+        let trees: Vec<_> = (1..=4)
+            .map(|n| Blake2bTree::from_leaves((0..n).map(|i| format!("leaf-{i}"))))
+            .collect();
+        let roots: Vec<Root> = trees.iter().map(MerkleTreeBuilder::root).collect();
+        assert_eq!(roots[2], trees[2].root());
+        assert_ne!(roots[2], roots[3]);
+        assert_eq!(roots[2].to_string(), trees[2].root_hex());
+        assert_eq!(roots[2].as_bytes(), trees[2].root_bytes());
+
+        let sizes: HashMap<Root, usize> =
+            roots.iter().zip(1..).map(|(root, n)| (*root, n)).collect();
+        assert_eq!(sizes[&trees[2].root()], 3);
+
+        let sorted: BTreeSet<Root> = roots.iter().copied().collect();
+        let mut by_bytes = roots.clone();
+        by_bytes.sort_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+        assert!(sorted.iter().eq(by_bytes.iter()));
+    }
+
+    #[test]
+    fn test_append_matches_fresh_build() {
+        let data: Vec<String> = (0..33).map(|i| format!("leaf-{i}")).collect();
+        let mut tree = MerkleTreeBuilder::<&String, Blake2b512>::new(std::iter::empty());
+        assert_eq!(tree.root_hex(), Blake2bTree::empty_root());
+
+        for (n, leaf) in data.iter().enumerate() {
+            let root = tree.append(leaf);
+            assert_eq!(
+                root.to_hex(),
+                Blake2bTree::merkle_root(data[..=n].iter()),
+                "after {} leaves",
+                n + 1
+            );
+            assert_eq!(tree.leaf_count(), n + 1);
+        }
+
+        // Proofs still come out of the incrementally maintained levels.
+        let root = tree.root_hex();
+        for i in 0..data.len() {
+            assert!(Blake2bTree::verify_proof(&root, &tree.proof(i).unwrap()).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_append_odd_to_even() {
+        let mut tree = MerkleTreeBuilder::<_, Blake2b512>::new(["a", "b", "c"].into_iter());
+        assert_eq!(
+            tree.append("d").to_hex(),
+            Blake2bTree::merkle_root(["a", "b", "c", "d"].iter())
+        );
+        assert_eq!(
+            tree.append("e").to_hex(),
+            Blake2bTree::merkle_root(["a", "b", "c", "d", "e"].iter())
+        );
+    }
+
+    #[test]
+    fn test_update_leaf_matches_fresh_build() {
+        let mut data: Vec<String> = (0..11).map(|i| format!("leaf-{i}")).collect();
+        let mut tree = MerkleTreeBuilder::<String, Blake2b512>::new(data.clone().into_iter());
+
+        for index in [0, 5, 9, 10] {
+            let new_value = format!("updated-{index}");
+            data[index] = new_value.clone();
+            let root = tree.update_leaf(index, new_value).unwrap().to_hex();
+            assert_eq!(root, Blake2bTree::merkle_root(data.iter()));
+            assert!(Blake2bTree::verify_proof(&root, &tree.proof(index).unwrap()).is_ok());
+        }
+
+        assert_eq!(
+            tree.update_leaf(11, String::new()).unwrap_err(),
+            ProofError::IndexOutOfRange { index: 11, len: 11 }
+        );
+    }
+
+    #[test]
+    #[ignore = "timing only; run with `cargo test --release -- --ignored --nocapture`"]
+    fn bench_even_level_chunks() {
+        // The dropped fast path, against the one `hash_level` keeps. Both
+        // came out at about 90ms for 2^18 nodes in release builds.
+        let level: Vec<Hash> = (0..1u32 << 18)
+            .map(|i| Blake2bTree::hash_leaf(i.to_le_bytes()))
+            .collect();
+        let time = |hash: &dyn Fn() -> Vec<Hash>| {
+            let start = std::time::Instant::now();
+            let next = hash();
+            (next, start.elapsed())
+        };
+        let (exact, exact_time) = time(&|| {
+            level
+                .chunks_exact(2)
+                .map(|pair| Blake2bTree::hash_pair(&PLAIN, &pair[0], &pair[1]))
+                .collect()
+        });
+        let (general, general_time) = time(&|| Blake2bTree::hash_level(&PLAIN, &level));
+        assert_eq!(exact, general);
+        println!(
+            "{} nodes: chunks_exact {exact_time:?}, chunks(2) {general_time:?}",
+            level.len()
+        );
+    }
+
+    #[test]
+    fn test_verify_proof_any_strategy() {
+        use OddNodeStrategy::{Duplicate, PadZero, Promote};
 
-impl MerkleTree {
-    pub fn merkle_root<I>(leaves: I) -> String
-    where
-        I: Iterator<Item = String>,
-    {
-        let mut hashed_leaves: Vec<String> = leaves
-            .map(|leaf| {
-                let mut hasher = Blake2b512::new();
-                hasher.update(leaf);
-                let hash = hasher.finalize();
-                hex::encode(hash)
+        let data = ["a", "b", "c", "d", "e"];
+        let all = [Promote, Duplicate, PadZero];
+        for strategy in all {
+            let options = TreeOptions {
+                odd_nodes: strategy,
+                ..TreeOptions::default()
+            };
+            let tree =
+                MerkleTreeBuilder::<&str, Blake2b512>::with_options(data.into_iter(), options);
+            let root = tree.root_hex();
+
+            // The last leaf is lone on every level below the root.
+            let lone = tree.proof(4).unwrap();
+            assert_eq!(
+                Blake2bTree::verify_proof_any_strategy(&root, &lone, &all),
+                Some(strategy)
+            );
+            let others: Vec<_> = all.into_iter().filter(|&s| s != strategy).collect();
+            assert_eq!(
+                Blake2bTree::verify_proof_any_strategy(&root, &lone, &others),
+                None
+            );
+
+            // Leaf 0 is never lone, so every strategy accepts it.
+            let paired = tree.proof(0).unwrap();
+            assert_eq!(
+                Blake2bTree::verify_proof_any_strategy(&root, &paired, &[PadZero, Promote]),
+                Some(PadZero)
+            );
+        }
+
+        let proof = Blake2bTree::merkle_proof(data.iter(), 4).unwrap();
+        let other_root = Blake2bTree::merkle_root(["x"].iter());
+        assert_eq!(
+            Blake2bTree::verify_proof_any_strategy(other_root, &proof, &all),
+            None
+        );
+    }
+
+    #[test]
+    fn test_node_hash() {
+        let data = ["a", "b", "c", "d", "e"];
+        let tree = MerkleTreeBuilder::<&str, Blake2b512>::new(data.into_iter());
+        let (a, b, e) = (
+            Blake2bTree::hash_leaf("a"),
+            Blake2bTree::hash_leaf("b"),
+            Blake2bTree::hash_leaf("e"),
+        );
+
+        assert_eq!(tree.node_hash(0, 4), Some(e.to_hex()));
+        assert_eq!(
+            tree.node_hash(1, 0),
+            Some(Blake2bTree::hash_node(&a, &b).to_hex())
+        );
+        // The lone leaf is promoted unchanged.
+        assert_eq!(tree.node_hash(1, 2), Some(e.to_hex()));
+        assert_eq!(tree.node_hash(3, 0), Some(tree.root_hex()));
+
+        assert_eq!(tree.node_hash(0, 5), None);
+        assert_eq!(tree.node_hash(1, 3), None);
+        assert_eq!(tree.node_hash(3, 1), None);
+        assert_eq!(tree.node_hash(4, 0), None);
+    }
+
+    #[test]
+    fn test_update_proof_after_leaf_change() {
+        let data: Vec<String> = (0..11).map(|i| format!("leaf-{i}")).collect();
+        for changed_index in [0, 5, 9, 10] {
+            let mut tree = MerkleTreeBuilder::<String, Blake2b512>::new(data.clone().into_iter());
+            let mut proofs: Vec<_> = tree.all_proofs().collect();
+            let changed = tree.proof(changed_index).unwrap();
+
+            let new_leaf = format!("updated-{changed_index}");
+            let new_leaf_hash = Blake2bTree::hash_leaf(&new_leaf);
+            let root = tree.update_leaf(changed_index, new_leaf).unwrap().to_hex();
+
+            for proof in &mut proofs {
+                Blake2bTree::update_proof(proof, &changed, &new_leaf_hash).unwrap();
+                if proof.leaf_index != changed_index {
+                    assert_eq!(*proof, tree.proof(proof.leaf_index).unwrap());
+                    assert!(Blake2bTree::verify_proof(&root, proof).is_ok());
+                }
+            }
+        }
+
+        let mut proof = Blake2bTree::merkle_proof(data.iter(), 0).unwrap();
+        let other = Blake2bTree::merkle_proof(data[..8].iter(), 1).unwrap();
+        assert_eq!(
+            Blake2bTree::update_proof(&mut proof, &other, &Blake2bTree::hash_leaf("x")),
+            Err(VerifyError::LeafCountMismatch {
+                expected: 11,
+                actual: 8
+            })
+        );
+    }
+
+    #[test]
+    fn test_recompute_root() {
+        let data = ["a", "b", "c", "d", "e"];
+        let tree = MerkleTreeBuilder::<&str, Blake2b512>::new(data.into_iter());
+        for proof in tree.all_proofs() {
+            assert_eq!(Blake2bTree::recompute_root(&proof), Ok(tree.root_hex()));
+        }
+
+        let mut tampered = tree.proof(2).unwrap();
+        tampered.leaf_content = "x";
+        let recomputed = Blake2bTree::recompute_root(&tampered).unwrap();
+        assert_ne!(recomputed, tree.root_hex());
+        assert!(Blake2bTree::verify_proof(&recomputed, &tampered).is_ok());
+
+        tampered.hashes.pop();
+        assert_eq!(
+            Blake2bTree::recompute_root(&tampered),
+            Err(VerifyError::InconsistentProofLength {
+                expected: 3,
+                actual: 2
+            })
+        );
+
+        let sorted = TreeOptions {
+            sorted_pairs: true,
+            ..TreeOptions::default()
+        };
+        let tree =
+            MerkleTreeBuilder::<&str, Blake2b512>::with_options(data.into_iter(), sorted.clone());
+        assert_eq!(
+            Blake2bTree::recompute_root_with(&tree.proof(4).unwrap(), &sorted),
+            Ok(tree.root_hex())
+        );
+    }
+
+    #[test]
+    fn test_validate_structure() {
+        let data = ["a", "b", "c", "d", "e"];
+        let tree = MerkleTreeBuilder::<&str, Blake2b512>::new(data.into_iter());
+        for proof in tree.all_proofs() {
+            assert_eq!(proof.validate_structure(), Ok(()));
+            assert_eq!(
+                proof.with_root(tree.root_hex()).validate_structure(),
+                Ok(())
+            );
+        }
+
+        let proof = tree.proof(1).unwrap();
+        let mut short = proof.clone();
+        short.hashes.pop();
+        assert_eq!(
+            short.validate_structure(),
+            Err(StructureError::WrongHashCount {
+                expected: 3,
+                actual: 2
+            })
+        );
+
+        let mut out_of_range = proof.clone();
+        out_of_range.leaf_index = 5;
+        assert_eq!(
+            out_of_range.validate_structure(),
+            Err(StructureError::LeafIndexOutOfRange {
+                index: 5,
+                num_of_leaves: 5
+            })
+        );
+
+        // Relabelled as another leaf with a path of the same length, which
+        // the recorded bits give away.
+        let mut moved = proof.clone();
+        moved.leaf_index = 2;
+        assert_eq!(
+            moved.validate_structure(),
+            Err(StructureError::PathBitsMismatch { level: 0 })
+        );
+        assert_eq!(
+            Blake2bTree::verify_proof(tree.root_hex(), &moved),
+            Err(VerifyError::PositionMismatch { level: 0 })
+        );
+        let mut moved = tree.proof(4).unwrap();
+        moved.leaf_index = 0;
+        moved.num_of_leaves = 2;
+        assert_eq!(
+            moved.validate_structure(),
+            Err(StructureError::PathBitsMismatch { level: 0 })
+        );
+        moved.path_bits.clear();
+        assert_eq!(moved.validate_structure(), Ok(()));
+
+        let mut mixed = proof.clone();
+        mixed.hashes[2] = Blake2sTree::hash_leaf("x");
+        assert_eq!(
+            mixed.validate_structure(),
+            Err(StructureError::HashLengthMismatch {
+                index: 2,
+                expected: 64,
+                actual: 32
+            })
+        );
+
+        let mut empty = proof.clone();
+        empty.hashes[0] = Hash::from_slice(&[]).unwrap();
+        assert_eq!(
+            empty.validate_structure(),
+            Err(StructureError::EmptyHash { index: 0 })
+        );
+
+        let upper = tree.root_hex().to_uppercase();
+        for root in ["not hex", upper.as_str(), &tree.root_hex()[2..]] {
+            assert_eq!(
+                proof.clone().with_root(root).validate_structure(),
+                Err(StructureError::InvalidRoot),
+                "{root}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_truncate_undoes_append() {
+        let data: Vec<String> = (0..21).map(|i| format!("leaf-{i}")).collect();
+        for options in [
+            TreeOptions::default(),
+            TreeOptions {
+                odd_nodes: OddNodeStrategy::Duplicate,
+                ..TreeOptions::default()
+            },
+            TreeOptions {
+                pad_to_power_of_two: true,
+                ..TreeOptions::default()
+            },
+        ] {
+            let build = |n: usize| {
+                MerkleTreeBuilder::<String, Blake2b512>::with_options(
+                    data[..n].iter().cloned(),
+                    options.clone(),
+                )
+            };
+            for start in [0, 1, 5, 8] {
+                let mut tree = build(start);
+                let before = tree.root();
+                for leaf in &data[start..] {
+                    tree.append(leaf.clone());
+                }
+                assert_eq!(tree.truncate(start), before, "back to {start} leaves");
+                assert_eq!(tree.levels(), build(start).levels());
+            }
+
+            let mut tree = build(data.len());
+            for new_len in [21, 30, 17, 16, 9, 2, 1, 0] {
+                let root = tree.truncate(new_len);
+                let fresh = build(new_len.min(data.len()));
+                assert_eq!(root, fresh.root(), "truncated to {new_len}");
+                assert_eq!(tree.levels(), fresh.levels());
+                assert_eq!(tree.leaf_count(), fresh.leaf_count());
+            }
+        }
+    }
+
+    #[test]
+    fn test_shared_tree_serves_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<MerkleTreeBuilder<String>>();
+        assert_send_sync::<MerkleTreeBuilder<Vec<u8>, Sha256>>();
+
+        let leaves: Vec<String> = (0..100).map(|i| format!("leaf-{i}")).collect();
+        let tree = std::sync::Arc::new(MerkleTreeBuilder::<String>::new(leaves.into_iter()));
+        let root = tree.root_hex();
+
+        let workers: Vec<_> = (0..4)
+            .map(|worker| {
+                let tree = std::sync::Arc::clone(&tree);
+                let root = root.clone();
+                std::thread::spawn(move || {
+                    for index in (worker..tree.leaf_count()).step_by(4) {
+                        let proof = tree.proof(index).unwrap();
+                        assert!(Blake2bTree::verify_proof(&root, &proof).is_ok());
+                    }
+                })
             })
             .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_bundled_root_verifies() {
+        let data = ["a", "b", "c", "d", "e"];
+        let tree = MerkleTreeBuilder::<&str, Blake2b512>::new(data.into_iter());
+
+        let bundled = tree.bundled_proof(3).unwrap();
+        assert_eq!(bundled.root, Some(tree.root_hex()));
+        assert!(bundled.verify().is_ok());
+        assert!(Blake2bTree::verify_proof(tree.root_hex(), &bundled).is_ok());
 
-        while hashed_leaves.len() > 1 {
-            let mut next_level = Vec::new();
+        let external = tree.proof(3).unwrap();
+        assert_eq!(external.verify(), Err(VerifyError::MissingRoot));
+        assert!(Blake2bTree::verify_proof(tree.root_hex(), &external).is_ok());
+        assert!(external.with_root(tree.root_hex()).verify().is_ok());
+
+        let other_root = Blake2bTree::merkle_root(["x", "y"].iter());
+        let wrong = tree.proof(3).unwrap().with_root(other_root);
+        assert_eq!(wrong.verify(), Err(VerifyError::RootMismatch));
+    }
 
-            for chunk in hashed_leaves.chunks(2) {
-                let concatenated = match chunk {
-                    [a, b] => a.clone() + b,
-                    [a] => a.clone() + a,
-                    _ => unreachable!(),
-                };
+    #[test]
+    fn test_diff_finds_changed_leaves() {
+        let data: Vec<String> = (0..13).map(|i| format!("leaf-{i}")).collect();
+        let old = MerkleTreeBuilder::<String, Blake2b512>::new(data.clone().into_iter());
+        assert!(old.diff(&old.clone()).is_empty());
 
-                let mut hasher = Blake2b512::new();
-                hasher.update(concatenated);
-                next_level.push(hex::encode(hasher.finalize()));
+        for changed in [vec![0], vec![12], vec![3, 4, 11], (0..13).collect()] {
+            let mut new = old.clone();
+            for &index in &changed {
+                new.update_leaf(index, format!("updated-{index}")).unwrap();
             }
+            assert_eq!(old.diff(&new), changed);
+            assert_eq!(new.diff(&old), changed);
+        }
+
+        let empty = MerkleTreeBuilder::<String, Blake2b512>::new(core::iter::empty());
+        assert!(empty.diff(&empty).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "different leaf counts")]
+    fn test_diff_rejects_different_sizes() {
+        let small = MerkleTreeBuilder::<&str, Blake2b512>::new(["a", "b"].into_iter());
+        let large = MerkleTreeBuilder::<&str, Blake2b512>::new(["a", "b", "c"].into_iter());
+        small.diff(&large);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_proof_json_round_trip() {
+        let data = ["a", "b", "c", "d", "e"];
+        let root = Blake2bTree::merkle_root(data.iter());
+        let proof = Blake2bTree::merkle_proof(data.iter().cloned().map(String::from), 4).unwrap();
+
+        let json = serde_json::to_string(&proof).unwrap();
+        assert!(json.contains(&proof.hashes[0].to_hex()));
+
+        let decoded: MerkleProof<String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.hashes, proof.hashes);
+        assert_eq!(decoded.leaf_content, "e");
+        assert!(Blake2bTree::verify_proof(&root, &decoded).is_ok());
+        assert!(!json.contains("root"));
+
+        let bundled = proof.with_root(&root);
+        let decoded: MerkleProof<String> =
+            serde_json::from_str(&serde_json::to_string(&bundled).unwrap()).unwrap();
+        assert_eq!(decoded.root, Some(root));
+        assert!(decoded.verify().is_ok());
+    }
 
-            hashed_leaves = next_level;
+    #[test]
+    fn test_flat_verify_matches_proof() {
+        let data = ["a", "b", "c", "d", "e", "f", "g"];
+        let root = Blake2bTree::merkle_root(data.iter());
+        for i in 0..data.len() {
+            let proof = Blake2bTree::merkle_proof(data.iter(), i).unwrap();
+            let flat = |leaf: &str, index, count, hashes: &[Hash], root: &str| {
+                Blake2bTree::verify(leaf, index, count, hashes, root)
+            };
+            let h = &proof.hashes;
+            assert!(flat(data[i], i, 7, h, &root));
+            assert_eq!(
+                flat(data[i], i, 7, h, &root),
+                Blake2bTree::verify_proof_bool(&root, &proof)
+            );
+            assert!(!flat("x", i, 7, h, &root));
+            assert!(!flat(data[i], 7, 7, h, &root));
+            assert!(!flat(data[i], i, 7, &h[1..], &root));
+            assert!(!flat(data[i], i, 7, h, &Blake2bTree::empty_root()));
+            if i ^ 1 < data.len() {
+                assert!(!flat(data[i], i ^ 1, 7, h, &root));
+            }
         }
 
-        hashed_leaves.pop().unwrap()
+        // Both reject the same tampering of a leaf of eight, whether in the
+        // index or in the count.
+        let data: Vec<String> = (0..8).map(|i| format!("leaf-{i}")).collect();
+        let root = Blake2bTree::merkle_root(data.iter());
+        let proof = Blake2bTree::merkle_proof(data.iter(), 5).unwrap();
+        for (index, count) in [(5, 8), (5, 7), (5, 6), (5, 3), (4, 8), (1, 8), (5, 0)] {
+            let mut tampered = proof.clone();
+            tampered.leaf_index = index;
+            tampered.num_of_leaves = count;
+            let expected = Blake2bTree::verify_proof_bool(&root, &tampered);
+            assert_eq!(
+                Blake2bTree::verify(&data[5], index, count, &proof.hashes, &root),
+                expected,
+                "leaf 5 of 8 claimed as {index} of {count}"
+            );
+            tampered.path_bits.clear();
+            assert_eq!(Blake2bTree::verify_proof_bool(&root, &tampered), expected);
+            assert_eq!(
+                expected,
+                (index, count) == (5, 8) || (index, count) == (5, 7)
+            );
+        }
     }
 
-    pub fn merkle_proof<I>(leaves: I, leaf_index: usize) -> MerkleProof<String>
-    where
-        I: Iterator<Item = String> + Clone,
-    {
-        let leaves: Vec<String> = leaves.collect();
-        let mut proof = MerkleProof {
-            hashes: Vec::new(),
-            num_of_leaves: leaves.len(),
-            leaf_index,
-            leaf_content: leaves[leaf_index].clone(),
+    #[test]
+    fn test_duplicate_policies() {
+        let data = ["a", "b", "a", "c", "b"];
+        let build = |policy| {
+            MerkleTreeBuilder::<_, Blake2b512>::with_duplicate_policy(
+                data.iter(),
+                TreeOptions::default(),
+                policy,
+            )
         };
 
-        let mut hashed_leaves: Vec<String> = leaves
-            .into_iter()
-            .map(|leaf| {
-                let mut hasher = Blake2b512::new();
-                hasher.update(leaf);
-                hex::encode(hasher.finalize())
-            })
-            .collect();
+        let allowed = build(DuplicatePolicy::Allow).unwrap();
+        assert_eq!(allowed.root_hex(), Blake2bTree::merkle_root(data.iter()));
+        assert_eq!(allowed.leaf_count(), 5);
 
-        let mut index = leaf_index;
+        assert_eq!(
+            build(DuplicatePolicy::Reject).unwrap_err(),
+            ProofError::DuplicateLeaf {
+                first: 0,
+                duplicate: 2
+            }
+        );
+        assert!(MerkleTreeBuilder::<_, Blake2b512>::with_duplicate_policy(
+            ["a", "b", "c"].iter(),
+            TreeOptions::default(),
+            DuplicatePolicy::Reject,
+        )
+        .is_ok());
+
+        let deduped = build(DuplicatePolicy::Dedup).unwrap();
+        assert_eq!(deduped.leaf_count(), 3);
+        assert_eq!(
+            deduped.root_hex(),
+            Blake2bTree::merkle_root(["a", "b", "c"].iter())
+        );
+        assert_eq!(deduped.index_of("c"), Some(2));
+        let proof = deduped.proof(2).unwrap();
+        assert_eq!(
+            Blake2bTree::verify_proof(deduped.root_hex(), &proof),
+            Ok(())
+        );
+    }
 
-        while hashed_leaves.len() > 1 {
-            let mut next_level = Vec::new();
+    #[test]
+    fn test_leaf_hashes() {
+        let data = ["a", "b", "c", "d", "e"];
+        let padded = TreeOptions {
+            pad_to_power_of_two: true,
+            ..TreeOptions::default()
+        };
+        for options in [TreeOptions::default(), padded] {
+            let tree = MerkleTreeBuilder::<_, Blake2b512>::with_options(data.iter(), options);
+            let hashes = tree.leaf_hashes();
+            assert_eq!(hashes.len(), tree.leaf_count());
+            for (leaf, hash) in data.iter().zip(hashes) {
+                assert_eq!(*hash, Blake2bTree::hash_leaf(leaf));
+            }
+        }
 
-            for (i, chunk) in hashed_leaves.chunks(2).enumerate() {
-                if i == index / 2 {
-                    proof.hashes.push(match chunk {
-                        [_, b] if index % 2 == 0 => b.clone(),
-                        [a, _] if index % 2 == 1 => a.clone(),
-                        [a] => a.clone(),
-                        _ => unreachable!(),
-                    });
-                }
+        let tree = MerkleTreeBuilder::<_, Blake2b512>::new(data.iter());
+        assert_eq!(
+            Blake2bTree::merkle_root_of_hashes(tree.leaf_hashes().iter()),
+            tree.root_hex()
+        );
+        assert!(
+            MerkleTreeBuilder::<&str, Blake2b512>::new(core::iter::empty())
+                .leaf_hashes()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_estimated_memory_matches_levels() {
+        for n in [0u32, 1, 2, 3, 7, 8, 9, 1000] {
+            let tree = MerkleTreeBuilder::<_, Blake2b512>::new((0..n).map(|i| i.to_le_bytes()));
+            let actual: usize = tree
+                .levels()
+                .iter()
+                .map(|level| level.len() * core::mem::size_of::<Hash>())
+                .sum();
+            assert_eq!(estimated_memory(n as usize, 64), actual, "{n} leaves");
+        }
+        // 4 + 2 + 1 nodes, whatever the digest width.
+        assert_eq!(estimated_memory(4, 32), 7 * core::mem::size_of::<Hash>());
 
-                let concatenated = match chunk {
-                    [a, b] => a.clone() + b,
-                    [a] => a.clone() + a,
-                    _ => unreachable!(),
-                };
+        assert_eq!(estimated_memory(4, MAX_HASH_LEN + 1), usize::MAX);
+        // Roughly 2n nodes: the node count itself overflows for huge n, and
+        // the byte count long before.
+        assert_eq!(estimated_memory(usize::MAX / 2 + 2, 32), usize::MAX);
+        assert_eq!(estimated_memory(usize::MAX / 64, 32), usize::MAX);
+    }
+
+    #[test]
+    fn test_tree_depth() {
+        let expected = [
+            (0, 0),
+            (1, 0),
+            (2, 1),
+            (3, 2),
+            (4, 2),
+            (5, 3),
+            (7, 3),
+            (8, 3),
+            (9, 4),
+        ];
+        for (num_of_leaves, depth) in expected {
+            assert_eq!(tree_depth(num_of_leaves), depth, "{num_of_leaves} leaves");
+        }
 
-                let mut hasher = Blake2b512::new();
-                hasher.update(concatenated);
-                next_level.push(hex::encode(hasher.finalize()));
+        for n in 1..70 {
+            let tree =
+                MerkleTreeBuilder::<_, Blake2b512>::new((0..n).map(|i: u32| i.to_le_bytes()));
+            assert_eq!(tree_depth(n as usize), tree.levels.len() - 1);
+            assert_eq!(tree.proof(0).unwrap().hashes.len(), tree_depth(n as usize));
+            for i in 0..n as usize {
+                assert_eq!(
+                    tree.proof(i).unwrap().hashes.len(),
+                    proof_length(i, n as usize)
+                );
             }
+        }
+    }
 
-            index /= 2;
-            hashed_leaves = next_level;
+    #[test]
+    fn test_huge_leaf_counts() {
+        const HALF: usize = usize::MAX / 2 + 1;
+        let bits = usize::BITS as usize;
+        assert_eq!(tree_depth(usize::MAX), bits);
+        assert_eq!(tree_depth(HALF), bits - 1);
+        assert_eq!(tree_depth(HALF + 1), bits);
+        assert_eq!(proof_length(usize::MAX - 1, usize::MAX), bits - 1);
+        assert_eq!(proof_length(0, usize::MAX), bits);
+        assert_eq!(proof_directions(HALF - 1, HALF), vec![true; bits - 1]);
+
+        // The last of 2^(bits - 1) + 1 leaves is promoted alone up to the
+        // top, where it meets the root of all the others.
+        let others = Blake2bTree::hash_leaf("others");
+        let leaf_hash = Blake2bTree::hash_leaf("last");
+        let root = Blake2bTree::hash_node(&others, &leaf_hash).to_hex();
+        let legacy = MerkleProof {
+            hashes: vec![others],
+            num_of_leaves: HALF + 1,
+            leaf_index: HALF,
+            leaf_content: "last",
+            path_bits: Vec::new(),
+            root: None,
+        };
+        let with_bits = MerkleProof {
+            path_bits: proof_directions(HALF, HALF + 1),
+            ..legacy.clone()
+        };
+        assert_eq!(with_bits.path_bits, [true]);
+
+        for mut proof in [legacy, with_bits] {
+            assert_eq!(Blake2bTree::verify_proof(&root, &proof), Ok(()));
+            assert_eq!(
+                Blake2bTree::verify_proof_for_size(&root, HALF + 1, &proof),
+                Ok(())
+            );
+
+            proof.num_of_leaves = usize::MAX;
+            proof.leaf_index = usize::MAX - 1;
+            assert_eq!(
+                Blake2bTree::verify_proof(&root, &proof),
+                Err(VerifyError::InconsistentProofLength {
+                    expected: bits - 1,
+                    actual: 1
+                })
+            );
+            proof.leaf_index = usize::MAX;
+            assert_eq!(
+                Blake2bTree::verify_proof(&root, &proof),
+                Err(VerifyError::LeafIndexOutOfRange {
+                    index: usize::MAX,
+                    num_of_leaves: usize::MAX
+                })
+            );
+            proof.leaf_index = 0;
+            assert_eq!(
+                Blake2bTree::verify_proof(&root, &proof),
+                Err(VerifyError::InconsistentProofLength {
+                    expected: bits,
+                    actual: 1
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_proof_directions() {
+        assert_eq!(proof_directions(0, 8), [false, false, false]);
+        assert_eq!(proof_directions(5, 8), [true, false, true]);
+        assert_eq!(proof_directions(4, 5), [true]);
+        assert!(proof_directions(0, 1).is_empty());
+
+        // Folding the hashes by the reported sides reproduces the root.
+        for n in [2, 5, 7, 12] {
+            let data: Vec<String> = (0..n).map(|i| format!("leaf-{i}")).collect();
+            let root = Blake2bTree::merkle_root(data.iter());
+            for i in 0..n {
+                let proof = Blake2bTree::merkle_proof(data.iter(), i).unwrap();
+                let directions = proof_directions(i, n);
+                assert_eq!(directions.len(), proof_length(i, n));
+                let folded = proof.hashes.iter().zip(&directions).fold(
+                    Blake2bTree::hash_leaf(&data[i]),
+                    |hash, (sibling, &is_right)| {
+                        if is_right {
+                            Blake2bTree::hash_node(sibling, &hash)
+                        } else {
+                            Blake2bTree::hash_node(&hash, sibling)
+                        }
+                    },
+                );
+                assert_eq!(folded.to_hex(), root, "leaf {i} of {n}");
+            }
         }
+    }
 
-        proof
+    #[test]
+    fn test_proof_length_skips_lone_levels() {
+        // 5 leaves: the fifth is alone on the first two levels.
+        assert_eq!(proof_length(0, 5), 3);
+        assert_eq!(proof_length(4, 5), 1);
+        // 6 leaves: the last pair is alone on the second level only.
+        assert_eq!(proof_length(5, 6), 2);
+        assert_eq!(proof_length(0, 1), 0);
+        assert!((0..8).all(|i| proof_length(i, 8) == 3));
+    }
+
+    #[test]
+    fn test_small_trees_carry_no_self_siblings() {
+        // A lone node is promoted, so no level ever needs its own hash as a
+        // sibling and there is nothing for the verifier to regenerate.
+        for (n, lengths) in [(1, &[0][..]), (2, &[1, 1]), (3, &[2, 2, 1])] {
+            let data: Vec<String> = (0..n).map(|i| format!("leaf-{i}")).collect();
+            let root = Blake2bTree::merkle_root(data.iter());
+            for (i, &length) in lengths.iter().enumerate() {
+                let proof = Blake2bTree::merkle_proof(data.iter(), i).unwrap();
+                assert_eq!(proof.hashes.len(), length, "leaf {i} of {n}");
+                assert!(proof
+                    .hashes
+                    .iter()
+                    .all(|hash| *hash != Blake2bTree::hash_leaf(&data[i])));
+                let decoded = MerkleProof::from_bytes(&proof.to_bytes().unwrap()).unwrap();
+                assert!(Blake2bTree::verify_proof(&root, &decoded).is_ok());
+            }
+        }
     }
 
-    pub fn verify_proof(root: &String, proof: &MerkleProof<String>) -> bool {
-        let mut hash = {
-            let mut hasher = Blake2b512::new();
-            hasher.update(&proof.leaf_content);
-            hex::encode(hasher.finalize())
+    #[test]
+    fn test_odd_node_strategies() {
+        let data = ["a", "b", "c", "d", "e"];
+        let with = |odd_nodes| TreeOptions {
+            odd_nodes,
+            ..TreeOptions::default()
         };
+        // Cross-checked against an independent implementation of each rule.
+        let vectors = [
+            (OddNodeStrategy::Promote, "b6e6178307582518d07fc7433472d7876338fb1b756c47319360b89d05533cd2b0a842416db0b0b5e7833fc0a40880c2d5754bd74e61c4d6abf9e9bc1aa0ae68"),
+            (OddNodeStrategy::Duplicate, "06dfc70800ea927cb489c9d0bef37bee9301657288dc913c53a15fd5e9bb7da2ad952bf9dc31884161f1167b57ec647a5ab6dc50184feb207c281a682dc77c8d"),
+            (OddNodeStrategy::PadZero, "3d300943a4be81972319ec941ed22df316f41076b57bfcdb09e0754b36c7b01ddcb2b88107f74fa578634465fd3ef07287f659b11649b4fa439c8519337a6515"),
+        ];
+        for (strategy, expected) in vectors {
+            let options = with(strategy);
+            let root = Blake2bTree::merkle_root_with(data.iter(), &options);
+            assert_eq!(root, expected, "{strategy:?}");
 
-        let mut index = proof.leaf_index;
+            for i in 0..data.len() {
+                let proof = Blake2bTree::merkle_proof_with(data.iter(), i, &options).unwrap();
+                assert_eq!(proof.hashes.len(), proof_length(i, data.len()));
+                assert!(Blake2bTree::verify_proof_with(&root, &proof, &options).is_ok());
+            }
 
-        for sibling_hash in &proof.hashes {
-            let concatenated = if index % 2 == 0 {
-                hash.clone() + sibling_hash
-            } else {
-                sibling_hash.clone() + &hash
-            };
+            // Only the last leaf's path crosses lone nodes, so only its
+            // proof depends on the strategy the verifier assumes.
+            let proof = Blake2bTree::merkle_proof_with(data.iter(), 4, &options).unwrap();
+            for (other, _) in vectors.iter().filter(|(other, _)| *other != strategy) {
+                assert_eq!(
+                    Blake2bTree::verify_proof_with(&root, &proof, &with(*other)),
+                    Err(VerifyError::RootMismatch),
+                    "{strategy:?} proof checked as {other:?}"
+                );
+            }
 
-            let mut hasher = Blake2b512::new();
-            hasher.update(concatenated);
-            hash = hex::encode(hasher.finalize());
+            let tree =
+                MerkleTreeBuilder::<_, Blake2b512>::with_options(data.iter(), options.clone());
+            let multiproof = tree.multiproof(&[1, 4]).unwrap();
+            assert!(Blake2bTree::verify_multiproof_with(&root, &multiproof, &options).is_ok());
 
-            index /= 2;
+            let mut grown =
+                MerkleTreeBuilder::<_, Blake2b512>::with_options(data[..2].iter(), options.clone());
+            for leaf in &data[2..] {
+                grown.append(leaf);
+            }
+            assert_eq!(grown.root_hex(), root, "{strategy:?} after appends");
         }
 
-        *root == hash
+        // Duplication is why the default promotes instead.
+        let duplicate = with(OddNodeStrategy::Duplicate);
+        assert_eq!(
+            Blake2bTree::merkle_root_with(["a", "b", "c"].iter(), &duplicate),
+            Blake2bTree::merkle_root_with(["a", "b", "c", "c"].iter(), &duplicate)
+        );
+        let zero = with(OddNodeStrategy::PadZero);
+        assert_ne!(
+            MerkleTree::<Sha256>::merkle_root_with(["a", "b", "c"].iter(), &zero),
+            MerkleTree::<Sha256>::merkle_root_with(["a", "b", "c", "c"].iter(), &zero)
+        );
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_pad_to_power_of_two() {
+        let data = ["a", "b", "c", "d", "e"];
+        let padded = TreeOptions {
+            pad_to_power_of_two: true,
+            ..TreeOptions::default()
+        };
+        let tree = MerkleTreeBuilder::<_, Blake2b512>::with_options(data.iter(), padded.clone());
+        let root = tree.root_hex();
+        assert_eq!(root, "354665eed8e5d77699eda2c072426cd2ebede31d66b8a1e408626e88e3c30e56ac8c1f32410be6cfcc60a9db1181cbba6a22cbb4bab72b547d0342a9fb16c8f8");
+        assert_eq!(tree.leaf_count(), 5);
+        assert_eq!(tree.levels()[0].len(), 8);
+        assert_eq!(
+            tree.levels()[0][5..],
+            [Hash::from_slice(&[0; 64]).unwrap(); 3]
+        );
+        assert_ne!(root, Blake2bTree::merkle_root(data.iter()));
+
+        for i in 0..data.len() {
+            let proof = tree.proof(i).unwrap();
+            assert_eq!(proof.num_of_leaves, 8);
+            assert_eq!(proof.hashes.len(), 3, "every leaf has full depth");
+            assert_eq!(
+                Blake2bTree::verify_proof_with(&root, &proof, &padded),
+                Ok(())
+            );
+        }
+        assert_eq!(
+            tree.proof(5).unwrap_err(),
+            ProofError::IndexOutOfRange { index: 5, len: 5 }
+        );
+
+        // Powers of two and the empty tree are left as they are.
+        let four = &data[..4];
+        assert_eq!(
+            Blake2bTree::merkle_root_with(four.iter(), &padded),
+            Blake2bTree::merkle_root(four.iter())
+        );
+        assert_eq!(
+            Blake2bTree::merkle_root_with(std::iter::empty::<&str>(), &padded),
+            Blake2bTree::empty_root()
+        );
+    }
 
     #[test]
-    fn test_merkle_root() {
-        let data = vec!["a", "b", "c", "d", "e"];
-        let root = MerkleTree::merkle_root(data.iter().cloned().map(String::from));
-        assert!(!root.is_empty(), "Root hash should not be empty");
+    fn test_append_keeps_padding() {
+        let padded = TreeOptions {
+            pad_to_power_of_two: true,
+            ..TreeOptions::default()
+        };
+        let mut tree = MerkleTreeBuilder::<String, Blake2b512>::with_options(
+            std::iter::empty(),
+            padded.clone(),
+        );
+        let leaves: Vec<String> = (0..10).map(|i| format!("leaf-{i}")).collect();
+        for (n, leaf) in leaves.iter().enumerate() {
+            let root = tree.append(leaf.clone());
+            let fresh = MerkleTreeBuilder::<_, Blake2b512>::with_options(
+                leaves[..=n].iter().cloned(),
+                padded.clone(),
+            );
+            assert_eq!(root, fresh.root(), "{} leaves", n + 1);
+            assert_eq!(tree.levels(), fresh.levels());
+        }
     }
 
     #[test]
-    fn test_merkle_proof() {
-        let data = vec!["a", "b", "c", "d", "e"];
-        let proof = MerkleTree::merkle_proof(data.iter().cloned().map(String::from), 1);
-        assert_eq!(proof.leaf_index, 1);
-        assert_eq!(proof.leaf_content, "b");
+    fn test_trailing_duplicate_does_not_collide() {
+        let (a, b, c) = (
+            Blake2bTree::hash_leaf("a"),
+            Blake2bTree::hash_leaf("b"),
+            Blake2bTree::hash_leaf("c"),
+        );
+        let ab = Blake2bTree::hash_node(&a, &b);
+
+        // Pairing a lone node with itself made [a, b, c] and [a, b, c, c]
+        // indistinguishable (CVE-2012-2459).
+        let duplicated = Blake2bTree::hash_node(&ab, &Blake2bTree::hash_node(&c, &c));
+        assert_eq!(
+            duplicated.to_hex(),
+            Blake2bTree::merkle_root(["a", "b", "c", "c"].iter())
+        );
+
+        let root = Blake2bTree::merkle_root(["a", "b", "c"].iter());
+        assert_eq!(root, Blake2bTree::hash_node(&ab, &c).to_hex());
+        assert_ne!(root, Blake2bTree::merkle_root(["a", "b", "c", "c"].iter()));
+
+        // Nor can a proof from the four-leaf tree pass for the three-leaf one.
+        let mut proof = Blake2bTree::merkle_proof(["a", "b", "c", "c"].iter(), 3).unwrap();
+        assert!(Blake2bTree::verify_proof(duplicated.to_hex(), &proof).is_ok());
+        proof.num_of_leaves = 3;
+        proof.leaf_index = 2;
+        assert!(Blake2bTree::verify_proof(&root, &proof).is_err());
     }
 
     #[test]
-    fn test_verify_proof() {
-        let data = vec!["a", "b", "c", "d", "e"];
-        let root = MerkleTree::merkle_root(data.iter().cloned().map(String::from));
-        let proof = MerkleTree::merkle_proof(data.iter().cloned().map(String::from), 1);
-        assert!(
-            MerkleTree::verify_proof(&root, &proof),
-            "Proof should be valid"
+    fn test_every_proof_of_odd_trees() {
+        for n in [3, 5, 6, 7, 9] {
+            let data: Vec<String> = (0..n).map(|i| format!("leaf-{i}")).collect();
+            let root = Blake2bTree::merkle_root(data.iter());
+            for i in 0..n {
+                let proof = Blake2bTree::merkle_proof(data.iter(), i).unwrap();
+                assert_eq!(
+                    Blake2bTree::verify_proof(&root, &proof),
+                    Ok(()),
+                    "leaf {i} of {n}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_prehashed_leaves() {
+        let files = ["file-1", "file-2", "file-3"];
+        let digests: Vec<[u8; 32]> = files.iter().map(|f| Sha256::digest(f).into()).collect();
+
+        // The bottom level is the digests themselves.
+        let (a, b, c) = (
+            Hash::from_slice(&digests[0]).unwrap(),
+            Hash::from_slice(&digests[1]).unwrap(),
+            Hash::from_slice(&digests[2]).unwrap(),
+        );
+        let expected = Blake2bTree::hash_node(&Blake2bTree::hash_node(&a, &b), &c);
+        let root = Blake2bTree::merkle_root_of_hashes(digests.iter());
+        assert_eq!(root, expected.to_hex());
+        assert_ne!(root, Blake2bTree::merkle_root(digests.iter()));
+
+        for i in 0..digests.len() {
+            let proof = Blake2bTree::merkle_proof_of_hashes(digests.iter(), i).unwrap();
+            assert_eq!(proof.leaf_content, &digests[i]);
+            assert!(Blake2bTree::verify_proof_of_hashes(&root, &proof).is_ok());
+            assert_eq!(
+                Blake2bTree::verify_proof(&root, &proof),
+                Err(VerifyError::RootMismatch)
+            );
+        }
+
+        let too_long = MerkleProof {
+            hashes: vec![b, c],
+            num_of_leaves: 3,
+            leaf_index: 0,
+            leaf_content: [0u8; MAX_HASH_LEN + 1],
+            path_bits: Vec::new(),
+            root: None,
+        };
+        assert_eq!(
+            Blake2bTree::verify_proof_of_hashes(&root, &too_long),
+            Err(VerifyError::RootMismatch)
         );
     }
 
     #[test]
-    fn test_odd_number_of_leaves() {
-        let data = vec!["a", "b", "c", "d", "e", "f", "g"];
-        let root = MerkleTree::merkle_root(data.iter().cloned().map(String::from));
-        let proof = MerkleTree::merkle_proof(data.iter().cloned().map(String::from), 4);
-        assert!(
-            MerkleTree::verify_proof(&root, &proof),
-            "Proof should be valid"
+    fn test_prehashed_flag_must_agree() {
+        let digests: Vec<[u8; 32]> = (0..5).map(|i| Sha256::digest([i]).into()).collect();
+        let prehashed = TreeOptions {
+            prehashed_leaves: true,
+            ..TreeOptions::default()
+        };
+        let root = Blake2bTree::merkle_root_with(digests.iter(), &prehashed);
+        assert_eq!(root, Blake2bTree::merkle_root_of_hashes(digests.iter()));
+        let plain_root = Blake2bTree::merkle_root_with(digests.iter(), &TreeOptions::default());
+        assert_eq!(plain_root, Blake2bTree::merkle_root(digests.iter()));
+
+        for i in 0..digests.len() {
+            let proof = Blake2bTree::merkle_proof_with(digests.iter(), i, &prehashed).unwrap();
+            assert!(Blake2bTree::verify_proof_with(&root, &proof, &prehashed).is_ok());
+            assert_eq!(
+                Blake2bTree::verify_proof_with(&root, &proof, &TreeOptions::default()),
+                Err(VerifyError::RootMismatch)
+            );
+
+            let plain =
+                Blake2bTree::merkle_proof_with(digests.iter(), i, &TreeOptions::default()).unwrap();
+            assert!(Blake2bTree::verify_proof(&plain_root, &plain).is_ok());
+            assert_eq!(
+                Blake2bTree::verify_proof_with(&plain_root, &plain, &prehashed),
+                Err(VerifyError::RootMismatch)
+            );
+        }
+    }
+
+    #[test]
+    fn test_prehashed_leaves_are_identity() {
+        // Another system's bottom level: raw 64-byte values, nodes hashed as
+        // Blake2b-512(0x01 || left || right) with no leaf hashing at all.
+        let leaves: Vec<[u8; 64]> = (0..4u8).map(|i| [i; 64]).collect();
+        let node = |left: &[u8], right: &[u8]| -> [u8; 64] {
+            Blake2b512::new()
+                .chain_update([0x01])
+                .chain_update(left)
+                .chain_update(right)
+                .finalize()
+                .into()
+        };
+        let expected = node(&node(&leaves[0], &leaves[1]), &node(&leaves[2], &leaves[3]));
+
+        let identity = TreeOptions {
+            prehashed_leaves: true,
+            node_encoding: NodeEncoding::Raw,
+            ..TreeOptions::default()
+        };
+        let tree =
+            MerkleTreeBuilder::<_, Blake2b512>::with_options(leaves.iter(), identity.clone());
+        assert_eq!(tree.levels()[0][2].as_bytes(), &leaves[2]);
+        assert_eq!(tree.root_bytes(), expected);
+
+        let root = hex::encode(expected);
+        for i in 0..leaves.len() {
+            let proof = tree.proof(i).unwrap();
+            assert!(Blake2bTree::verify_proof_with(&root, &proof, &identity).is_ok());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds MAX_HASH_LEN")]
+    fn test_prehashed_leaf_too_long_panics() {
+        Blake2bTree::merkle_root_of_hashes([[0u8; MAX_HASH_LEN + 1]].iter());
+    }
+
+    #[test]
+    fn test_sorted_pairs_vector() {
+        let data = ["a", "b", "c", "d"];
+        let sorted = TreeOptions {
+            sorted_pairs: true,
+            ..TreeOptions::default()
+        };
+        let tree = MerkleTreeBuilder::<_, Blake2b512>::with_options(data.iter(), sorted);
+
+        // Recompute the root by hand: H(0x01 || hex(min) || hex(max)) at each node.
+        let leaf = |s: &str| Blake2b512::digest([b"\x00".as_slice(), s.as_bytes()].concat());
+        let node = |x: &[u8], y: &[u8]| {
+            let (lo, hi) = if x <= y { (x, y) } else { (y, x) };
+            Blake2b512::digest(
+                [
+                    b"\x01".as_slice(),
+                    hex::encode(lo).as_bytes(),
+                    hex::encode(hi).as_bytes(),
+                ]
+                .concat(),
+            )
+        };
+        let left = node(&leaf("a"), &leaf("b"));
+        let right = node(&leaf("c"), &leaf("d"));
+        assert_eq!(tree.root_hex(), hex::encode(node(&left, &right)));
+        assert_ne!(tree.root_hex(), Blake2bTree::merkle_root(data.iter()));
+    }
+
+    #[test]
+    fn test_sorted_pairs_position_independent() {
+        let data: Vec<String> = (0..9).map(|i| format!("leaf-{i}")).collect();
+        let sorted = TreeOptions {
+            sorted_pairs: true,
+            ..TreeOptions::default()
+        };
+        let tree = MerkleTreeBuilder::<_, Blake2b512>::with_options(data.iter(), sorted.clone());
+        let root = tree.root_hex();
+
+        let mut rejected_positionally = 0;
+        for i in 0..data.len() {
+            let mut proof = tree.proof(i).unwrap();
+            assert!(Blake2bTree::verify_proof_with(&root, &proof, &sorted).is_ok());
+            if Blake2bTree::verify_proof(&root, &proof).is_err() {
+                rejected_positionally += 1;
+            }
+
+            // Sides no longer matter, so claiming to be the sibling leaf
+            // (which shares every ancestor) still verifies, unless the proof
+            // recorded the path bits of its real position.
+            if i ^ 1 < data.len() {
+                proof.leaf_index = i ^ 1;
+                assert_eq!(
+                    Blake2bTree::verify_proof_with(&root, &proof, &sorted),
+                    Err(VerifyError::PositionMismatch { level: 0 })
+                );
+                proof.path_bits.clear();
+                assert!(Blake2bTree::verify_proof_with(&root, &proof, &sorted).is_ok());
+            }
+        }
+        assert!(rejected_positionally > 0);
+    }
+
+    #[test]
+    fn test_right_left_child_order() {
+        let data: Vec<String> = (0..7).map(|i| format!("leaf-{i}")).collect();
+        let legacy = TreeOptions {
+            child_order: ChildOrder::RightLeft,
+            ..TreeOptions::default()
+        };
+        let root = Blake2bTree::merkle_root_with(data.iter(), &legacy);
+        assert_ne!(root, Blake2bTree::merkle_root(data.iter()));
+
+        let (a, b) = (Blake2bTree::hash_leaf("a"), Blake2bTree::hash_leaf("b"));
+        assert_eq!(
+            Blake2bTree::merkle_root_with(["a", "b"].iter(), &legacy),
+            Blake2bTree::hash_node(&b, &a).to_hex()
+        );
+
+        let tree = MerkleTreeBuilder::<_, Blake2b512>::with_options(data.iter(), legacy.clone());
+        assert_eq!(tree.root_hex(), root);
+        for i in 0..data.len() {
+            let proof = Blake2bTree::merkle_proof_with(data.iter(), i, &legacy).unwrap();
+            assert_eq!(tree.proof(i).unwrap(), proof);
+            assert_eq!(
+                Blake2bTree::verify_proof_with(&root, &proof, &legacy),
+                Ok(())
+            );
+            assert!(Blake2bTree::verify_proof(&root, &proof).is_err());
+        }
+    }
+
+    #[test]
+    fn test_pair_order_comparator() {
+        let data: Vec<String> = (0..9).map(|i| format!("leaf-{i}")).collect();
+        let reverse = TreeOptions {
+            pair_order: Some(PairComparator(|a, b| b.cmp(a))),
+            ..TreeOptions::default()
+        };
+        let sorted = TreeOptions {
+            sorted_pairs: true,
+            ..TreeOptions::default()
+        };
+        let tree = MerkleTreeBuilder::<_, Blake2b512>::with_options(data.iter(), reverse.clone());
+        let root = tree.root_hex();
+
+        // Reverse order puts the larger child first at every node.
+        let (a, b) = (Blake2bTree::hash_leaf("a"), Blake2bTree::hash_leaf("b"));
+        let (hi, lo) = if a > b { (a, b) } else { (b, a) };
+        assert_eq!(
+            Blake2bTree::merkle_root_with(["a", "b"].iter(), &reverse),
+            Blake2bTree::hash_node(&hi, &lo).to_hex()
         );
+
+        for i in 0..data.len() {
+            let mut proof = tree.proof(i).unwrap();
+            assert_eq!(
+                Blake2bTree::verify_proof_with(&root, &proof, &reverse),
+                Ok(())
+            );
+            assert!(Blake2bTree::verify_proof_with(&root, &proof, &sorted).is_err());
+            assert!(Blake2bTree::verify_proof(&root, &proof).is_err());
+            if i ^ 1 < data.len() {
+                proof.leaf_index = i ^ 1;
+                assert!(Blake2bTree::verify_proof_with(&root, &proof, &reverse).is_err());
+                proof.path_bits.clear();
+                assert!(Blake2bTree::verify_proof_with(&root, &proof, &reverse).is_ok());
+            }
+        }
+
+        // The comparator takes precedence over sorted_pairs.
+        let both = TreeOptions {
+            sorted_pairs: true,
+            ..reverse.clone()
+        };
+        assert_eq!(Blake2bTree::merkle_root_with(data.iter(), &both), root);
     }
 }
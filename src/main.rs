@@ -1,20 +1,111 @@
-fn main() {
-    use merkle::*;
+//! Command-line front end over the Blake2b-512 tree.
+//!
+//! Leaves are read one per line, skipping empty lines as
+//! `merkle_root_from_reader` does, from a file or from stdin when the file
+//! is `-` or omitted. Results are printed as JSON.
 
-    let data = vec![
-        "abc".to_string(),
-        "bcd".to_string(),
-        "cde".to_string(),
-        "def".to_string(),
-        "efg".to_string(),
-    ];
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::process::ExitCode;
 
-    let root = MerkleTree::merkle_root(data.clone().into_iter());
-    println!("Merkle Root: {:?}", root);
+use merkle::{Blake2bTree, MerkleProof};
+use serde_json::json;
 
-    let proof = MerkleTree::merkle_proof(data.clone().into_iter(), 0);
-    println!("Merkle Proof: {:?}", proof);
+const USAGE: &str = "usage:
+    merkle root [<file>]
+    merkle proof <file> <index>
+    merkle verify <root> [<proof.json>]
 
-    let is_valid = MerkleTree::verify_proof(&root, &proof);
-    println!("Is proof valid? {}", is_valid);
+A file of `-`, or none, reads from stdin.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(code) => code,
+        Err(message) => {
+            eprintln!("error: {message}\n\n{USAGE}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Runs one subcommand, returning the exit code or a message for stderr.
+fn run(args: &[String]) -> Result<ExitCode, String> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["root"] => root("-"),
+        ["root", file] => root(file),
+        ["proof", file, index] => {
+            let index = index
+                .parse()
+                .map_err(|_| format!("invalid leaf index {index:?}"))?;
+            proof(file, index)
+        }
+        ["verify", root] => verify(root, "-"),
+        ["verify", root, proof] => verify(root, proof),
+        [] => Err("missing subcommand".to_string()),
+        [command @ ("root" | "proof" | "verify"), ..] => {
+            Err(format!("wrong number of arguments for {command}"))
+        }
+        [command, ..] => Err(format!("unknown subcommand {command:?}")),
+    }
+}
+
+fn root(file: &str) -> Result<ExitCode, String> {
+    let root = Blake2bTree::merkle_root_from_reader(open(file)?, false)
+        .map_err(|e| format!("cannot read {file}: {e}"))?;
+    println!("{}", json!({ "root": root }));
+    Ok(ExitCode::SUCCESS)
+}
+
+fn proof(file: &str, index: usize) -> Result<ExitCode, String> {
+    let leaves = read_leaves(file)?;
+    let proof = Blake2bTree::merkle_proof(leaves.into_iter(), index).map_err(|e| e.to_string())?;
+    println!(
+        "{}",
+        serde_json::to_string(&proof).map_err(|e| e.to_string())?
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn verify(root: &str, file: &str) -> Result<ExitCode, String> {
+    let mut json = String::new();
+    open(file)?
+        .read_to_string(&mut json)
+        .map_err(|e| format!("cannot read {file}: {e}"))?;
+    let proof: MerkleProof<String> =
+        serde_json::from_str(&json).map_err(|e| format!("invalid proof in {file}: {e}"))?;
+
+    match Blake2bTree::verify_proof(root, &proof) {
+        Ok(()) => {
+            println!("{}", json!({ "valid": true }));
+            Ok(ExitCode::SUCCESS)
+        }
+        Err(e) => {
+            println!("{}", json!({ "valid": false, "error": e.to_string() }));
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
+/// Opens `file`, or stdin for `-`.
+fn open(file: &str) -> Result<Box<dyn BufRead>, String> {
+    if file == "-" {
+        return Ok(Box::new(io::stdin().lock()));
+    }
+    File::open(file)
+        .map(|f| Box::new(BufReader::new(f)) as Box<dyn BufRead>)
+        .map_err(|e| format!("cannot open {file}: {e}"))
+}
+
+/// Reads the non-empty lines of `file`, without their line endings.
+fn read_leaves(file: &str) -> Result<Vec<String>, String> {
+    let mut leaves = Vec::new();
+    for line in open(file)?.lines() {
+        let line = line.map_err(|e| format!("cannot read {file}: {e}"))?;
+        if !line.is_empty() {
+            leaves.push(line);
+        }
+    }
+    Ok(leaves)
 }
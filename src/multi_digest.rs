@@ -0,0 +1,97 @@
+//! Roots of the same leaves under several digests chosen at runtime.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Blake2b512, Blake2s256};
+
+use crate::MerkleTree;
+
+/// A digest [`merkle_roots_multi`] can build a tree with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, core::hash::Hash)]
+#[non_exhaustive]
+pub enum DigestKind {
+    /// Blake2b-512, as in [`Blake2bTree`](crate::Blake2bTree).
+    Blake2b512,
+    /// Blake2s-256, as in [`Blake2sTree`](crate::Blake2sTree).
+    Blake2s256,
+    /// Blake2b with a 32-byte output, as in
+    /// [`Blake2b256Tree`](crate::Blake2b256Tree).
+    Blake2b256,
+    /// SHA-256, enabled by the `sha2` feature.
+    #[cfg(feature = "sha2")]
+    Sha256,
+}
+
+/// The [`merkle_root`](MerkleTree::merkle_root) of `leaves` under each digest
+/// in `kinds`, in the same order.
+///
+/// The leaves are collected once and every tree is built from that buffer,
+/// so a source that can only be iterated once yields every root.
+pub fn merkle_roots_multi<I, L>(leaves: I, kinds: &[DigestKind]) -> Vec<String>
+where
+    I: Iterator<Item = L>,
+    L: AsRef<[u8]>,
+{
+    let leaves: Vec<L> = leaves.collect();
+    kinds
+        .iter()
+        .map(|kind| match kind {
+            DigestKind::Blake2b512 => MerkleTree::<Blake2b512>::merkle_root(leaves.iter()),
+            DigestKind::Blake2s256 => MerkleTree::<Blake2s256>::merkle_root(leaves.iter()),
+            DigestKind::Blake2b256 => MerkleTree::<Blake2b<U32>>::merkle_root(leaves.iter()),
+            #[cfg(feature = "sha2")]
+            DigestKind::Sha256 => MerkleTree::<sha2::Sha256>::merkle_root(leaves.iter()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Blake2b256Tree, Blake2bTree, Blake2sTree};
+
+    #[test]
+    fn test_roots_match_single_digest_builds() {
+        for n in [0, 1, 2, 5, 16] {
+            let leaves: Vec<String> = (0..n).map(|i| format!("leaf-{i}")).collect();
+            // A consuming iterator, which cannot be replayed.
+            let roots = merkle_roots_multi(
+                leaves.clone().into_iter(),
+                &[
+                    DigestKind::Blake2b512,
+                    DigestKind::Blake2s256,
+                    DigestKind::Blake2b256,
+                    DigestKind::Blake2b512,
+                ],
+            );
+            let blake2b = Blake2bTree::merkle_root(leaves.iter());
+            assert_eq!(
+                roots,
+                [
+                    blake2b.clone(),
+                    Blake2sTree::merkle_root(leaves.iter()),
+                    Blake2b256Tree::merkle_root(leaves.iter()),
+                    blake2b,
+                ]
+            );
+        }
+        assert!(merkle_roots_multi(["a"].iter(), &[]).is_empty());
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn test_sha256_root() {
+        let leaves = ["a", "b", "c"];
+        let roots =
+            merkle_roots_multi(leaves.iter(), &[DigestKind::Blake2b512, DigestKind::Sha256]);
+        assert_eq!(
+            roots,
+            [
+                Blake2bTree::merkle_root(leaves.iter()),
+                MerkleTree::<sha2::Sha256>::merkle_root(leaves.iter()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,330 @@
+//! Proofs covering several leaves at once.
+//!
+//! Independent proofs for `k` leaves of a depth-`d` tree carry `k * d`
+//! sibling hashes. Leaves that share ancestors only need those ancestors'
+//! siblings once, and a sibling that is itself one of the proven nodes never
+//! needs to be sent at all. For `k` scattered leaves a multiproof holds
+//! roughly `k * (d - log2(k))` hashes; for adjacent leaves it is smaller
+//! still (two neighbouring leaves need `d - 1` hashes between them instead
+//! of `2 * d`).
+
+use alloc::vec::Vec;
+
+use blake2::Digest;
+
+use crate::{Hash, MerkleTree, MerkleTreeBuilder, ProofError, TreeOptions, VerifyError};
+
+/// Inclusion proof for a set of leaves of the same tree.
+#[derive(Debug, Clone, PartialEq, Eq, core::hash::Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MerkleMultiproof<T> {
+    /// Sibling hashes the verifier cannot derive itself, bottom level first
+    /// and left to right within a level.
+    pub hashes: Vec<Hash>,
+    pub num_of_leaves: usize,
+    /// Proven leaf indices, strictly increasing.
+    pub leaf_indices: Vec<usize>,
+    /// Contents of the proven leaves, in the same order as `leaf_indices`.
+    pub leaf_contents: Vec<T>,
+}
+
+impl<D: Digest> MerkleTree<D> {
+    /// Builds one proof for every leaf in `indices`. Duplicate indices are
+    /// ignored and the order they are given in does not matter.
+    pub fn merkle_multiproof<I, L>(
+        leaves: I,
+        indices: &[usize],
+    ) -> Result<MerkleMultiproof<L>, ProofError>
+    where
+        I: Iterator<Item = L>,
+        L: AsRef<[u8]> + Clone,
+    {
+        MerkleTreeBuilder::<L, D>::new(leaves).multiproof(indices)
+    }
+
+    /// Checks that every leaf in `multiproof` belongs to the tree with `root`.
+    pub fn verify_multiproof<T: AsRef<[u8]>>(
+        root: impl AsRef<str>,
+        multiproof: &MerkleMultiproof<T>,
+    ) -> Result<(), VerifyError> {
+        Self::verify_multiproof_with(root, multiproof, &TreeOptions::default())
+    }
+
+    /// Like [`verify_multiproof`](Self::verify_multiproof), for a tree built
+    /// with `options`.
+    pub fn verify_multiproof_with<T: AsRef<[u8]>>(
+        root: impl AsRef<str>,
+        multiproof: &MerkleMultiproof<T>,
+        options: &TreeOptions,
+    ) -> Result<(), VerifyError> {
+        let indices = &multiproof.leaf_indices;
+        if indices.is_empty()
+            || indices.len() != multiproof.leaf_contents.len()
+            || !indices.windows(2).all(|pair| pair[0] < pair[1])
+        {
+            return Err(VerifyError::MalformedMultiproof);
+        }
+        if let Some(&index) = indices.last().filter(|&&i| i >= multiproof.num_of_leaves) {
+            return Err(VerifyError::LeafIndexOutOfRange {
+                index,
+                num_of_leaves: multiproof.num_of_leaves,
+            });
+        }
+
+        let expected = sibling_count(indices, multiproof.num_of_leaves);
+        if multiproof.hashes.len() != expected {
+            return Err(VerifyError::InconsistentProofLength {
+                expected,
+                actual: multiproof.hashes.len(),
+            });
+        }
+
+        let Some(mut known) = indices
+            .iter()
+            .zip(&multiproof.leaf_contents)
+            .map(|(&index, leaf)| Some((index, Self::leaf_hash(options, leaf)?)))
+            .collect::<Option<Vec<(usize, Hash)>>>()
+        else {
+            return Err(VerifyError::RootMismatch);
+        };
+        let mut siblings = multiproof.hashes.iter();
+        let mut len = multiproof.num_of_leaves;
+
+        while len > 1 {
+            let mut next_level = Vec::with_capacity(known.len());
+            let mut i = 0;
+            while i < known.len() {
+                let (index, hash) = known[i];
+                let parent = if pairs_with_next(&known, i, |&(index, _)| index) {
+                    i += 1;
+                    Self::hash_pair(options, &hash, &known[i].1)
+                } else if index ^ 1 >= len {
+                    // A trailing odd node has no sibling in the proof.
+                    Self::hash_lone(options, &hash)
+                } else {
+                    let sibling = siblings.next().expect("proof length checked above");
+                    if index.is_multiple_of(2) {
+                        Self::hash_pair(options, &hash, sibling)
+                    } else {
+                        Self::hash_pair(options, sibling, &hash)
+                    }
+                };
+                next_level.push((index / 2, parent));
+                i += 1;
+            }
+            known = next_level;
+            len = len.div_ceil(2);
+        }
+
+        if root.as_ref() == known[0].1.to_hex() {
+            Ok(())
+        } else {
+            Err(VerifyError::RootMismatch)
+        }
+    }
+}
+
+impl<L: AsRef<[u8]> + Clone, D: Digest> MerkleTreeBuilder<L, D> {
+    /// Builds one proof for every leaf in `indices` from the cached levels.
+    pub fn multiproof(&self, indices: &[usize]) -> Result<MerkleMultiproof<L>, ProofError> {
+        if self.leaves.is_empty() {
+            return Err(ProofError::EmptyTree);
+        }
+
+        let mut leaf_indices = indices.to_vec();
+        leaf_indices.sort_unstable();
+        leaf_indices.dedup();
+        match leaf_indices.last() {
+            None => return Err(ProofError::NoIndices),
+            Some(&index) if index >= self.leaves.len() => {
+                return Err(ProofError::IndexOutOfRange {
+                    index,
+                    len: self.leaves.len(),
+                })
+            }
+            Some(_) => {}
+        }
+
+        let mut hashes = Vec::new();
+        let mut known = leaf_indices.clone();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let mut i = 0;
+            while i < known.len() {
+                let index = known[i];
+                if pairs_with_next(&known, i, |&index| index) {
+                    i += 1;
+                } else if let Some(sibling) = level.get(index ^ 1) {
+                    // A trailing odd node has no sibling to carry.
+                    hashes.push(*sibling);
+                }
+                i += 1;
+            }
+            known.iter_mut().for_each(|index| *index /= 2);
+            known.dedup();
+        }
+
+        Ok(MerkleMultiproof {
+            hashes,
+            num_of_leaves: self.width(),
+            leaf_contents: leaf_indices
+                .iter()
+                .map(|&index| self.leaves[index].clone())
+                .collect(),
+            leaf_indices,
+        })
+    }
+}
+
+/// Whether the node at `known[i]` is a left child whose right sibling is
+/// `known[i + 1]`, in which case neither needs a hash from the proof.
+pub(crate) fn pairs_with_next<K>(known: &[K], i: usize, index: impl Fn(&K) -> usize) -> bool {
+    let current = index(&known[i]);
+    current.is_multiple_of(2)
+        && known
+            .get(i + 1)
+            .is_some_and(|next| index(next) == current + 1)
+}
+
+/// Number of sibling hashes a multiproof for `indices` must carry.
+fn sibling_count(indices: &[usize], num_of_leaves: usize) -> usize {
+    let mut known = indices.to_vec();
+    let mut len = num_of_leaves;
+    let mut count = 0;
+    while len > 1 {
+        let mut i = 0;
+        while i < known.len() {
+            if pairs_with_next(&known, i, |&index| index) {
+                i += 1;
+            } else if known[i] ^ 1 < len {
+                count += 1;
+            }
+            i += 1;
+        }
+        known.iter_mut().for_each(|index| *index /= 2);
+        known.dedup();
+        len = len.div_ceil(2);
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tree_depth, Blake2bTree};
+
+    fn leaves(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("leaf-{i}")).collect()
+    }
+
+    #[test]
+    fn test_single_index_matches_single_proof() {
+        for n in [1, 2, 3, 5, 8, 13] {
+            let data = leaves(n);
+            let root = Blake2bTree::merkle_root(data.iter());
+            for i in 0..n {
+                let multiproof = Blake2bTree::merkle_multiproof(data.iter(), &[i]).unwrap();
+                let proof = Blake2bTree::merkle_proof(data.iter(), i).unwrap();
+                assert_eq!(multiproof.hashes, proof.hashes, "n = {n}, i = {i}");
+                assert!(Blake2bTree::verify_multiproof(&root, &multiproof).is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn test_multiproof_verifies() {
+        let data = leaves(23);
+        let root = Blake2bTree::merkle_root(data.iter());
+        for indices in [
+            vec![0, 1],
+            vec![22, 0, 7],
+            vec![3, 4, 5, 6],
+            (0..23).collect(),
+        ] {
+            let multiproof = Blake2bTree::merkle_multiproof(data.iter(), &indices).unwrap();
+            assert!(Blake2bTree::verify_multiproof(&root, &multiproof).is_ok());
+        }
+
+        let all =
+            Blake2bTree::merkle_multiproof(data.iter(), &(0..23).collect::<Vec<_>>()).unwrap();
+        // Every node is derivable, so nothing needs to be carried.
+        assert!(all.hashes.is_empty());
+    }
+
+    #[test]
+    fn test_multiproof_is_smaller_than_independent_proofs() {
+        let data = leaves(10_000);
+        let tree = MerkleTreeBuilder::<_, blake2::Blake2b512>::new(data.iter());
+        let indices: Vec<usize> = (0..50).map(|i| i * 197 + 3).collect();
+
+        let multiproof = tree.multiproof(&indices).unwrap();
+        let independent: usize = indices
+            .iter()
+            .map(|&i| tree.proof(i).unwrap().hashes.len())
+            .sum();
+        assert!(multiproof.hashes.len() < independent);
+        assert!(Blake2bTree::verify_multiproof(tree.root_hex(), &multiproof).is_ok());
+
+        let adjacent = tree.multiproof(&[4000, 4001]).unwrap();
+        assert_eq!(adjacent.hashes.len(), tree_depth(10_000) - 1);
+    }
+
+    #[test]
+    fn test_multiproof_rejects_tampering() {
+        let data = leaves(9);
+        let root = Blake2bTree::merkle_root(data.iter());
+        let mut multiproof = Blake2bTree::merkle_multiproof(data.iter(), &[1, 4, 8]).unwrap();
+
+        multiproof.leaf_contents[1] = &data[5];
+        assert_eq!(
+            Blake2bTree::verify_multiproof(&root, &multiproof),
+            Err(VerifyError::RootMismatch)
+        );
+
+        multiproof.leaf_contents[1] = &data[4];
+        multiproof.leaf_indices = vec![4, 1, 8];
+        assert_eq!(
+            Blake2bTree::verify_multiproof(&root, &multiproof),
+            Err(VerifyError::MalformedMultiproof)
+        );
+
+        multiproof.leaf_indices = vec![1, 4, 8];
+        let extra = multiproof.hashes[0];
+        multiproof.hashes.push(extra);
+        assert!(matches!(
+            Blake2bTree::verify_multiproof(&root, &multiproof),
+            Err(VerifyError::InconsistentProofLength { .. })
+        ));
+    }
+
+    #[test]
+    fn test_multiproof_errors() {
+        let data = leaves(4);
+        assert_eq!(
+            Blake2bTree::merkle_multiproof(data.iter(), &[]).unwrap_err(),
+            ProofError::NoIndices
+        );
+        assert_eq!(
+            Blake2bTree::merkle_multiproof(data.iter(), &[1, 4]).unwrap_err(),
+            ProofError::IndexOutOfRange { index: 4, len: 4 }
+        );
+        assert_eq!(
+            Blake2bTree::merkle_multiproof(std::iter::empty::<&str>(), &[0]).unwrap_err(),
+            ProofError::EmptyTree
+        );
+    }
+
+    #[test]
+    fn test_sorted_pairs_multiproof() {
+        let data = leaves(12);
+        let options = TreeOptions {
+            sorted_pairs: true,
+            ..TreeOptions::default()
+        };
+        let tree =
+            MerkleTreeBuilder::<_, blake2::Blake2b512>::with_options(data.iter(), options.clone());
+        let multiproof = tree.multiproof(&[2, 3, 11]).unwrap();
+        assert!(
+            Blake2bTree::verify_multiproof_with(tree.root_hex(), &multiproof, &options).is_ok()
+        );
+    }
+}
@@ -0,0 +1,320 @@
+//! Trees where every internal node has up to `arity` children.
+//!
+//! A wider tree is shallower, so its proofs have fewer levels, at the cost of
+//! up to `arity - 1` sibling hashes per level. A trailing group with fewer
+//! than `arity` nodes is hashed with just the children it has, and a lone
+//! trailing node is promoted unchanged. For `arity = 2` that is exactly the
+//! binary construction: roots and proofs from this module then match
+//! [`MerkleTree::merkle_root`] and [`MerkleTree::merkle_proof`].
+
+use core::iter;
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use blake2::Digest;
+
+use crate::{Hash, MerkleTree, ProofError, VerifyError};
+
+/// One level of an [`NaryProof`]. Levels where the proven node is promoted
+/// alone have no entry.
+#[derive(Debug, Clone, PartialEq, Eq, core::hash::Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NaryProofLevel {
+    /// Position of the proven node among its siblings, `0..arity`.
+    pub position: usize,
+    /// The other children of the parent, left to right: `arity - 1` of them
+    /// except in the trailing group of a level.
+    pub siblings: Vec<Hash>,
+}
+
+/// Inclusion proof for a leaf of an n-ary tree.
+#[derive(Debug, Clone, PartialEq, Eq, core::hash::Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NaryProof<T> {
+    pub arity: usize,
+    /// Bottom level first.
+    pub levels: Vec<NaryProofLevel>,
+    pub num_of_leaves: usize,
+    pub leaf_index: usize,
+    pub leaf_content: T,
+}
+
+impl<D: Digest> MerkleTree<D> {
+    /// Computes the hex-encoded root of the `arity`-ary tree over `leaves`.
+    ///
+    /// Panics if `arity` is below 2.
+    pub fn merkle_root_with_arity<I, L>(leaves: I, arity: usize) -> String
+    where
+        I: Iterator<Item = L>,
+        L: AsRef<[u8]>,
+    {
+        let hashed_leaves = leaves.map(Self::hash_leaf).collect();
+        match Self::nary_levels(hashed_leaves, arity).last() {
+            Some(level) if !level.is_empty() => level[0].to_hex(),
+            _ => Self::empty_root(),
+        }
+    }
+
+    /// Builds the inclusion proof for the leaf at `leaf_index` of the
+    /// `arity`-ary tree over `leaves`.
+    ///
+    /// Panics if `arity` is below 2.
+    pub fn merkle_proof_with_arity<I, L>(
+        leaves: I,
+        leaf_index: usize,
+        arity: usize,
+    ) -> Result<NaryProof<L>, ProofError>
+    where
+        I: Iterator<Item = L>,
+        L: AsRef<[u8]>,
+    {
+        let mut leaves: Vec<L> = leaves.collect();
+        if leaves.is_empty() {
+            return Err(ProofError::EmptyTree);
+        }
+        if leaf_index >= leaves.len() {
+            return Err(ProofError::IndexOutOfRange {
+                index: leaf_index,
+                len: leaves.len(),
+            });
+        }
+
+        let levels = Self::nary_levels(leaves.iter().map(Self::hash_leaf).collect(), arity);
+        let mut index = leaf_index;
+        let proof_levels = levels[..levels.len() - 1]
+            .iter()
+            .filter_map(|level| {
+                let start = index - index % arity;
+                let group = &level[start..level.len().min(start + arity)];
+                let position = index % arity;
+                index /= arity;
+                (group.len() > 1).then(|| NaryProofLevel {
+                    position,
+                    siblings: group
+                        .iter()
+                        .enumerate()
+                        .filter(|&(i, _)| i != position)
+                        .map(|(_, hash)| *hash)
+                        .collect(),
+                })
+            })
+            .collect();
+
+        Ok(NaryProof {
+            arity,
+            levels: proof_levels,
+            num_of_leaves: leaves.len(),
+            leaf_index,
+            leaf_content: leaves.swap_remove(leaf_index),
+        })
+    }
+
+    /// Checks that `proof` is consistent with an `proof.arity`-ary tree of
+    /// `proof.num_of_leaves` leaves and that it hashes to `root`.
+    pub fn verify_nary_proof<T: AsRef<[u8]>>(
+        root: impl AsRef<str>,
+        proof: &NaryProof<T>,
+    ) -> Result<(), VerifyError> {
+        let arity = proof.arity;
+        if arity < 2 {
+            return Err(VerifyError::InvalidArity(arity));
+        }
+        if proof.leaf_index >= proof.num_of_leaves {
+            return Err(VerifyError::LeafIndexOutOfRange {
+                index: proof.leaf_index,
+                num_of_leaves: proof.num_of_leaves,
+            });
+        }
+
+        let expected = nary_proof_length(proof.leaf_index, proof.num_of_leaves, arity);
+        if proof.levels.len() != expected {
+            return Err(VerifyError::InconsistentProofLength {
+                expected,
+                actual: proof.levels.len(),
+            });
+        }
+
+        let mut hash = Self::hash_leaf(&proof.leaf_content);
+        let mut index = proof.leaf_index;
+        let mut len = proof.num_of_leaves;
+        let mut steps = proof.levels.iter().enumerate();
+
+        while len > 1 {
+            let group_len = group_len(index, len, arity);
+            if group_len > 1 {
+                let (level, step) = steps.next().expect("proof length checked above");
+                if step.siblings.len() != group_len - 1 {
+                    return Err(VerifyError::InconsistentProofLength {
+                        expected: group_len - 1,
+                        actual: step.siblings.len(),
+                    });
+                }
+                if step.position != index % arity {
+                    return Err(VerifyError::PositionMismatch { level });
+                }
+
+                let (before, after) = step.siblings.split_at(step.position);
+                hash = Self::hash_children(before.iter().chain(iter::once(&hash)).chain(after));
+            }
+            index /= arity;
+            len = len.div_ceil(arity);
+        }
+
+        if root.as_ref() == hash.to_hex() {
+            Ok(())
+        } else {
+            Err(VerifyError::RootMismatch)
+        }
+    }
+
+    /// Every level of the `arity`-ary tree over `hashed_leaves`, leaves first.
+    fn nary_levels(hashed_leaves: Vec<Hash>, arity: usize) -> Vec<Vec<Hash>> {
+        assert!(arity >= 2, "arity must be at least 2, got {arity}");
+
+        let mut levels = vec![hashed_leaves];
+        while levels[levels.len() - 1].len() > 1 {
+            let next_level = levels[levels.len() - 1]
+                .chunks(arity)
+                .map(|group| match group {
+                    [lone] => *lone,
+                    _ => Self::hash_children(group),
+                })
+                .collect();
+            levels.push(next_level);
+        }
+        levels
+    }
+}
+
+/// Size of the group that the node at `index` of a level of `len` nodes
+/// belongs to.
+fn group_len(index: usize, len: usize, arity: usize) -> usize {
+    let start = index - index % arity;
+    arity.min(len - start)
+}
+
+/// Number of levels in the proof for the leaf at `leaf_index`: the depth
+/// less the levels where that leaf's ancestor is promoted alone.
+fn nary_proof_length(leaf_index: usize, num_of_leaves: usize, arity: usize) -> usize {
+    let (mut index, mut len) = (leaf_index, num_of_leaves);
+    let mut length = 0;
+    while len > 1 {
+        if group_len(index, len, arity) > 1 {
+            length += 1;
+        }
+        index /= arity;
+        len = len.div_ceil(arity);
+    }
+    length
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Blake2bTree;
+
+    fn leaves(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("leaf-{i}")).collect()
+    }
+
+    /// Number of levels above the leaves in an `arity`-ary tree.
+    fn nary_depth(num_of_leaves: usize, arity: usize) -> usize {
+        let mut len = num_of_leaves;
+        let mut depth = 0;
+        while len > 1 {
+            len = len.div_ceil(arity);
+            depth += 1;
+        }
+        depth
+    }
+
+    #[test]
+    fn test_arity_two_matches_binary_tree() {
+        for n in [1, 2, 3, 5, 8, 11] {
+            let data = leaves(n);
+            assert_eq!(
+                Blake2bTree::merkle_root_with_arity(data.iter(), 2),
+                Blake2bTree::merkle_root(data.iter())
+            );
+            for i in 0..n {
+                let nary = Blake2bTree::merkle_proof_with_arity(data.iter(), i, 2).unwrap();
+                let binary = Blake2bTree::merkle_proof(data.iter(), i).unwrap();
+                let siblings: Vec<Hash> =
+                    nary.levels.iter().map(|level| level.siblings[0]).collect();
+                assert_eq!(siblings, binary.hashes);
+            }
+        }
+    }
+
+    #[test]
+    fn test_wide_trees_verify() {
+        for arity in [3, 4, 16] {
+            for n in [1, 2, 4, 5, 16, 17, 40] {
+                let data = leaves(n);
+                let root = Blake2bTree::merkle_root_with_arity(data.iter(), arity);
+                for i in 0..n {
+                    let proof =
+                        Blake2bTree::merkle_proof_with_arity(data.iter(), i, arity).unwrap();
+                    assert_eq!(proof.levels.len(), nary_proof_length(i, n, arity));
+                    assert!(proof.levels.len() <= nary_depth(n, arity));
+                    assert!(proof
+                        .levels
+                        .iter()
+                        .all(|level| (1..arity).contains(&level.siblings.len())));
+                    assert_eq!(
+                        Blake2bTree::verify_nary_proof(&root, &proof),
+                        Ok(()),
+                        "arity {arity}, leaf {i} of {n}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_wider_trees_are_shallower() {
+        let data = leaves(256);
+        let binary = Blake2bTree::merkle_proof_with_arity(data.iter(), 100, 2).unwrap();
+        let wide = Blake2bTree::merkle_proof_with_arity(data.iter(), 100, 16).unwrap();
+        assert_eq!(binary.levels.len(), 8);
+        assert_eq!(wide.levels.len(), 2);
+        assert_ne!(
+            Blake2bTree::merkle_root_with_arity(data.iter(), 2),
+            Blake2bTree::merkle_root_with_arity(data.iter(), 16)
+        );
+    }
+
+    #[test]
+    fn test_nary_proof_rejects_tampering() {
+        let data = leaves(20);
+        let root = Blake2bTree::merkle_root_with_arity(data.iter(), 4);
+        let mut proof = Blake2bTree::merkle_proof_with_arity(data.iter(), 6, 4).unwrap();
+
+        proof.levels[0].position = 3;
+        assert_eq!(
+            Blake2bTree::verify_nary_proof(&root, &proof),
+            Err(VerifyError::PositionMismatch { level: 0 })
+        );
+
+        proof.levels[0].position = 2;
+        proof.levels[1].siblings.swap(0, 1);
+        assert_eq!(
+            Blake2bTree::verify_nary_proof(&root, &proof),
+            Err(VerifyError::RootMismatch)
+        );
+
+        proof.arity = 1;
+        assert_eq!(
+            Blake2bTree::verify_nary_proof(&root, &proof),
+            Err(VerifyError::InvalidArity(1))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "arity must be at least 2")]
+    fn test_arity_below_two_panics() {
+        Blake2bTree::merkle_root_with_arity(leaves(3).iter(), 1);
+    }
+}
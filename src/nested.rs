@@ -0,0 +1,199 @@
+//! Trees whose leaves are the roots of sub-trees.
+//!
+//! A top tree takes each sub-tree [`Root`] as a leaf hash as is, with no leaf
+//! prefix or rehashing. When every sub-tree has the same power-of-two number
+//! of leaves, the top root is the root of the flat tree over all sub-leaves,
+//! so a sub-tree proof followed by the top-tree proof of its root is a plain
+//! [`MerkleProof`] from a sub-leaf to the top root.
+
+use alloc::vec::Vec;
+
+use blake2::Digest;
+
+use crate::{
+    proof_directions, MerkleProof, MerkleTree, MerkleTreeBuilder, ProofError, Root, VerifyError,
+    PLAIN, PREHASHED,
+};
+
+impl<D: Digest> MerkleTree<D> {
+    /// The root of the tree whose leaf hashes are `roots`.
+    pub fn merkle_root_of_roots<I>(roots: I) -> Root
+    where
+        I: Iterator<Item = Root>,
+    {
+        MerkleTreeBuilder::<Root, D>::with_options(roots, PREHASHED).root()
+    }
+
+    /// Builds the proof that the sub-tree root at `index` is a leaf of the
+    /// tree over `roots`, for [`compose_proofs`](Self::compose_proofs).
+    pub fn merkle_proof_of_roots<I>(roots: I, index: usize) -> Result<MerkleProof<Root>, ProofError>
+    where
+        I: Iterator<Item = Root>,
+    {
+        Self::merkle_proof_with(roots, index, &PREHASHED)
+    }
+
+    /// Joins `inner`, a proof within a sub-tree, and `outer`, the proof of
+    /// that sub-tree's root in the top tree, into one proof from the sub-leaf
+    /// to the top root. It verifies with
+    /// [`verify_proof`](Self::verify_proof) as the proof of leaf
+    /// `outer.leaf_index * inner.num_of_leaves + inner.leaf_index` of
+    /// `outer.num_of_leaves * inner.num_of_leaves`, and keeps any root
+    /// bundled with `outer`.
+    ///
+    /// Both proofs must pass the shape checks of `verify_proof`. Fails with
+    /// [`VerifyError::RootMismatch`] if `inner` does not hash to the root
+    /// `outer` proves, with [`VerifyError::UnevenSubtree`] if the sub-tree's
+    /// leaf count is not a power of two (and the top tree has more than one
+    /// leaf), since the sub-trees would then not line up with the levels of
+    /// a single tree, and with [`VerifyError::LeafCountOverflow`] if the
+    /// composed leaf count does not fit in a `usize`.
+    pub fn compose_proofs<T: AsRef<[u8]>>(
+        outer: &MerkleProof<Root>,
+        inner: MerkleProof<T>,
+    ) -> Result<MerkleProof<T>, VerifyError> {
+        outer.check_shape()?;
+        let inner_root = Self::path_root(&inner, &PLAIN, |leaf| Self::leaf_hash(&PLAIN, leaf))?;
+        if inner_root != outer.leaf_content.0 {
+            return Err(VerifyError::RootMismatch);
+        }
+        if outer.num_of_leaves > 1 && !inner.num_of_leaves.is_power_of_two() {
+            return Err(VerifyError::UnevenSubtree {
+                num_of_leaves: inner.num_of_leaves,
+            });
+        }
+
+        let num_of_leaves = outer
+            .num_of_leaves
+            .checked_mul(inner.num_of_leaves)
+            .ok_or(VerifyError::LeafCountOverflow)?;
+        let leaf_index = outer
+            .leaf_index
+            .checked_mul(inner.num_of_leaves)
+            .and_then(|first| first.checked_add(inner.leaf_index))
+            .ok_or(VerifyError::LeafCountOverflow)?;
+
+        let mut hashes = Vec::with_capacity(inner.hashes.len() + outer.hashes.len());
+        hashes.extend_from_slice(&inner.hashes);
+        hashes.extend_from_slice(&outer.hashes);
+        Ok(MerkleProof {
+            hashes,
+            num_of_leaves,
+            leaf_index,
+            leaf_content: inner.leaf_content,
+            path_bits: proof_directions(leaf_index, num_of_leaves),
+            root: outer.root.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Blake2bTree;
+    use blake2::Blake2b512;
+
+    fn sub_trees(groups: &[&[&'static str]]) -> Vec<MerkleTreeBuilder<&'static str, Blake2b512>> {
+        groups
+            .iter()
+            .map(|group| MerkleTreeBuilder::new(group.iter().copied()))
+            .collect()
+    }
+
+    #[test]
+    fn test_nested_two_by_two() {
+        let subs = sub_trees(&[&["a", "b"], &["c", "d"]]);
+        let roots: Vec<Root> = subs.iter().map(|sub| sub.root()).collect();
+        let top = Blake2bTree::merkle_root_of_roots(roots.iter().copied());
+        assert_eq!(
+            top.to_hex(),
+            Blake2bTree::merkle_root(["a", "b", "c", "d"].iter())
+        );
+
+        for (i, sub) in subs.iter().enumerate() {
+            let outer = Blake2bTree::merkle_proof_of_roots(roots.iter().copied(), i).unwrap();
+            assert_eq!(outer.leaf_content, roots[i]);
+            for j in 0..sub.leaf_count() {
+                let composed = Blake2bTree::compose_proofs(&outer, sub.proof(j).unwrap()).unwrap();
+                assert_eq!(
+                    (composed.leaf_index, composed.num_of_leaves),
+                    (2 * i + j, 4)
+                );
+                assert_eq!(Blake2bTree::verify_proof(top.to_hex(), &composed), Ok(()));
+                let flat = Blake2bTree::merkle_proof(["a", "b", "c", "d"].iter(), 2 * i + j);
+                assert_eq!(composed.hashes, flat.unwrap().hashes);
+            }
+        }
+
+        let outer = Blake2bTree::merkle_proof_of_roots(roots.iter().copied(), 0)
+            .unwrap()
+            .with_root(top.to_hex());
+        let composed = Blake2bTree::compose_proofs(&outer, subs[0].proof(1).unwrap()).unwrap();
+        assert_eq!(composed.verify(), Ok(()));
+    }
+
+    #[test]
+    fn test_compose_rejects_mismatched_or_uneven_subtrees() {
+        let subs = sub_trees(&[&["a", "b"], &["c", "d"]]);
+        let roots: Vec<Root> = subs.iter().map(|sub| sub.root()).collect();
+        let outer = Blake2bTree::merkle_proof_of_roots(roots.iter().copied(), 0).unwrap();
+        assert_eq!(
+            Blake2bTree::compose_proofs(&outer, subs[1].proof(0).unwrap()),
+            Err(VerifyError::RootMismatch)
+        );
+
+        let subs = sub_trees(&[&["a", "b", "c"], &["d", "e", "f"]]);
+        let roots: Vec<Root> = subs.iter().map(|sub| sub.root()).collect();
+        let outer = Blake2bTree::merkle_proof_of_roots(roots.iter().copied(), 1).unwrap();
+        assert_eq!(
+            Blake2bTree::compose_proofs(&outer, subs[1].proof(2).unwrap()),
+            Err(VerifyError::UnevenSubtree { num_of_leaves: 3 })
+        );
+
+        // An outer proof must itself be well-formed, whatever its index.
+        let mut outer = Blake2bTree::merkle_proof_of_roots(roots.iter().copied(), 0).unwrap();
+        let inner = subs[0].proof(1).unwrap();
+        outer.leaf_index = usize::MAX;
+        assert_eq!(
+            Blake2bTree::compose_proofs(&outer, inner.clone()),
+            Err(VerifyError::LeafIndexOutOfRange {
+                index: usize::MAX,
+                num_of_leaves: 2
+            })
+        );
+        outer.leaf_index = 1;
+        assert_eq!(
+            Blake2bTree::compose_proofs(&outer, inner),
+            Err(VerifyError::PositionMismatch { level: 0 })
+        );
+
+        // A top tree of one sub-tree adds no levels, so any sub-tree composes.
+        let outer = Blake2bTree::merkle_proof_of_roots(roots[..1].iter().copied(), 0).unwrap();
+        let composed = Blake2bTree::compose_proofs(&outer, subs[0].proof(2).unwrap()).unwrap();
+        assert_eq!(
+            Blake2bTree::verify_proof(roots[0].to_hex(), &composed),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_compose_rejects_overflowing_counts() {
+        const HALF: usize = usize::MAX / 2 + 1;
+        let subs = sub_trees(&[&["a", "b", "c", "d"]]);
+        let inner = subs[0].proof(3).unwrap();
+
+        // The last of 2^(bits - 1) + 1 sub-trees, over four leaves each.
+        let outer = MerkleProof {
+            hashes: vec![Blake2bTree::hash_leaf("others")],
+            num_of_leaves: HALF + 1,
+            leaf_index: HALF,
+            leaf_content: subs[0].root(),
+            path_bits: vec![true],
+            root: None,
+        };
+        assert_eq!(
+            Blake2bTree::compose_proofs(&outer, inner),
+            Err(VerifyError::LeafCountOverflow)
+        );
+    }
+}
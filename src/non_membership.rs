@@ -0,0 +1,258 @@
+//! Proofs that a key is absent from a sorted tree.
+//!
+//! When the leaves are sorted, a key that is not among them falls between two
+//! adjacent leaves, or before the first or after the last one. Inclusion
+//! proofs for those neighbours, plus the fact that their indices are
+//! consecutive, show that there is no room for the key in between.
+//!
+//! Leaves are compared as byte strings. The tree builder does not sort or
+//! check the leaves: they must already be in strictly increasing byte order,
+//! and a verifier must trust whoever built the tree to have ensured that.
+
+use blake2::Digest;
+
+use crate::{MerkleProof, MerkleTree, MerkleTreeBuilder, ProofError, VerifyError};
+
+/// Evidence that a key is not a leaf of a sorted tree.
+#[derive(Debug, Clone, PartialEq, Eq, core::hash::Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NonMembershipProof<T> {
+    /// The tree has no leaves at all.
+    EmptyTree,
+    /// The key sorts before the first leaf, proven here.
+    BeforeFirst(MerkleProof<T>),
+    /// The key sorts after the last leaf, proven here.
+    AfterLast(MerkleProof<T>),
+    /// The key sorts strictly between two adjacent leaves.
+    Between {
+        left: MerkleProof<T>,
+        right: MerkleProof<T>,
+    },
+}
+
+impl<D: Digest> MerkleTree<D> {
+    /// Proves that `key` is not one of `sorted_leaves`, which must be in
+    /// strictly increasing byte order.
+    pub fn non_membership_proof<I, L>(
+        sorted_leaves: I,
+        key: impl AsRef<[u8]>,
+    ) -> Result<NonMembershipProof<L>, ProofError>
+    where
+        I: Iterator<Item = L>,
+        L: AsRef<[u8]> + Clone,
+    {
+        MerkleTreeBuilder::<L, D>::new(sorted_leaves).non_membership_proof(key)
+    }
+
+    /// Checks that `proof` shows `key` is absent from the sorted tree with
+    /// `root`.
+    pub fn verify_non_membership<T: AsRef<[u8]>>(
+        root: impl AsRef<str>,
+        proof: &NonMembershipProof<T>,
+        key: impl AsRef<[u8]>,
+    ) -> Result<(), VerifyError> {
+        let (root, key) = (root.as_ref(), key.as_ref());
+        match proof {
+            NonMembershipProof::EmptyTree => {
+                if root == Self::empty_root() {
+                    Ok(())
+                } else {
+                    Err(VerifyError::RootMismatch)
+                }
+            }
+            NonMembershipProof::BeforeFirst(first) => {
+                bracketed(first.leaf_index == 0 && key < first.leaf_content.as_ref())?;
+                Self::verify_proof(root, first)
+            }
+            NonMembershipProof::AfterLast(last) => {
+                bracketed(
+                    last.leaf_index.checked_add(1) == Some(last.num_of_leaves)
+                        && last.leaf_content.as_ref() < key,
+                )?;
+                Self::verify_proof(root, last)
+            }
+            NonMembershipProof::Between { left, right } => {
+                bracketed(
+                    left.num_of_leaves == right.num_of_leaves
+                        && left.leaf_index.checked_add(1) == Some(right.leaf_index)
+                        && left.leaf_content.as_ref() < key
+                        && key < right.leaf_content.as_ref(),
+                )?;
+                Self::verify_proof(root, left)?;
+                Self::verify_proof(root, right)
+            }
+        }
+    }
+}
+
+impl<L: AsRef<[u8]> + Clone, D: Digest> MerkleTreeBuilder<L, D> {
+    /// Proves that `key` is not a leaf, assuming the leaves are in strictly
+    /// increasing byte order.
+    pub fn non_membership_proof(
+        &self,
+        key: impl AsRef<[u8]>,
+    ) -> Result<NonMembershipProof<L>, ProofError> {
+        let key = key.as_ref();
+        let index = self.leaves.partition_point(|leaf| leaf.as_ref() < key);
+        if self
+            .leaves
+            .get(index)
+            .is_some_and(|leaf| leaf.as_ref() == key)
+        {
+            return Err(ProofError::KeyPresent { index });
+        }
+
+        Ok(match index {
+            _ if self.leaves.is_empty() => NonMembershipProof::EmptyTree,
+            0 => NonMembershipProof::BeforeFirst(self.proof(0)?),
+            _ if index == self.leaves.len() => {
+                NonMembershipProof::AfterLast(self.proof(index - 1)?)
+            }
+            _ => NonMembershipProof::Between {
+                left: self.proof(index - 1)?,
+                right: self.proof(index)?,
+            },
+        })
+    }
+}
+
+fn bracketed(holds: bool) -> Result<(), VerifyError> {
+    if holds {
+        Ok(())
+    } else {
+        Err(VerifyError::KeyNotBracketed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Blake2bTree;
+
+    const KEYS: [&str; 5] = ["banana", "cherry", "fig", "grape", "kiwi"];
+
+    #[test]
+    fn test_absent_keys_verify() {
+        let root = Blake2bTree::merkle_root(KEYS.iter());
+        for key in ["apple", "coconut", "date", "guava", "lemon"] {
+            let proof = Blake2bTree::non_membership_proof(KEYS.iter(), key).unwrap();
+            assert_eq!(
+                Blake2bTree::verify_non_membership(&root, &proof, key),
+                Ok(()),
+                "{key}"
+            );
+        }
+
+        assert!(matches!(
+            Blake2bTree::non_membership_proof(KEYS.iter(), "apple").unwrap(),
+            NonMembershipProof::BeforeFirst(_)
+        ));
+        assert!(matches!(
+            Blake2bTree::non_membership_proof(KEYS.iter(), "lemon").unwrap(),
+            NonMembershipProof::AfterLast(_)
+        ));
+    }
+
+    #[test]
+    fn test_present_key_has_no_proof() {
+        assert_eq!(
+            Blake2bTree::non_membership_proof(KEYS.iter(), "fig").unwrap_err(),
+            ProofError::KeyPresent { index: 2 }
+        );
+    }
+
+    #[test]
+    fn test_proof_does_not_cover_other_keys() {
+        let root = Blake2bTree::merkle_root(KEYS.iter());
+        let proof = Blake2bTree::non_membership_proof(KEYS.iter(), "date").unwrap();
+        for key in ["banana", "cherry", "fig", "apple", "kiwi"] {
+            assert_eq!(
+                Blake2bTree::verify_non_membership(&root, &proof, key),
+                Err(VerifyError::KeyNotBracketed),
+                "{key}"
+            );
+        }
+
+        // Two present leaves that are not adjacent leave room for the key.
+        let NonMembershipProof::Between { left, .. } = &proof else {
+            panic!("expected a bracketing proof");
+        };
+        let gapped = NonMembershipProof::Between {
+            left: left.clone(),
+            right: Blake2bTree::merkle_proof(KEYS.iter(), 3).unwrap(),
+        };
+        assert_eq!(
+            Blake2bTree::verify_non_membership(&root, &gapped, "date"),
+            Err(VerifyError::KeyNotBracketed)
+        );
+
+        let other_root = Blake2bTree::merkle_root(KEYS[..4].iter());
+        assert_eq!(
+            Blake2bTree::verify_non_membership(&other_root, &proof, "date"),
+            Err(VerifyError::RootMismatch)
+        );
+    }
+
+    #[test]
+    fn test_empty_tree_excludes_everything() {
+        let root = Blake2bTree::merkle_root(std::iter::empty::<&str>());
+        let proof = Blake2bTree::non_membership_proof(std::iter::empty::<&str>(), "x").unwrap();
+        assert_eq!(proof, NonMembershipProof::EmptyTree);
+        assert!(Blake2bTree::verify_non_membership(&root, &proof, "x").is_ok());
+
+        let non_empty = Blake2bTree::merkle_root(KEYS.iter());
+        assert_eq!(
+            Blake2bTree::verify_non_membership(&non_empty, &proof, "x"),
+            Err(VerifyError::RootMismatch)
+        );
+    }
+
+    #[test]
+    fn test_index_at_usize_max_is_not_bracketed() {
+        let root = Blake2bTree::merkle_root(KEYS.iter());
+        let NonMembershipProof::AfterLast(mut last) =
+            Blake2bTree::non_membership_proof(KEYS.iter(), "lemon").unwrap()
+        else {
+            panic!("expected a proof of the last leaf");
+        };
+        last.leaf_index = usize::MAX;
+        last.num_of_leaves = usize::MAX;
+        let forged = NonMembershipProof::Between {
+            left: last.clone(),
+            right: last.clone(),
+        };
+        for proof in [NonMembershipProof::AfterLast(last), forged] {
+            assert_eq!(
+                Blake2bTree::verify_non_membership(&root, &proof, "lemon"),
+                Err(VerifyError::KeyNotBracketed)
+            );
+        }
+    }
+
+    #[test]
+    fn test_shrunk_leaf_count_is_not_last() {
+        // Cut the tree down to two leaves on paper, so that "cherry" would
+        // be the last and "fig" would seem to come after everything.
+        let root = Blake2bTree::merkle_root(KEYS.iter());
+        let mut cherry = Blake2bTree::merkle_proof(KEYS.iter(), 1).unwrap();
+        cherry.num_of_leaves = 2;
+        assert_eq!(
+            Blake2bTree::verify_non_membership(
+                &root,
+                &NonMembershipProof::AfterLast(cherry.clone()),
+                "fig"
+            ),
+            Err(VerifyError::InconsistentProofLength {
+                expected: 1,
+                actual: 3
+            })
+        );
+        cherry.path_bits.clear();
+        assert!(Blake2bTree::verify_non_membership(
+            &root,
+            &NonMembershipProof::AfterLast(cherry),
+            "fig"
+        )
+        .is_err());
+    }
+}
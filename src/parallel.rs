@@ -0,0 +1,109 @@
+//! Multi-threaded root computation, enabled by the `rayon` feature.
+
+use blake2::Digest;
+use rayon::prelude::*;
+
+use crate::{Hash, MerkleProof, MerkleTree, TreeOptions, VerifyError};
+
+impl<D: Digest> MerkleTree<D> {
+    /// Computes the same root as [`merkle_root`](Self::merkle_root), hashing
+    /// the leaves and then each level in parallel. Nodes within a level are
+    /// independent, so the output is byte-for-byte identical.
+    pub fn merkle_root_parallel<I, L>(leaves: I) -> String
+    where
+        I: Iterator<Item = L>,
+        L: AsRef<[u8]> + Sync,
+    {
+        let leaves: Vec<L> = leaves.collect();
+        let mut level: Vec<Hash> = leaves.par_iter().map(Self::hash_leaf).collect();
+
+        let options = TreeOptions::default();
+        while level.len() > 1 {
+            level = level
+                .par_chunks(2)
+                .map(|chunk| Self::hash_chunk(&options, chunk))
+                .collect();
+        }
+
+        level
+            .pop()
+            .map_or_else(Self::empty_root, |root| root.to_hex())
+    }
+
+    /// [`verify_proofs`](Self::verify_proofs) with the proofs checked in
+    /// parallel. Results are in the same order and identical to the serial
+    /// version.
+    pub fn verify_proofs_parallel<T: AsRef<[u8]> + Sync>(
+        root: impl AsRef<str>,
+        proofs: &[MerkleProof<T>],
+    ) -> Vec<Result<(), VerifyError>> {
+        let root = root.as_ref();
+        proofs
+            .par_iter()
+            .map(|proof| Self::verify_proof(root, proof))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use crate::{Blake2bTree, MerkleTree};
+
+    /// Deterministic pseudo-random leaves of varying length.
+    fn random_leaves(n: usize) -> Vec<Vec<u8>> {
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        (0..n)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state.to_le_bytes()[..(state % 8) as usize + 1].to_vec()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parallel_root_matches_serial() {
+        let data = random_leaves(100_000);
+        assert_eq!(
+            Blake2bTree::merkle_root_parallel(data.iter()),
+            Blake2bTree::merkle_root(data.iter())
+        );
+        assert_eq!(
+            MerkleTree::<Sha256>::merkle_root_parallel(data.iter()),
+            MerkleTree::<Sha256>::merkle_root(data.iter())
+        );
+    }
+
+    #[test]
+    fn test_parallel_root_small_trees() {
+        for n in 0..10 {
+            let data = random_leaves(n);
+            assert_eq!(
+                Blake2bTree::merkle_root_parallel(data.iter()),
+                Blake2bTree::merkle_root(data.iter()),
+                "{n} leaves"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parallel_verify_matches_serial() {
+        let data = random_leaves(1_000);
+        let tree = Blake2bTree::from_leaves(data.iter());
+        let mut proofs: Vec<_> = (0..data.len()).map(|i| tree.proof(i).unwrap()).collect();
+        for proof in proofs.iter_mut().step_by(7) {
+            proof.leaf_index = (proof.leaf_index + 1) % data.len();
+        }
+
+        let serial = Blake2bTree::verify_proofs(tree.root_hex(), &proofs);
+        assert_eq!(
+            Blake2bTree::verify_proofs_parallel(tree.root_hex(), &proofs),
+            serial
+        );
+        assert!(serial.iter().any(|result| result.is_err()));
+        assert!(serial.iter().any(|result| result.is_ok()));
+    }
+}
@@ -0,0 +1,104 @@
+//! Root computation that reports its progress, for callers that would
+//! otherwise appear hung while hashing tens of millions of leaves.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use blake2::Digest;
+
+use crate::{tree_depth, MerkleTree, PLAIN};
+
+/// Leaves hashed between two progress reports.
+const PROGRESS_INTERVAL: usize = 1 << 16;
+
+impl<D: Digest> MerkleTree<D> {
+    /// Computes the same root as [`merkle_root`](Self::merkle_root), calling
+    /// `progress` as it goes.
+    ///
+    /// While hashing leaves it reports `(leaves_processed, total)` every
+    /// 65536 leaves and once more when all are hashed, so the leaf pass ends
+    /// with `(total, total)`. While reducing it then reports
+    /// `(level, total_levels)` after each level above the leaves, from 1 up
+    /// to [`tree_depth`]. A tree of `n` leaves thus gets about
+    /// `n / 65536 + log2(n)` calls. [`merkle_root`](Self::merkle_root) is
+    /// untouched and pays nothing for this.
+    pub fn merkle_root_with_progress<I, L>(
+        leaves: I,
+        mut progress: impl FnMut(usize, usize),
+    ) -> String
+    where
+        I: ExactSizeIterator<Item = L>,
+        L: AsRef<[u8]>,
+    {
+        let total = leaves.len();
+        let mut level = Vec::with_capacity(total);
+        for leaf in leaves {
+            level.push(Self::hash_leaf(leaf));
+            if level.len() % PROGRESS_INTERVAL == 0 && level.len() != total {
+                progress(level.len(), total);
+            }
+        }
+        progress(level.len(), total);
+
+        let total_levels = tree_depth(level.len());
+        for height in 1..=total_levels {
+            level = Self::hash_level(&PLAIN, &level);
+            progress(height, total_levels);
+        }
+        match level.first() {
+            Some(root) => root.to_hex(),
+            None => Self::empty_root(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Blake2bTree;
+
+    #[test]
+    fn test_progress_reports_both_passes() {
+        let data = ["a", "b", "c", "d", "e"];
+        let mut calls = Vec::new();
+        let root = Blake2bTree::merkle_root_with_progress(data.iter(), |done, total| {
+            calls.push((done, total))
+        });
+        assert_eq!(root, Blake2bTree::merkle_root(data.iter()));
+        assert_eq!(calls, [(5, 5), (1, 3), (2, 3), (3, 3)]);
+
+        let mut calls = Vec::new();
+        let root =
+            Blake2bTree::merkle_root_with_progress(core::iter::empty::<&str>(), |done, total| {
+                calls.push((done, total))
+            });
+        assert_eq!(root, Blake2bTree::empty_root());
+        assert_eq!(calls, [(0, 0)]);
+    }
+
+    #[test]
+    fn test_progress_interval() {
+        let n = 3 * PROGRESS_INTERVAL + 5;
+        let leaves: Vec<[u8; 4]> = (0..n as u32).map(u32::to_le_bytes).collect();
+        let mut calls = Vec::new();
+        Blake2bTree::merkle_root_with_progress(leaves.iter(), |done, total| {
+            calls.push((done, total))
+        });
+
+        let depth = tree_depth(n);
+        assert_eq!(calls.len(), 4 + depth);
+        assert_eq!(
+            calls[..4],
+            [
+                (PROGRESS_INTERVAL, n),
+                (2 * PROGRESS_INTERVAL, n),
+                (3 * PROGRESS_INTERVAL, n),
+                (n, n)
+            ]
+        );
+        assert_eq!(
+            calls[4..],
+            (1..=depth).map(|level| (level, depth)).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! Proofs that a contiguous run of leaves belongs to a tree.
+//!
+//! The leaves in `[start, end)` share all their ancestors inside the range,
+//! so the verifier can rebuild those itself from the revealed leaf hashes.
+//! Only the siblings just outside the range are sent: at most one on the
+//! left and one on the right per level, so a range proof holds at most
+//! `2 * depth` hashes however long the range is.
+
+use alloc::vec::Vec;
+
+use blake2::Digest;
+
+use crate::{Hash, MerkleTree, MerkleTreeBuilder, ProofError, TreeOptions, VerifyError};
+
+/// The boundary siblings proving leaves `[start, end)` of a tree.
+#[derive(Debug, Clone, PartialEq, Eq, core::hash::Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RangeProof {
+    /// Siblings outside the range, bottom level first and the left one
+    /// before the right one within a level.
+    pub hashes: Vec<Hash>,
+    pub num_of_leaves: usize,
+}
+
+impl<D: Digest> MerkleTree<D> {
+    /// Proves that leaves `[start, end)` of `leaves` form a contiguous slice
+    /// of the tree. The verifier also needs the leaf hashes of the range,
+    /// from [`hash_leaf`](Self::hash_leaf).
+    pub fn range_proof<I, L>(leaves: I, start: usize, end: usize) -> Result<RangeProof, ProofError>
+    where
+        I: Iterator<Item = L>,
+        L: AsRef<[u8]>,
+    {
+        MerkleTreeBuilder::<L, D>::new(leaves).range_proof(start, end)
+    }
+
+    /// Checks that `leaf_hashes` are the hashes of leaves `[start, end)` of
+    /// the tree with `root`.
+    pub fn verify_range_proof(
+        root: impl AsRef<str>,
+        start: usize,
+        end: usize,
+        leaf_hashes: &[Hash],
+        proof: &RangeProof,
+    ) -> Result<(), VerifyError> {
+        Self::verify_range_proof_with(
+            root,
+            start,
+            end,
+            leaf_hashes,
+            proof,
+            &TreeOptions::default(),
+        )
+    }
+
+    /// Like [`verify_range_proof`](Self::verify_range_proof), for a tree built
+    /// with `options`.
+    pub fn verify_range_proof_with(
+        root: impl AsRef<str>,
+        start: usize,
+        end: usize,
+        leaf_hashes: &[Hash],
+        proof: &RangeProof,
+        options: &TreeOptions,
+    ) -> Result<(), VerifyError> {
+        if start >= end || leaf_hashes.len() != end - start {
+            return Err(VerifyError::MalformedRange);
+        }
+        if end > proof.num_of_leaves {
+            return Err(VerifyError::LeafIndexOutOfRange {
+                index: end - 1,
+                num_of_leaves: proof.num_of_leaves,
+            });
+        }
+        let expected = boundary_count(start, end, proof.num_of_leaves);
+        if proof.hashes.len() != expected {
+            return Err(VerifyError::InconsistentProofLength {
+                expected,
+                actual: proof.hashes.len(),
+            });
+        }
+
+        let mut siblings = proof.hashes.iter();
+        let mut known = leaf_hashes.to_vec();
+        let (mut lo, mut hi, mut len) = (start, end, proof.num_of_leaves);
+        while len > 1 {
+            if lo % 2 == 1 {
+                let sibling = siblings.next().expect("proof length checked above");
+                known.insert(0, *sibling);
+            }
+            if hi % 2 == 1 && hi < len {
+                let sibling = siblings.next().expect("proof length checked above");
+                known.push(*sibling);
+            }
+            known = known
+                .chunks(2)
+                .map(|chunk| Self::hash_chunk(options, chunk))
+                .collect();
+            (lo, hi, len) = (lo / 2, hi.div_ceil(2), len.div_ceil(2));
+        }
+
+        if root.as_ref() == known[0].to_hex() {
+            Ok(())
+        } else {
+            Err(VerifyError::RootMismatch)
+        }
+    }
+}
+
+impl<L: AsRef<[u8]>, D: Digest> MerkleTreeBuilder<L, D> {
+    /// Proves leaves `[start, end)` from the cached levels.
+    pub fn range_proof(&self, start: usize, end: usize) -> Result<RangeProof, ProofError> {
+        let len = self.leaves.len();
+        if len == 0 {
+            return Err(ProofError::EmptyTree);
+        }
+        if start >= end {
+            return Err(ProofError::EmptyRange { start, end });
+        }
+        if end > len {
+            return Err(ProofError::IndexOutOfRange {
+                index: end - 1,
+                len,
+            });
+        }
+
+        let mut hashes = Vec::new();
+        let (mut lo, mut hi) = (start, end);
+        for level in &self.levels[..self.levels.len() - 1] {
+            if lo % 2 == 1 {
+                hashes.push(level[lo - 1]);
+            }
+            if hi % 2 == 1 && hi < level.len() {
+                // A trailing odd node has no sibling to carry.
+                hashes.push(level[hi]);
+            }
+            (lo, hi) = (lo / 2, hi.div_ceil(2));
+        }
+
+        Ok(RangeProof {
+            hashes,
+            num_of_leaves: self.width(),
+        })
+    }
+}
+
+/// Number of boundary hashes a range proof for `[start, end)` must carry.
+fn boundary_count(start: usize, end: usize, num_of_leaves: usize) -> usize {
+    let (mut lo, mut hi, mut len) = (start, end, num_of_leaves);
+    let mut count = 0;
+    while len > 1 {
+        count += usize::from(lo % 2 == 1) + usize::from(hi % 2 == 1 && hi < len);
+        (lo, hi, len) = (lo / 2, hi.div_ceil(2), len.div_ceil(2));
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tree_depth, Blake2bTree};
+
+    fn leaves(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("leaf-{i}")).collect()
+    }
+
+    fn leaf_hashes(data: &[String], start: usize, end: usize) -> Vec<Hash> {
+        data[start..end]
+            .iter()
+            .map(Blake2bTree::hash_leaf)
+            .collect()
+    }
+
+    #[test]
+    fn test_range_spanning_subtree_boundary() {
+        // [3, 6) of 8 leaves crosses the boundary between the two 4-leaf
+        // subtrees: it needs leaf 2, then the parents of leaves 0-1 and 6-7,
+        // and both subtree roots are rebuilt from those.
+        let data = leaves(8);
+        let root = Blake2bTree::merkle_root(data.iter());
+        let tree = MerkleTreeBuilder::<_, blake2::Blake2b512>::new(data.iter());
+        let proof = Blake2bTree::range_proof(data.iter(), 3, 6).unwrap();
+        let levels = tree.levels();
+        assert_eq!(proof.hashes, [levels[0][2], levels[1][0], levels[1][3]]);
+        assert_eq!(
+            Blake2bTree::verify_range_proof(&root, 3, 6, &leaf_hashes(&data, 3, 6), &proof),
+            Ok(())
+        );
+
+        let mut swapped = leaf_hashes(&data, 3, 6);
+        swapped.swap(0, 1);
+        assert_eq!(
+            Blake2bTree::verify_range_proof(&root, 3, 6, &swapped, &proof),
+            Err(VerifyError::RootMismatch)
+        );
+        // The same hashes do not prove a shifted range.
+        assert!(
+            Blake2bTree::verify_range_proof(&root, 2, 5, &leaf_hashes(&data, 3, 6), &proof)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_every_range_verifies() {
+        for n in [1, 2, 3, 5, 8, 13] {
+            let data = leaves(n);
+            let tree = MerkleTreeBuilder::<_, blake2::Blake2b512>::new(data.iter());
+            for start in 0..n {
+                for end in start + 1..=n {
+                    let proof = tree.range_proof(start, end).unwrap();
+                    assert!(proof.hashes.len() <= 2 * tree_depth(n));
+                    assert_eq!(
+                        Blake2bTree::verify_range_proof(
+                            tree.root_hex(),
+                            start,
+                            end,
+                            &leaf_hashes(&data, start, end),
+                            &proof
+                        ),
+                        Ok(()),
+                        "n = {n}, [{start}, {end})"
+                    );
+                }
+            }
+            // A single-leaf range is an ordinary proof.
+            for i in 0..n {
+                assert_eq!(
+                    tree.range_proof(i, i + 1).unwrap().hashes,
+                    tree.proof(i).unwrap().hashes
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_range_errors() {
+        let data = leaves(5);
+        assert_eq!(
+            Blake2bTree::range_proof(data.iter(), 2, 2).unwrap_err(),
+            ProofError::EmptyRange { start: 2, end: 2 }
+        );
+        assert_eq!(
+            Blake2bTree::range_proof(data.iter(), 3, 6).unwrap_err(),
+            ProofError::IndexOutOfRange { index: 5, len: 5 }
+        );
+        assert_eq!(
+            Blake2bTree::range_proof(std::iter::empty::<&str>(), 0, 1).unwrap_err(),
+            ProofError::EmptyTree
+        );
+
+        let root = Blake2bTree::merkle_root(data.iter());
+        let mut proof = Blake2bTree::range_proof(data.iter(), 1, 3).unwrap();
+        let hashes = leaf_hashes(&data, 1, 3);
+        assert_eq!(
+            Blake2bTree::verify_range_proof(&root, 1, 4, &hashes, &proof),
+            Err(VerifyError::MalformedRange)
+        );
+        proof.hashes.pop();
+        assert!(matches!(
+            Blake2bTree::verify_range_proof(&root, 1, 3, &hashes, &proof),
+            Err(VerifyError::InconsistentProofLength { .. })
+        ));
+    }
+}
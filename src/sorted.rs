@@ -0,0 +1,112 @@
+//! Proofs for trees built with [`TreeOptions::sorted_pairs`].
+//!
+//! Sorting each node's children makes the root independent of which side
+//! every sibling is on, so such a proof needs neither the leaf index nor the
+//! leaf count: a [`SortedProof`] is just the sibling hashes and the leaf.
+//! Like [`SteppedProof`](crate::SteppedProof), it proves membership but not
+//! position.
+
+use alloc::vec::Vec;
+
+use blake2::Digest;
+
+use crate::{Hash, MerkleProof, MerkleTree, TreeOptions, VerifyError};
+
+/// An inclusion proof in a sorted-pairs tree, sibling hashes bottom level
+/// first.
+#[derive(Debug, Clone, PartialEq, Eq, core::hash::Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SortedProof<T> {
+    pub hashes: Vec<Hash>,
+    pub leaf_content: T,
+}
+
+impl<T> MerkleProof<T> {
+    /// Drops the leaf index and count, which a sorted-pairs tree does not
+    /// need to place the siblings.
+    ///
+    /// Only valid for a proof from a tree built with
+    /// [`TreeOptions::sorted_pairs`]: a positional proof converted this way
+    /// will not verify with [`MerkleTree::verify_sorted`].
+    pub fn into_sorted(self) -> SortedProof<T> {
+        SortedProof {
+            hashes: self.hashes,
+            leaf_content: self.leaf_content,
+        }
+    }
+}
+
+impl<D: Digest> MerkleTree<D> {
+    /// Checks that folding the siblings of `proof` over its leaf hash, each
+    /// pair in sorted order, gives `root`.
+    pub fn verify_sorted<T: AsRef<[u8]>>(
+        root: impl AsRef<str>,
+        proof: &SortedProof<T>,
+    ) -> Result<(), VerifyError> {
+        let options = TreeOptions {
+            sorted_pairs: true,
+            ..TreeOptions::default()
+        };
+        let leaf = Self::hash_leaf(&proof.leaf_content);
+        let computed = proof.hashes.iter().fold(leaf, |hash, sibling| {
+            Self::hash_pair(&options, &hash, sibling)
+        });
+
+        if root.as_ref() == computed.to_hex() {
+            Ok(())
+        } else {
+            Err(VerifyError::RootMismatch)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Blake2bTree, MerkleTreeBuilder};
+
+    #[test]
+    fn test_sorted_proofs_verify() {
+        let data = ["a", "b", "c", "d", "e", "f", "g"];
+        let sorted = TreeOptions {
+            sorted_pairs: true,
+            ..TreeOptions::default()
+        };
+        let tree = MerkleTreeBuilder::<_, blake2::Blake2b512>::with_options(data.iter(), sorted);
+        let root = tree.root_hex();
+
+        for proof in tree.all_proofs() {
+            let sorted = proof.into_sorted();
+            assert!(Blake2bTree::verify_sorted(&root, &sorted).is_ok());
+
+            let mut reversed = sorted.clone();
+            reversed.hashes.reverse();
+            if sorted.hashes.len() > 1 {
+                assert_eq!(
+                    Blake2bTree::verify_sorted(&root, &reversed),
+                    Err(VerifyError::RootMismatch)
+                );
+            }
+
+            let forged = SortedProof {
+                leaf_content: &"x",
+                ..sorted
+            };
+            assert_eq!(
+                Blake2bTree::verify_sorted(&root, &forged),
+                Err(VerifyError::RootMismatch)
+            );
+        }
+    }
+
+    #[test]
+    fn test_positional_proof_does_not_convert() {
+        let data = ["a", "b", "c", "d", "e"];
+        let root = Blake2bTree::merkle_root(data.iter());
+        let proof = Blake2bTree::merkle_proof(data.iter(), 2).unwrap();
+        assert_eq!(
+            Blake2bTree::verify_sorted(root, &proof.into_sorted()),
+            Err(VerifyError::RootMismatch)
+        );
+    }
+}
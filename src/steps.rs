@@ -0,0 +1,252 @@
+//! Proofs that spell out the side of every sibling.
+//!
+//! A [`MerkleProof`] leaves the side of each sibling hash implied by
+//! `leaf_index` and `num_of_leaves`. A [`SteppedProof`] records it next to
+//! the hash instead, so verifying is a plain fold over the steps.
+//!
+//! The steps show that the leaf is in the tree but not where: they carry no
+//! index or leaf count, so a verifier that cares about the position must
+//! check the sides itself (see [`proof_directions`]).
+//!
+//! Converting a [`MerkleProof`] derives the sides from its claimed
+//! `num_of_leaves`. [`MerkleTreeBuilder::stepped_proof`] takes them from the
+//! tree itself instead, so nothing the verifier reads depends on a leaf count
+//! that could be tampered with, and also covers lone nodes that the tree's
+//! odd-node strategy hashes.
+
+use alloc::vec::Vec;
+
+use blake2::Digest;
+
+use crate::{
+    proof_directions, Hash, MerkleProof, MerkleTree, MerkleTreeBuilder, OddNodeStrategy,
+    ProofError, TreeOptions, VerifyError,
+};
+
+/// One sibling on the path from a leaf to the root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, core::hash::Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProofStep {
+    pub hash: Hash,
+    /// Whether `hash` is the left child, so the running hash is the right.
+    pub is_left: bool,
+}
+
+/// An inclusion proof as explicit steps, bottom level first.
+#[derive(Debug, Clone, PartialEq, Eq, core::hash::Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SteppedProof<T> {
+    pub steps: Vec<ProofStep>,
+    pub leaf_content: T,
+}
+
+impl<T> From<MerkleProof<T>> for SteppedProof<T> {
+    /// Pairs each sibling hash with the side implied by the proof's index and
+    /// leaf count. A proof with the wrong number of hashes for its shape
+    /// gives steps that will not verify.
+    fn from(proof: MerkleProof<T>) -> Self {
+        let directions = proof_directions(proof.leaf_index, proof.num_of_leaves);
+        SteppedProof {
+            steps: proof
+                .hashes
+                .into_iter()
+                .zip(directions)
+                .map(|(hash, is_left)| ProofStep { hash, is_left })
+                .collect(),
+            leaf_content: proof.leaf_content,
+        }
+    }
+}
+
+impl<L: AsRef<[u8]>, D: Digest> MerkleTreeBuilder<L, D> {
+    /// Builds the proof for the leaf at `leaf_index` as steps, with each
+    /// side read from the tree's own shape when the proof is generated.
+    ///
+    /// A lone node that [`TreeOptions::odd_nodes`] pairs with itself or with
+    /// the zero hash gets a step for that partner, so the steps verify with
+    /// [`verify_stepped_proof_with`](MerkleTree::verify_stepped_proof_with)
+    /// under every strategy.
+    pub fn stepped_proof(&self, leaf_index: usize) -> Result<SteppedProof<L>, ProofError>
+    where
+        L: Clone,
+    {
+        self.check_leaf_index(leaf_index)?;
+
+        let mut steps = Vec::new();
+        let mut index = leaf_index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let partner = match level.get(index ^ 1) {
+                Some(sibling) => Some(*sibling),
+                None => match self.options.odd_nodes {
+                    OddNodeStrategy::Promote => None,
+                    OddNodeStrategy::Duplicate => Some(level[index]),
+                    OddNodeStrategy::PadZero => Some(MerkleTree::<D>::zero_hash()),
+                },
+            };
+            if let Some(hash) = partner {
+                steps.push(ProofStep {
+                    hash,
+                    is_left: index % 2 == 1,
+                });
+            }
+            index /= 2;
+        }
+
+        Ok(SteppedProof {
+            steps,
+            leaf_content: self.leaves[leaf_index].clone(),
+        })
+    }
+}
+
+impl<D: Digest> MerkleTree<D> {
+    /// Checks that folding the steps of `proof` over its leaf hash gives
+    /// `root`.
+    pub fn verify_stepped_proof<T: AsRef<[u8]>>(
+        root: impl AsRef<str>,
+        proof: &SteppedProof<T>,
+    ) -> Result<(), VerifyError> {
+        Self::verify_stepped_proof_with(root, proof, &TreeOptions::default())
+    }
+
+    /// Like [`verify_stepped_proof`](Self::verify_stepped_proof), for a tree
+    /// built with `options`.
+    pub fn verify_stepped_proof_with<T: AsRef<[u8]>>(
+        root: impl AsRef<str>,
+        proof: &SteppedProof<T>,
+        options: &TreeOptions,
+    ) -> Result<(), VerifyError> {
+        let Some(leaf) = Self::leaf_hash(options, &proof.leaf_content) else {
+            return Err(VerifyError::RootMismatch);
+        };
+        let computed = proof.steps.iter().fold(leaf, |hash, step| match step {
+            ProofStep {
+                hash: sibling,
+                is_left: true,
+            } => Self::hash_pair(options, sibling, &hash),
+            ProofStep {
+                hash: sibling,
+                is_left: false,
+            } => Self::hash_pair(options, &hash, sibling),
+        });
+
+        if root.as_ref() == computed.to_hex() {
+            Ok(())
+        } else {
+            Err(VerifyError::RootMismatch)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Blake2bTree, MerkleTreeBuilder};
+
+    #[test]
+    fn test_steps_verify_like_the_proof() {
+        for n in [1, 2, 5, 8, 13] {
+            let data: Vec<String> = (0..n).map(|i| format!("leaf-{i}")).collect();
+            let root = Blake2bTree::merkle_root(data.iter());
+            for i in 0..n {
+                let proof = Blake2bTree::merkle_proof(data.iter(), i).unwrap();
+                let stepped = SteppedProof::from(proof.clone());
+                assert_eq!(stepped.steps.len(), proof.hashes.len());
+                assert_eq!(
+                    Blake2bTree::verify_stepped_proof(&root, &stepped),
+                    Blake2bTree::verify_proof(&root, &proof)
+                );
+
+                let mut forged = proof.clone();
+                forged.leaf_content = &data[(i + 1) % n];
+                if n > 1 {
+                    assert_eq!(
+                        Blake2bTree::verify_stepped_proof(&root, &SteppedProof::from(forged)),
+                        Err(VerifyError::RootMismatch)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_tree_steps_ignore_leaf_count() {
+        let data = ["a", "b", "c", "d", "e"];
+        let tree = MerkleTreeBuilder::<_, blake2::Blake2b512>::new(data.into_iter());
+        let root = tree.root_hex();
+        for i in 0..data.len() {
+            let stepped = tree.stepped_proof(i).unwrap();
+            assert_eq!(stepped, SteppedProof::from(tree.proof(i).unwrap()));
+            assert!(Blake2bTree::verify_stepped_proof(&root, &stepped).is_ok());
+        }
+
+        // A wrong leaf count misplaces the siblings of the index-based proof,
+        // but the steps were fixed when they were generated.
+        let mut proof = tree.proof(4).unwrap();
+        proof.num_of_leaves = 6;
+        assert!(Blake2bTree::verify_proof(&root, &proof).is_err());
+        assert!(Blake2bTree::verify_stepped_proof(&root, &tree.stepped_proof(4).unwrap()).is_ok());
+
+        assert_eq!(
+            tree.stepped_proof(5),
+            Err(ProofError::IndexOutOfRange { index: 5, len: 5 })
+        );
+    }
+
+    #[test]
+    fn test_tree_steps_cover_lone_nodes() {
+        let data = ["a", "b", "c", "d", "e"];
+        for odd_nodes in [
+            OddNodeStrategy::Promote,
+            OddNodeStrategy::Duplicate,
+            OddNodeStrategy::PadZero,
+        ] {
+            let options = TreeOptions {
+                odd_nodes,
+                ..TreeOptions::default()
+            };
+            let tree = MerkleTreeBuilder::<_, blake2::Blake2b512>::with_options(
+                data.into_iter(),
+                options.clone(),
+            );
+            for i in 0..data.len() {
+                let stepped = tree.stepped_proof(i).unwrap();
+                assert!(
+                    Blake2bTree::verify_stepped_proof_with(tree.root_hex(), &stepped, &options)
+                        .is_ok(),
+                    "{odd_nodes:?}, leaf {i}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_flipped_side_is_rejected() {
+        let data = ["a", "b", "c", "d", "e"];
+        let root = Blake2bTree::merkle_root(data.iter());
+        let mut stepped = SteppedProof::from(Blake2bTree::merkle_proof(data.iter(), 2).unwrap());
+        assert!(Blake2bTree::verify_stepped_proof(&root, &stepped).is_ok());
+        stepped.steps[0].is_left = !stepped.steps[0].is_left;
+        assert_eq!(
+            Blake2bTree::verify_stepped_proof(&root, &stepped),
+            Err(VerifyError::RootMismatch)
+        );
+    }
+
+    #[test]
+    fn test_sorted_pairs_ignore_sides() {
+        let data = ["a", "b", "c", "d", "e"];
+        let sorted = TreeOptions {
+            sorted_pairs: true,
+            ..TreeOptions::default()
+        };
+        let tree =
+            MerkleTreeBuilder::<_, blake2::Blake2b512>::with_options(data.iter(), sorted.clone());
+        let mut stepped = SteppedProof::from(tree.proof(3).unwrap());
+        stepped
+            .steps
+            .iter_mut()
+            .for_each(|step| step.is_left = !step.is_left);
+        assert!(Blake2bTree::verify_stepped_proof_with(tree.root_hex(), &stepped, &sorted).is_ok());
+    }
+}
@@ -0,0 +1,350 @@
+//! Root computation that consumes leaves one at a time.
+//!
+//! Only the roots of the perfect subtrees seen so far are kept, one per set
+//! bit of the leaf count, so memory stays O(log n) however long the stream.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use blake2::{Blake2b512, Digest};
+
+use crate::{Hash, MerkleTree, Root};
+
+/// Roots of the perfect subtrees covering the leaves pushed so far, tallest
+/// (leftmost) first, each with its height.
+struct PendingSubtrees {
+    stack: Vec<(usize, Hash)>,
+}
+
+impl PendingSubtrees {
+    fn new() -> Self {
+        PendingSubtrees { stack: Vec::new() }
+    }
+
+    /// Adds a leaf hash, merging equal-height subtrees like a binary counter.
+    fn push<D: Digest>(&mut self, leaf: Hash) {
+        let mut node = (0, leaf);
+        while let Some(&(height, left)) = self.stack.last() {
+            if height != node.0 {
+                break;
+            }
+            self.stack.pop();
+            node = (height + 1, MerkleTree::<D>::hash_node(&left, &node.1));
+        }
+        self.stack.push(node);
+    }
+
+    /// Root of the tree over everything pushed so far, or `None` if nothing
+    /// was pushed.
+    fn root<D: Digest>(&self) -> Option<Hash> {
+        bag_subtrees::<D>(&self.stack)
+    }
+}
+
+/// Combines the roots of perfect subtrees, tallest (leftmost) first and
+/// tagged with their heights, into the root of the tree they cover.
+///
+/// The batch construction carries a trailing odd node up unchanged, so each
+/// shorter subtree rises untouched until it meets its left neighbour and the
+/// roots fold together from the right.
+pub(crate) fn bag_subtrees<D: Digest>(subtrees: &[(usize, Hash)]) -> Option<Hash> {
+    subtrees
+        .iter()
+        .rev()
+        .map(|&(_, root)| root)
+        .reduce(|acc, left| MerkleTree::<D>::hash_node(&left, &acc))
+}
+
+/// A running Merkle root over a stream that never ends: leaves are pushed
+/// one at a time and [`root`](Self::root) is available after every push.
+///
+/// After `k` pushes the root equals [`MerkleTree::merkle_root`] over those
+/// `k` leaves. Pushing is amortized O(1) hashes and a root query costs
+/// O(log k); memory is O(log k).
+pub struct Accumulator<D = Blake2b512> {
+    pending: PendingSubtrees,
+    leaf_count: usize,
+    _digest: PhantomData<D>,
+}
+
+impl<D: Digest> Accumulator<D> {
+    /// An accumulator with no leaves, whose root is the empty root.
+    pub fn new() -> Self {
+        Accumulator {
+            pending: PendingSubtrees::new(),
+            leaf_count: 0,
+            _digest: PhantomData,
+        }
+    }
+
+    /// Adds the next leaf.
+    pub fn push(&mut self, leaf: impl AsRef<[u8]>) {
+        self.pending.push::<D>(MerkleTree::<D>::hash_leaf(leaf));
+        self.leaf_count += 1;
+    }
+
+    /// Root over every leaf pushed so far.
+    pub fn root(&self) -> Root {
+        Root(
+            self.pending
+                .root::<D>()
+                .unwrap_or_else(|| Hash::from_digest(D::new())),
+        )
+    }
+
+    /// Number of leaves pushed so far.
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_count
+    }
+}
+
+impl<D: Digest> Default for Accumulator<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D: Digest> MerkleTree<D> {
+    /// Computes the same root as [`merkle_root`](Self::merkle_root) without
+    /// buffering the leaves: each one is hashed as it is pulled from the
+    /// iterator and then dropped. Peak memory is O(log n).
+    pub fn merkle_root_streaming<I, L>(leaves: I) -> String
+    where
+        I: Iterator<Item = L>,
+        L: AsRef<[u8]>,
+    {
+        let mut pending = PendingSubtrees::new();
+        for leaf in leaves {
+            pending.push::<D>(Self::hash_leaf(leaf));
+        }
+        pending
+            .root::<D>()
+            .map_or_else(Self::empty_root, |root| root.to_hex())
+    }
+
+    /// Hex-encoded roots of the perfect subtrees covering `leaves`, tallest
+    /// (leftmost) first: one per set bit of the leaf count, as the peaks of
+    /// a Merkle Mountain Range. Folding them from the right with the node
+    /// hash gives [`merkle_root`](Self::merkle_root).
+    /// Leaves are consumed as they are pulled, like
+    /// [`merkle_root_streaming`](Self::merkle_root_streaming).
+    pub fn peaks<I, L>(leaves: I) -> Vec<String>
+    where
+        I: Iterator<Item = L>,
+        L: AsRef<[u8]>,
+    {
+        let mut pending = PendingSubtrees::new();
+        for leaf in leaves {
+            pending.push::<D>(Self::hash_leaf(leaf));
+        }
+        pending
+            .stack
+            .iter()
+            .map(|(_, root)| root.to_hex())
+            .collect()
+    }
+
+    /// Computes the same root as [`merkle_root`](Self::merkle_root) over the
+    /// bytes each reader yields, one leaf per reader. Every leaf is streamed
+    /// through the hasher a chunk at a time, so no leaf is ever held whole;
+    /// peak memory is O(log n) plus one read buffer.
+    #[cfg(feature = "std")]
+    pub fn merkle_root_from_readers<I, R>(leaves: I) -> std::io::Result<String>
+    where
+        I: Iterator<Item = R>,
+        R: std::io::Read,
+    {
+        let mut pending = PendingSubtrees::new();
+        let mut buf = [0u8; 8 * 1024];
+        for mut leaf in leaves {
+            let mut hasher = Self::new_hasher(&crate::PLAIN);
+            hasher.update([crate::LEAF_PREFIX]);
+            loop {
+                match leaf.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => hasher.update(&buf[..n]),
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            pending.push::<D>(Hash::from_digest(hasher));
+        }
+        Ok(pending
+            .root::<D>()
+            .map_or_else(Self::empty_root, |root| root.to_hex()))
+    }
+
+    /// Computes the root over the lines of `reader`, one leaf per line,
+    /// reading lazily like [`merkle_root_streaming`](Self::merkle_root_streaming).
+    ///
+    /// Lines end at `\n`, with a preceding `\r` also stripped, and the last
+    /// line counts whether or not it ends in a newline. A newline at the very
+    /// end does not start another line. Empty lines become empty leaves only
+    /// if `include_empty_lines` is set. Lines are hashed as raw bytes and need
+    /// not be UTF-8.
+    #[cfg(feature = "std")]
+    pub fn merkle_root_from_reader(
+        mut reader: impl std::io::BufRead,
+        include_empty_lines: bool,
+    ) -> std::io::Result<String> {
+        let mut pending = PendingSubtrees::new();
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            let mut content = line.strip_suffix(b"\n").unwrap_or(&line);
+            content = content.strip_suffix(b"\r").unwrap_or(content);
+            if include_empty_lines || !content.is_empty() {
+                pending.push::<D>(Self::hash_leaf(content));
+            }
+        }
+        Ok(pending
+            .root::<D>()
+            .map_or_else(Self::empty_root, |root| root.to_hex()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::Blake2bTree;
+
+    #[test]
+    fn test_streaming_matches_batch() {
+        let data: Vec<String> = (0..10_001).map(|i| format!("leaf-{i}")).collect();
+        assert_eq!(
+            Blake2bTree::merkle_root_streaming(data.iter()),
+            Blake2bTree::merkle_root(data.iter())
+        );
+    }
+
+    #[test]
+    fn test_streaming_small_trees() {
+        for n in 0..40 {
+            let data: Vec<String> = (0..n).map(|i| format!("leaf-{i}")).collect();
+            assert_eq!(
+                MerkleTree::<Sha256>::merkle_root_streaming(data.iter()),
+                MerkleTree::<Sha256>::merkle_root(data.iter()),
+                "{n} leaves"
+            );
+        }
+    }
+
+    #[test]
+    fn test_streaming_owned_leaves() {
+        // Each leaf is produced, hashed and dropped without being collected.
+        let root =
+            Blake2bTree::merkle_root_streaming((0..100u32).map(|i| i.to_le_bytes().to_vec()));
+        assert_eq!(
+            root,
+            Blake2bTree::merkle_root((0..100u32).map(|i| i.to_le_bytes()))
+        );
+    }
+
+    #[test]
+    fn test_accumulator_roots_every_prefix() {
+        let data: Vec<String> = (0..70).map(|i| format!("leaf-{i}")).collect();
+        let mut accumulator = Accumulator::<Sha256>::new();
+        assert_eq!(
+            accumulator.root().to_hex(),
+            MerkleTree::<Sha256>::empty_root()
+        );
+        for k in 1..=data.len() {
+            accumulator.push(&data[k - 1]);
+            assert_eq!(accumulator.leaf_count(), k);
+            assert_eq!(
+                accumulator.root().to_hex(),
+                MerkleTree::<Sha256>::merkle_root(data[..k].iter()),
+                "after {k} pushes"
+            );
+        }
+    }
+
+    #[test]
+    fn test_peaks() {
+        let data: Vec<String> = (0..7).map(|i| format!("leaf-{i}")).collect();
+        let peaks = Blake2bTree::peaks(data.iter());
+        assert_eq!(
+            peaks,
+            [
+                Blake2bTree::merkle_root(data[..4].iter()),
+                Blake2bTree::merkle_root(data[4..6].iter()),
+                Blake2bTree::merkle_root(data[6..].iter()),
+            ]
+        );
+
+        let bagged = peaks
+            .iter()
+            .map(|peak| Hash::from_slice(&hex::decode(peak).unwrap()).unwrap())
+            .rev()
+            .reduce(|acc, left| Blake2bTree::hash_node(&left, &acc))
+            .unwrap();
+        assert_eq!(bagged.to_hex(), Blake2bTree::merkle_root(data.iter()));
+
+        assert_eq!(Blake2bTree::peaks(data[..4].iter()).len(), 1);
+        assert!(Blake2bTree::peaks(std::iter::empty::<&str>()).is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_root_from_reader() {
+        let root = Blake2bTree::merkle_root(["alpha", "beta", "gamma"].iter());
+        for text in [
+            "alpha\nbeta\ngamma",
+            "alpha\nbeta\ngamma\n",
+            "alpha\r\nbeta\r\ngamma\r\n",
+            "alpha\n\nbeta\ngamma\n\n",
+        ] {
+            assert_eq!(
+                Blake2bTree::merkle_root_from_reader(text.as_bytes(), false).unwrap(),
+                root,
+                "{text:?}"
+            );
+        }
+
+        let with_empty = Blake2bTree::merkle_root(["alpha", "", "beta"].iter());
+        assert_eq!(
+            Blake2bTree::merkle_root_from_reader("alpha\n\nbeta\n".as_bytes(), true).unwrap(),
+            with_empty
+        );
+        assert_eq!(
+            Blake2bTree::merkle_root_from_reader("".as_bytes(), true).unwrap(),
+            Blake2bTree::empty_root()
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_root_from_readers_streams_wide_leaves() {
+        let wide: Vec<u8> = (0..5 << 20).map(|i| (i % 251) as u8).collect();
+        let leaves = [b"small".to_vec(), wide, Vec::new()];
+        let root = Blake2bTree::merkle_root(leaves.iter());
+        assert_eq!(
+            Blake2bTree::merkle_root_from_readers(leaves.iter().map(std::io::Cursor::new)).unwrap(),
+            root
+        );
+        assert_eq!(
+            Blake2bTree::merkle_root_from_readers(std::iter::empty::<&[u8]>()).unwrap(),
+            Blake2bTree::empty_root()
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_root_from_reader_propagates_errors() {
+        struct Failing;
+        impl std::io::Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("disk on fire"))
+            }
+        }
+        let err = Blake2bTree::merkle_root_from_reader(std::io::BufReader::new(Failing), false)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "disk on fire");
+    }
+}
@@ -0,0 +1,317 @@
+//! Trees over leaves of any type, serialized by a caller-supplied function.
+//!
+//! The `AsRef<[u8]>` methods hash a leaf's bytes directly. These variants
+//! take a `to_bytes` closure instead, so a tree can be built over structured
+//! values while proofs carry the typed value itself as `leaf_content`. The
+//! verifier must serialize with the same function to reproduce the leaf hash.
+//! The `_indexed` variants also pass the closure each leaf's index, e.g. to
+//! derive a per-leaf blinding factor for a commitment.
+//!
+//! Key-value entries have a fixed serialization, `len(key) as u64 LE || key
+//! || value`, so the key boundary is committed and a proof cannot move bytes
+//! between key and value.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use blake2::Digest;
+
+use crate::{
+    proof_directions, MerkleProof, MerkleTree, MerkleTreeBuilder, ProofError, TreeOptions,
+    VerifyError,
+};
+
+impl<D: Digest> MerkleTree<D> {
+    /// Computes the hex-encoded root over `leaves`, each serialized with
+    /// `to_bytes` and then hashed as `H(0x00 || bytes)`.
+    pub fn merkle_root_by<I, T, B>(leaves: I, to_bytes: impl Fn(&T) -> B) -> String
+    where
+        I: Iterator<Item = T>,
+        B: AsRef<[u8]>,
+    {
+        Self::merkle_root(leaves.map(|leaf| to_bytes(&leaf)))
+    }
+
+    /// Builds the inclusion proof for the leaf at `leaf_index`, serializing
+    /// every leaf with `to_bytes`. The proof holds the leaf itself, not its
+    /// bytes.
+    pub fn merkle_proof_by<I, T, B>(
+        leaves: I,
+        leaf_index: usize,
+        to_bytes: impl Fn(&T) -> B,
+    ) -> Result<MerkleProof<T>, ProofError>
+    where
+        I: Iterator<Item = T>,
+        B: AsRef<[u8]>,
+    {
+        Self::merkle_proof_indexed(leaves, leaf_index, |_, leaf| to_bytes(leaf))
+    }
+
+    /// Like [`verify_proof`](Self::verify_proof), serializing `leaf_content`
+    /// with `to_bytes` before hashing it.
+    pub fn verify_proof_by<T, B>(
+        root: impl AsRef<str>,
+        proof: &MerkleProof<T>,
+        to_bytes: impl Fn(&T) -> B,
+    ) -> Result<(), VerifyError>
+    where
+        B: AsRef<[u8]>,
+    {
+        Self::verify_path(root.as_ref(), proof, &TreeOptions::default(), |leaf| {
+            Some(Self::hash_leaf(to_bytes(leaf)))
+        })
+    }
+
+    /// Like [`merkle_root_by`](Self::merkle_root_by), passing `transform` the
+    /// index of each leaf along with the leaf.
+    pub fn merkle_root_indexed<I, T, B>(leaves: I, transform: impl Fn(usize, &T) -> B) -> String
+    where
+        I: Iterator<Item = T>,
+        B: AsRef<[u8]>,
+    {
+        Self::merkle_root(
+            leaves
+                .enumerate()
+                .map(|(index, leaf)| transform(index, &leaf)),
+        )
+    }
+
+    /// Like [`merkle_proof_by`](Self::merkle_proof_by), passing `transform`
+    /// the index of each leaf along with the leaf.
+    pub fn merkle_proof_indexed<I, T, B>(
+        leaves: I,
+        leaf_index: usize,
+        transform: impl Fn(usize, &T) -> B,
+    ) -> Result<MerkleProof<T>, ProofError>
+    where
+        I: Iterator<Item = T>,
+        B: AsRef<[u8]>,
+    {
+        let mut leaves: Vec<T> = leaves.collect();
+        let tree = MerkleTreeBuilder::<B, D>::new(
+            leaves
+                .iter()
+                .enumerate()
+                .map(|(index, leaf)| transform(index, leaf)),
+        );
+        Ok(MerkleProof {
+            hashes: tree.proof_hashes(leaf_index)?,
+            num_of_leaves: leaves.len(),
+            leaf_index,
+            path_bits: proof_directions(leaf_index, leaves.len()),
+            leaf_content: leaves.swap_remove(leaf_index),
+            root: None,
+        })
+    }
+
+    /// Like [`verify_proof_by`](Self::verify_proof_by), passing `transform`
+    /// the proof's `leaf_index` along with `leaf_content`.
+    pub fn verify_proof_indexed<T, B>(
+        root: impl AsRef<str>,
+        proof: &MerkleProof<T>,
+        transform: impl Fn(usize, &T) -> B,
+    ) -> Result<(), VerifyError>
+    where
+        B: AsRef<[u8]>,
+    {
+        Self::verify_proof_by(root, proof, |leaf| transform(proof.leaf_index, leaf))
+    }
+
+    /// Computes the hex-encoded root over `(key, value)` entries, each hashed
+    /// in the encoding described in the module docs.
+    pub fn merkle_root_kv<I, K, V>(entries: I) -> String
+    where
+        I: Iterator<Item = (K, V)>,
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        Self::merkle_root_by(entries, kv_bytes)
+    }
+
+    /// Builds the inclusion proof for the entry at `leaf_index`. The proof
+    /// carries both the key and the value as `leaf_content`.
+    pub fn merkle_proof_kv<I, K, V>(
+        entries: I,
+        leaf_index: usize,
+    ) -> Result<MerkleProof<(K, V)>, ProofError>
+    where
+        I: Iterator<Item = (K, V)>,
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        Self::merkle_proof_by(entries, leaf_index, kv_bytes)
+    }
+
+    /// Checks that `proof` is for an entry under `expected_key` and that the
+    /// entry belongs to the tree with `root`.
+    pub fn verify_proof_kv<K, V>(
+        root: impl AsRef<str>,
+        proof: &MerkleProof<(K, V)>,
+        expected_key: impl AsRef<[u8]>,
+    ) -> Result<(), VerifyError>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        if proof.leaf_content.0.as_ref() != expected_key.as_ref() {
+            return Err(VerifyError::KeyMismatch);
+        }
+        Self::verify_proof_by(root, proof, kv_bytes)
+    }
+}
+
+/// The leaf bytes of a key-value entry: `len(key) as u64 LE || key || value`.
+fn kv_bytes<K: AsRef<[u8]>, V: AsRef<[u8]>>((key, value): &(K, V)) -> Vec<u8> {
+    let (key, value) = (key.as_ref(), value.as_ref());
+    let mut bytes = Vec::with_capacity(8 + key.len() + value.len());
+    bytes.extend_from_slice(&(key.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(key);
+    bytes.extend_from_slice(value);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Blake2bTree;
+
+    #[derive(Debug, PartialEq)]
+    struct Account {
+        id: u32,
+        balance: u64,
+    }
+
+    fn account_bytes(account: &Account) -> [u8; 12] {
+        let mut bytes = [0; 12];
+        bytes[..4].copy_from_slice(&account.id.to_be_bytes());
+        bytes[4..].copy_from_slice(&account.balance.to_be_bytes());
+        bytes
+    }
+
+    fn accounts() -> impl Iterator<Item = Account> {
+        (0..7).map(|id| Account {
+            id,
+            balance: u64::from(id) * 100,
+        })
+    }
+
+    /// A toy commitment `H(blinding(index) || leaf)`, with the blinding
+    /// derived from a secret seed and the leaf's index.
+    fn commit(index: usize, leaf: &&str) -> Vec<u8> {
+        let blinding = blake2::Blake2b512::new()
+            .chain_update(b"seed")
+            .chain_update((index as u64).to_le_bytes())
+            .finalize();
+        blake2::Blake2b512::new()
+            .chain_update(blinding)
+            .chain_update(leaf)
+            .finalize()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_blinded_leaves() {
+        let leaves = ["alice", "bob", "carol", "bob", "dave"];
+        let root = Blake2bTree::merkle_root_indexed(leaves.into_iter(), commit);
+        assert_ne!(root, Blake2bTree::merkle_root(leaves.iter()));
+
+        for i in 0..leaves.len() {
+            let proof = Blake2bTree::merkle_proof_indexed(leaves.into_iter(), i, commit).unwrap();
+            assert_eq!(proof.leaf_content, leaves[i]);
+            assert!(Blake2bTree::verify_proof_indexed(&root, &proof, commit).is_ok());
+            assert_eq!(
+                Blake2bTree::verify_proof_by(&root, &proof, |leaf| leaf.as_bytes().to_vec()),
+                Err(VerifyError::RootMismatch)
+            );
+        }
+
+        // The same leaf commits differently at another index.
+        assert_ne!(commit(1, &leaves[1]), commit(3, &leaves[3]));
+    }
+
+    #[test]
+    fn test_struct_leaves() {
+        let root = Blake2bTree::merkle_root_by(accounts(), account_bytes);
+        assert_eq!(
+            root,
+            Blake2bTree::merkle_root(accounts().map(|a| account_bytes(&a)))
+        );
+
+        for i in 0..7 {
+            let proof = Blake2bTree::merkle_proof_by(accounts(), i, account_bytes).unwrap();
+            assert_eq!(
+                proof.leaf_content,
+                Account {
+                    id: i as u32,
+                    balance: i as u64 * 100
+                }
+            );
+            assert!(Blake2bTree::verify_proof_by(&root, &proof, account_bytes).is_ok());
+        }
+    }
+
+    const ENTRIES: [(&str, &str); 4] = [
+        ("alice", "10"),
+        ("bob", "25"),
+        ("carol", "7"),
+        ("dave", "0"),
+    ];
+
+    #[test]
+    fn test_key_value_proofs() {
+        let root = Blake2bTree::merkle_root_kv(ENTRIES.into_iter());
+        for (i, (key, value)) in ENTRIES.into_iter().enumerate() {
+            let proof = Blake2bTree::merkle_proof_kv(ENTRIES.into_iter(), i).unwrap();
+            assert_eq!(proof.leaf_content, (key, value));
+            assert_eq!(Blake2bTree::verify_proof_kv(&root, &proof, key), Ok(()));
+        }
+
+        let proof = Blake2bTree::merkle_proof_kv(ENTRIES.into_iter(), 1).unwrap();
+        assert_eq!(
+            Blake2bTree::verify_proof_kv(&root, &proof, "carol"),
+            Err(VerifyError::KeyMismatch)
+        );
+        let mut forged = proof.clone();
+        forged.leaf_content.1 = "2500";
+        assert_eq!(
+            Blake2bTree::verify_proof_kv(&root, &forged, "bob"),
+            Err(VerifyError::RootMismatch)
+        );
+    }
+
+    #[test]
+    fn test_key_boundary_is_committed() {
+        // Without the length prefix both entries would hash "ab" || "c".
+        let entries = [("ab", "c"), ("a", "bc")];
+        let root = Blake2bTree::merkle_root_kv(entries.into_iter());
+        let mut proof = Blake2bTree::merkle_proof_kv(entries.into_iter(), 0).unwrap();
+        proof.leaf_content = ("a", "bc");
+        assert_eq!(
+            Blake2bTree::verify_proof_kv(&root, &proof, "a"),
+            Err(VerifyError::RootMismatch)
+        );
+    }
+
+    #[test]
+    fn test_serialization_must_match() {
+        let root = Blake2bTree::merkle_root_by(accounts(), account_bytes);
+        let mut proof = Blake2bTree::merkle_proof_by(accounts(), 3, account_bytes).unwrap();
+
+        // Serializing only one field gives a different leaf hash.
+        let id_only = |account: &Account| account.id.to_be_bytes();
+        assert_eq!(
+            Blake2bTree::verify_proof_by(&root, &proof, id_only),
+            Err(VerifyError::RootMismatch)
+        );
+
+        proof.leaf_content.balance += 1;
+        assert_eq!(
+            Blake2bTree::verify_proof_by(&root, &proof, account_bytes),
+            Err(VerifyError::RootMismatch)
+        );
+        assert_eq!(
+            Blake2bTree::merkle_proof_by(accounts(), 7, account_bytes).unwrap_err(),
+            ProofError::IndexOutOfRange { index: 7, len: 7 }
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! Proof verification that consumes sibling hashes one at a time.
+//!
+//! A [`ProofVerifier`] folds each sibling into the running hash as it
+//! arrives, so a proof received over the network never has to be buffered.
+//! The result is the same as [`MerkleTree::verify_proof`] on the whole proof.
+
+use core::marker::PhantomData;
+
+use blake2::{Blake2b512, Digest};
+
+use crate::{Hash, MerkleTree, PLAIN};
+
+/// An inclusion proof being checked sibling by sibling, bottom level first.
+pub struct ProofVerifier<D = Blake2b512> {
+    hash: Hash,
+    index: usize,
+    len: usize,
+    valid: bool,
+    _digest: PhantomData<D>,
+}
+
+impl<D: Digest> ProofVerifier<D> {
+    /// Starts checking a proof for `leaf_content` at `leaf_index` in a tree
+    /// of `num_of_leaves`. The leaf count says which levels have no sibling
+    /// to wait for, as in [`proof_length`](crate::proof_length).
+    pub fn start(leaf_content: impl AsRef<[u8]>, leaf_index: usize, num_of_leaves: usize) -> Self {
+        ProofVerifier {
+            hash: MerkleTree::<D>::hash_leaf(leaf_content),
+            index: leaf_index,
+            len: num_of_leaves,
+            valid: leaf_index < num_of_leaves,
+            _digest: PhantomData,
+        }
+    }
+
+    /// Folds in the next sibling hash. Feeding more siblings than the path
+    /// has makes [`finish`](Self::finish) fail.
+    pub fn feed(&mut self, sibling: &Hash) {
+        self.skip_lone_levels();
+        if self.len <= 1 {
+            self.valid = false;
+            return;
+        }
+        self.hash = if self.index.is_multiple_of(2) {
+            MerkleTree::<D>::hash_pair(&PLAIN, &self.hash, sibling)
+        } else {
+            MerkleTree::<D>::hash_pair(&PLAIN, sibling, &self.hash)
+        };
+        self.climb();
+    }
+
+    /// Whether every sibling arrived and the path climbed to the hex-encoded
+    /// `root`.
+    pub fn finish(mut self, root: impl AsRef<str>) -> bool {
+        self.skip_lone_levels();
+        self.valid && self.len <= 1 && root.as_ref() == self.hash.to_hex()
+    }
+
+    /// Promotes the running hash past levels where it is a trailing odd
+    /// node, which have no sibling in the proof.
+    fn skip_lone_levels(&mut self) {
+        while self.len > 1 && self.index ^ 1 >= self.len {
+            self.climb();
+        }
+    }
+
+    fn climb(&mut self) {
+        self.index /= 2;
+        self.len = self.len.div_ceil(2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Blake2bTree;
+
+    fn stream(proof: &crate::MerkleProof<&String>, hashes: &[Hash]) -> ProofVerifier {
+        let mut verifier =
+            ProofVerifier::start(proof.leaf_content, proof.leaf_index, proof.num_of_leaves);
+        for sibling in hashes {
+            verifier.feed(sibling);
+        }
+        verifier
+    }
+
+    #[test]
+    fn test_streaming_matches_batch() {
+        for n in [1, 2, 5, 8, 13] {
+            let data: Vec<String> = (0..n).map(|i| format!("leaf-{i}")).collect();
+            let root = Blake2bTree::merkle_root(data.iter());
+            let other_root = Blake2bTree::merkle_root(data[..n - 1].iter());
+            for i in 0..n {
+                let proof = Blake2bTree::merkle_proof(data.iter(), i).unwrap();
+                assert!(stream(&proof, &proof.hashes).finish(&root));
+                assert_eq!(
+                    stream(&proof, &proof.hashes).finish(&other_root),
+                    Blake2bTree::verify_proof(&other_root, &proof).is_ok()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_wrong_sibling_count_fails() {
+        let data: Vec<String> = (0..5).map(|i| format!("leaf-{i}")).collect();
+        let root = Blake2bTree::merkle_root(data.iter());
+        let proof = Blake2bTree::merkle_proof(data.iter(), 1).unwrap();
+
+        assert!(!stream(&proof, &proof.hashes[..2]).finish(&root));
+        let mut extra = proof.hashes.clone();
+        extra.push(proof.hashes[0]);
+        assert!(!stream(&proof, &extra).finish(&root));
+
+        let verifier = ProofVerifier::<Blake2b512>::start("leaf-0", 5, 5);
+        assert!(!verifier.finish(&root));
+    }
+}
@@ -0,0 +1,50 @@
+//! Counts heap allocations to check that building a root allocates per
+//! level, not per leaf.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use merkle::Blake2bTree;
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Allocations made on this thread by computing the root of `leaves`.
+fn root_allocations(leaves: &[[u8; 8]]) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    let root = Blake2bTree::merkle_root(leaves.iter());
+    let after = ALLOCATIONS.with(Cell::get);
+    assert_eq!(root.len(), 128);
+    after - before
+}
+
+#[test]
+fn test_leaf_pass_allocates_per_level() {
+    let leaves: Vec<[u8; 8]> = (0..1u64 << 16).map(u64::to_le_bytes).collect();
+    // A few allocations per level (the level itself and the growth of the
+    // list of levels) plus a constant: nothing scales with the leaf count.
+    for (n, levels) in [(1 << 4, 5), (1 << 16, 17)] {
+        let allocations = root_allocations(&leaves[..n]);
+        assert!(
+            allocations <= 3 + 2 * levels,
+            "{allocations} allocations for {n} leaves"
+        );
+    }
+}
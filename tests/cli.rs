@@ -0,0 +1,91 @@
+#![cfg(feature = "cli")]
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+use merkle::{Blake2bTree, MerkleProof};
+
+const LEAVES: [&str; 5] = ["abc", "bcd", "cde", "def", "efg"];
+
+fn merkle(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_merkle"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout_json(output: &Output) -> serde_json::Value {
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+/// Writes `contents` to a file in the temp directory, named per process.
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("merkle-cli-{}-{name}", std::process::id()));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn test_root_from_file_and_stdin() {
+    let expected = Blake2bTree::merkle_root(LEAVES.iter());
+    let path = temp_file("root.txt", "abc\nbcd\n\ncde\r\ndef\nefg");
+
+    let from_file = merkle(&["root", path.to_str().unwrap()], "");
+    assert!(from_file.status.success());
+    assert_eq!(stdout_json(&from_file)["root"], expected.as_str());
+
+    let from_stdin = merkle(&["root", "-"], "abc\nbcd\ncde\ndef\nefg\n");
+    assert_eq!(stdout_json(&from_stdin)["root"], expected.as_str());
+    assert_eq!(
+        stdout_json(&merkle(&["root"], "abc\nbcd\ncde\ndef\nefg\n"))["root"],
+        expected.as_str()
+    );
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_proof_then_verify() {
+    let root = Blake2bTree::merkle_root(LEAVES.iter());
+    let path = temp_file("proof.txt", &LEAVES.join("\n"));
+
+    let output = merkle(&["proof", path.to_str().unwrap(), "3"], "");
+    assert!(output.status.success());
+    let proof: MerkleProof<String> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(proof.leaf_content, "def");
+    assert!(Blake2bTree::verify_proof(&root, &proof).is_ok());
+
+    let proof_json = String::from_utf8(output.stdout).unwrap();
+    let valid = merkle(&["verify", &root], &proof_json);
+    assert!(valid.status.success());
+    assert_eq!(stdout_json(&valid)["valid"], true);
+
+    let other_root = Blake2bTree::merkle_root(LEAVES[..4].iter());
+    let invalid = merkle(&["verify", &other_root, "-"], &proof_json);
+    assert_eq!(invalid.status.code(), Some(1));
+    assert_eq!(stdout_json(&invalid)["valid"], false);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_usage_errors() {
+    for args in [&[][..], &["hash"], &["proof", "-", "x"], &["verify"]] {
+        let output = merkle(args, "");
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+        assert!(String::from_utf8_lossy(&output.stderr).contains("usage:"));
+    }
+
+    let output = merkle(&["proof", "-", "5"], &LEAVES.join("\n"));
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+}
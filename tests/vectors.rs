@@ -0,0 +1,116 @@
+//! Known-answer vectors pinning the hashing scheme, as a reference for
+//! implementations in other languages. Every value here was computed
+//! independently of this crate from the scheme described in the README:
+//!
+//! - leaf hash: `H(0x00 || leaf)`,
+//! - node hash: `H(0x01 || hex(left) || hex(right))` with lowercase hex
+//!   ([`NodeEncoding::Hex`], the default) or `H(0x01 || left || right)` over
+//!   the raw bytes ([`NodeEncoding::Raw`]),
+//! - a trailing odd node is promoted to the next level unchanged,
+//! - roots and proof hashes are written as lowercase hex.
+//!
+//! If one of these tests fails, roots computed by earlier versions no longer
+//! verify: fix the code, not the vector.
+
+use merkle::{Blake2bTree, MerkleTree, NodeEncoding, TreeOptions};
+use sha2::Sha256;
+
+const LEAVES: [&str; 5] = ["a", "b", "c", "d", "e"];
+
+/// Hex encodings of a root and of the sibling hashes of the proofs for leaves
+/// 2 and 4, bottom level first.
+struct Vector {
+    root: &'static str,
+    proof_2: &'static [&'static str],
+    proof_4: &'static [&'static str],
+}
+
+const BLAKE2B_HEX: Vector = Vector {
+    root: "b6e6178307582518d07fc7433472d7876338fb1b756c47319360b89d05533cd2\
+           b0a842416db0b0b5e7833fc0a40880c2d5754bd74e61c4d6abf9e9bc1aa0ae68",
+    proof_2: &[
+        "4fd1975466076941485edcba43262fc700a104520a9c295ca89a41df21d0c97e\
+         98be897537cbb13ffa915b2fd62e26269418318ceac9e1591d75ab29130a5416",
+        "a1c676a5174d4664081bfd4e97510e3b88f40ad84e3867dba611e5d7776b4274\
+         feda19e0b402d4f463d74223f6ff09087a48e6385b77d4bfe1392b83dcbf2883",
+        "4847cd1465a0e16d1349cdf79c790b4bee53fdc45453f3919f572ccd3c3f5cc8\
+         eab8465f582cd2e940728824ca6ad3bc4c6b20303f9d248445f8d23e0d06e9b2",
+    ],
+    proof_4: &[
+        "bb54919c752f600ffedb22579d7e27fc81cc51a1fcfa1b3fe75fefe73210c924\
+         d2bb417d9ff4286c52912e457568caa1d171ab651ea48e49d15697b9c80ce6bb",
+    ],
+};
+
+const BLAKE2B_RAW: Vector = Vector {
+    root: "9b46bb43f208a906ee650b77436ae5eedc84105b2579f5547bf3736e595e8baf\
+           6363f787ab6c296eb2c401c22b0277fbe8050ace2a89a7d48f884c2fff5a3c3a",
+    proof_2: &[
+        "4fd1975466076941485edcba43262fc700a104520a9c295ca89a41df21d0c97e\
+         98be897537cbb13ffa915b2fd62e26269418318ceac9e1591d75ab29130a5416",
+        "b90869844959bd2f5bbc5c786558fd1e2cad6d7d0017d3124fef8204ffa2281a\
+         8644a8dff5fa3db1c296661195032cda62f5551ae7ed7fc78d7096946150867a",
+        "4847cd1465a0e16d1349cdf79c790b4bee53fdc45453f3919f572ccd3c3f5cc8\
+         eab8465f582cd2e940728824ca6ad3bc4c6b20303f9d248445f8d23e0d06e9b2",
+    ],
+    proof_4: &[
+        "ac6ede08db2c1a6a88c716820fd72f5fa339f625d67f04353e6a8adc0310d088\
+         7ba25fe9fca6baa38e5a24c24abb47308a8b2ea985fb9dd25d5b7f3a70351732",
+    ],
+};
+
+const SHA256_HEX: Vector = Vector {
+    root: "c9481669d120766d583b0e0980a42a8b75f9535b3630d95626550bdc70c9d19d",
+    proof_2: &[
+        "d070dc5b8da9aea7dc0f5ad4c29d89965200059c9a0ceca3abd5da2492dcb71d",
+        "4c64254e6636add7f281ff49278beceb26378bd0021d1809974994e6e233ec35",
+        "2824a7ccda2caa720c85c9fba1e8b5b735eecfdb03878e4f8dfe6c3625030bc4",
+    ],
+    proof_4: &["9dc1674ae1ee61c90ba50b6261e8f9a47f7ea07d92612158edfe3c2a37c6d74c"],
+};
+
+fn check<D: sha2::Digest>(vector: &Vector, options: &TreeOptions) {
+    let root = MerkleTree::<D>::merkle_root_with(LEAVES.iter(), options);
+    assert_eq!(root, vector.root);
+
+    for (index, expected) in [(2, vector.proof_2), (4, vector.proof_4)] {
+        let proof = MerkleTree::<D>::merkle_proof_with(LEAVES.iter(), index, options).unwrap();
+        let hashes: Vec<String> = proof.hashes.iter().map(|hash| hash.to_hex()).collect();
+        assert_eq!(hashes, expected, "proof of leaf {index}");
+        assert!(MerkleTree::<D>::verify_proof_with(vector.root, &proof, options).is_ok());
+    }
+}
+
+#[test]
+fn test_blake2b_hex_vector() {
+    check::<blake2::Blake2b512>(&BLAKE2B_HEX, &TreeOptions::default());
+    assert_eq!(Blake2bTree::merkle_root(LEAVES.iter()), BLAKE2B_HEX.root);
+}
+
+#[test]
+fn test_blake2b_raw_vector() {
+    let raw = TreeOptions {
+        node_encoding: NodeEncoding::Raw,
+        ..TreeOptions::default()
+    };
+    check::<blake2::Blake2b512>(&BLAKE2B_RAW, &raw);
+}
+
+#[test]
+fn test_sha256_hex_vector() {
+    check::<Sha256>(&SHA256_HEX, &TreeOptions::default());
+}
+
+#[test]
+fn test_leaf_and_empty_vectors() {
+    assert_eq!(
+        Blake2bTree::hash_leaf("a").to_hex(),
+        "27fd241f10a0145111c137d684025960df4a37f82769b976c43a918bea676ab9\
+         39c65a19dac3796282d42f6b9c08fb534dc432a948084f8764608cc259bd75ee"
+    );
+    assert_eq!(
+        Blake2bTree::empty_root(),
+        "786a02f742015903c6c6fd852552d272912f4740e15847618a86e217f71f5419\
+         d25e1031afee585313896444934eb04b903a685b1448b755d56f701afe9be2ce"
+    );
+}